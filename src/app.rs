@@ -13,6 +13,7 @@ use cgmath::{EuclideanSpace, Point2, Vector2};
 use egui::{Button, CursorIcon, Event, Modifiers, RichText, Style};
 use image::{ColorType, DynamicImage, imageops::FilterType};
 use num_traits::Zero;
+use simp::io::ToneMapOperator;
 use winit::{
     event::WindowEvent, event_loop::EventLoopProxy, keyboard::ModifiersState, window::Fullscreen,
 };
@@ -25,16 +26,21 @@ use crate::{
 };
 
 pub mod image_view;
-use image_view::ImageView;
+use image_view::{ImageView, LoopMode, SPEEDS};
 
 pub mod image_list;
 
 mod clipboard;
 
 mod color;
+mod exposure;
+mod exr_layers;
+mod frame_editor;
 mod help;
 mod menu_bar;
 mod metadata;
+mod psd_layers;
+mod raw_development;
 
 pub mod op_queue;
 use op_queue::{Op, OpQueue, Output};
@@ -47,7 +53,18 @@ mod undo_stack;
 
 mod cache;
 
-mod resize;
+mod filmstrip;
+use filmstrip::{FILMSTRIP_SIZE, Filmstrip};
+
+mod gallery;
+use gallery::Gallery;
+
+mod prefetch;
+
+mod thumbnails;
+
+pub mod resize;
+use frame_editor::FrameEditor;
 use resize::Resize;
 
 pub mod preferences;
@@ -58,7 +75,7 @@ use self::{
     dialog_manager::{DialogManager, DialogProxy},
     image_view::{crop_renderer, image_renderer},
     preferences::PREFERENCES,
-    undo_stack::UndoFrame,
+    undo_stack::{Snapshot, UndoFrame},
 };
 
 enum ResizeMode {
@@ -87,12 +104,21 @@ pub struct App {
     current_filename: String,
     op_queue: OpQueue,
     resize: Resize,
+    frame_editor: FrameEditor,
+    gallery: Gallery,
+    filmstrip: Filmstrip,
     resize_mode: ResizeMode,
     help_visible: bool,
     color_visible: bool,
     color_space_visible: bool,
+    exposure_visible: bool,
+    exr_layers_visible: bool,
+    psd_layers_visible: bool,
+    raw_visible: bool,
     metadata_visible: bool,
     preferences_visible: bool,
+    /// The monitor profile path while it is being typed, it is applied once editing ends.
+    monitor_profile_edit: Option<String>,
     enter: bool,
     zen_mode: bool,
 }
@@ -102,13 +128,41 @@ impl App {
         !self.op_queue.working() && self.image_view.is_some()
     }
 
+    /// The image is hidden while the gallery is shown.
+    pub fn gallery_visible(&self) -> bool {
+        self.gallery.visible
+    }
+
     pub fn handle_output(&mut self, wgpu: &WgpuState, output: Output) {
         let stack = self.op_queue.undo_stack_mut();
         match output {
             Output::ImageLoaded(image_data, path) => {
-                stack.clear();
+                // A full image replaces its preview, like a developed raw file or one that
+                // only fit the cache downscaled, at the same size on screen. Previews can only
+                // be rotated and flipped so those are kept along with the undo stack.
+                let replaces_preview = self.image_view.as_ref().filter(|view| {
+                    !image_data.is_preview()
+                        && view.path == path
+                        && view.image_data.read().unwrap().is_preview()
+                });
+                let previous =
+                    replaces_preview.map(|view| (view.size.x * view.scale, view.position));
+                let orientation = replaces_preview
+                    .map(|view| (view.rotation(), view.horizontal_flip, view.vertical_flip));
+
+                if previous.is_none() {
+                    stack.clear();
+                }
                 self.current_filename = if let Some(path) = &path {
                     self.op_queue.image_list.change_dir(path);
+                    let preferences = PREFERENCES.lock().unwrap();
+                    if preferences.save_thumbnails {
+                        self.op_queue.thumbnailer.request(
+                            path.clone(),
+                            preferences.thumbnail_size,
+                            true,
+                        );
+                    }
                     path.file_name().unwrap().to_str().unwrap().to_string()
                 } else {
                     String::new()
@@ -116,7 +170,17 @@ impl App {
 
                 self.color_type = image_data.frames[0].buffer().color();
 
-                let view = Box::new(ImageView::new(wgpu, image_data, path));
+                let mut view = Box::new(ImageView::new(wgpu, image_data, path));
+                // Playback settings stay while browsing through animations.
+                if let Some(old) = &self.image_view {
+                    view.speed = old.speed;
+                    view.loop_mode = old.loop_mode;
+                }
+                if let Some((rotation, horizontal_flip, vertical_flip)) = orientation {
+                    view.set_rotation(rotation);
+                    view.horizontal_flip = horizontal_flip;
+                    view.vertical_flip = vertical_flip;
+                }
                 self.resize
                     .set_size(Vector2::new(view.size.x as u32, view.size.y as u32));
                 self.image_view = Some(view);
@@ -128,7 +192,14 @@ impl App {
                 }
 
                 self.set_bar_size(wgpu);
-                self.largest_fit();
+                match previous {
+                    Some((width, position)) => {
+                        let view = self.image_view.as_mut().unwrap();
+                        view.scale = width / view.size.x;
+                        view.position = position;
+                    }
+                    None => self.largest_fit(),
+                }
             }
             Output::FlipHorizontal => {
                 let view = self.image_view.as_mut().unwrap();
@@ -154,24 +225,27 @@ impl App {
                 self.image_view.as_mut().unwrap().rotate(dir);
                 stack.push(UndoFrame::Rotate(dir));
             }
-            Output::Resize(mut frames) => {
+            Output::Resize(frames) => {
                 if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
                     view.swap_frames(wgpu, &mut frames);
                     stack.push(UndoFrame::Resize(frames));
                 }
             }
-            Output::ColorSpace(mut frames) => {
+            Output::ColorSpace(frames) => {
                 if let Some(ref mut view) = self.image_view {
                     let new = frames[0].buffer().color();
                     let old = view.image_data.read().unwrap().frames[0].buffer().color();
                     if new != old {
+                        let mut frames = Snapshot::from(frames);
                         view.swap_frames(wgpu, &mut frames);
                         stack.push(UndoFrame::ColorSpace(frames));
                     }
                 }
             }
-            Output::Color(mut frames) => {
+            Output::Color(frames) => {
                 if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
                     view.swap_frames(wgpu, &mut frames);
                     stack.push(UndoFrame::Color(frames));
                     view.hue = 0.0;
@@ -183,13 +257,69 @@ impl App {
                     wgpu.window.request_redraw();
                 }
             }
-            Output::Crop(mut frames, rotation) => {
+            Output::ToneMap(frames) => {
+                if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
+                    view.swap_frames(wgpu, &mut frames);
+                    stack.push(UndoFrame::Color(frames));
+                    view.exposure = 0.0;
+                    view.gamma = 1.0;
+                    view.tonemap = ToneMapOperator::Clamp;
+                    wgpu.window.request_redraw();
+                }
+            }
+            Output::ExrLayer(frames, mut selection) => {
+                if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
+                    mem::swap(&mut view.exr_selection, &mut selection);
+                    view.swap_frames(wgpu, &mut frames);
+                    stack.push(UndoFrame::ExrLayer { frames, selection });
+                }
+            }
+            Output::Page(frames, mut page) => {
+                if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
+                    view.store_page();
+                    view.swap_frames(wgpu, &mut frames);
+                    mem::swap(&mut view.page, &mut page);
+                    stack.push(UndoFrame::Page { frames, page });
+                }
+            }
+            Output::PsdVisibility(frames, mut visibility) => {
+                if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
+                    view.swap_frames(wgpu, &mut frames);
+                    mem::swap(&mut view.psd_visibility, &mut visibility);
+                    stack.push(UndoFrame::PsdVisibility { frames, visibility });
+                }
+            }
+            Output::RawDevelop(frames, mut settings) => {
+                if let Some(ref mut view) = self.image_view {
+                    let mut frames = Snapshot::from(frames);
+                    view.swap_frames(wgpu, &mut frames);
+                    mem::swap(&mut view.raw_settings, &mut settings);
+                    view.raw_draft = view.raw_settings;
+                    view.store_raw_settings();
+                    self.color_type = view.image_data.read().unwrap().frames[0].buffer().color();
+                    stack.push(UndoFrame::RawDevelop { frames, settings });
+                }
+            }
+            Output::Crop(frames, rotation) => {
                 if let Some(ref mut view) = self.image_view {
                     view.set_rotation(0);
+                    let mut frames = Snapshot::from(frames);
                     view.swap_frames(wgpu, &mut frames);
                     stack.push(UndoFrame::Crop { frames, rotation })
                 }
             }
+            Output::Frames { frames, mut index } => {
+                if let Some(ref mut view) = self.image_view {
+                    mem::swap(&mut view.index, &mut index);
+                    let mut frames = Snapshot::from(frames);
+                    view.swap_frames(wgpu, &mut frames);
+                    stack.push(UndoFrame::Frames { frames, index });
+                }
+            }
             Output::Undo => {
                 let frame = stack.undo();
                 if let Some(frame) = frame {
@@ -222,6 +352,36 @@ impl App {
                             self.color_type =
                                 view.image_data.read().unwrap().frames[0].buffer().color();
                         }
+                        UndoFrame::ExrLayer { frames, selection } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            mem::swap(&mut view.exr_selection, selection);
+                            view.swap_frames(wgpu, frames);
+                        }
+                        UndoFrame::Page { frames, page } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.store_page();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.page, page);
+                        }
+                        UndoFrame::PsdVisibility { frames, visibility } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.psd_visibility, visibility);
+                        }
+                        UndoFrame::RawDevelop { frames, settings } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.raw_settings, settings);
+                            view.raw_draft = view.raw_settings;
+                            view.store_raw_settings();
+                            self.color_type =
+                                view.image_data.read().unwrap().frames[0].buffer().color();
+                        }
+                        UndoFrame::Frames { frames, index } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            mem::swap(&mut view.index, index);
+                            view.swap_frames(wgpu, frames);
+                        }
                     }
                 }
             }
@@ -257,6 +417,36 @@ impl App {
                             self.color_type =
                                 view.image_data.read().unwrap().frames[0].buffer().color();
                         }
+                        UndoFrame::ExrLayer { frames, selection } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            mem::swap(&mut view.exr_selection, selection);
+                            view.swap_frames(wgpu, frames);
+                        }
+                        UndoFrame::Page { frames, page } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.store_page();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.page, page);
+                        }
+                        UndoFrame::PsdVisibility { frames, visibility } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.psd_visibility, visibility);
+                        }
+                        UndoFrame::RawDevelop { frames, settings } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            view.swap_frames(wgpu, frames);
+                            mem::swap(&mut view.raw_settings, settings);
+                            view.raw_draft = view.raw_settings;
+                            view.store_raw_settings();
+                            self.color_type =
+                                view.image_data.read().unwrap().frames[0].buffer().color();
+                        }
+                        UndoFrame::Frames { frames, index } => {
+                            let view = self.image_view.as_mut().unwrap();
+                            mem::swap(&mut view.index, index);
+                            view.swap_frames(wgpu, frames);
+                        }
                     }
                 }
             }
@@ -286,8 +476,17 @@ impl App {
             UserEvent::QueueSave(path) => {
                 self.queue(Op::Save(path.to_path_buf()));
             }
-            UserEvent::QueueDelete(path) => {
-                self.queue(Op::Delete(path.to_path_buf()));
+            UserEvent::QueueSavePages(path) => {
+                self.queue(Op::SavePages(path.to_path_buf()));
+            }
+            UserEvent::QueueExportSvg(path) => {
+                self.queue(Op::ExportSvg(path.to_path_buf()));
+            }
+            UserEvent::QueueExtractFrame(path, index) => {
+                self.queue(Op::ExtractFrame(path.to_path_buf(), *index));
+            }
+            UserEvent::QueueDelete(paths) => {
+                self.queue(Op::Delete(mem::take(paths)));
             }
             UserEvent::ErrorMessage(error) => {
                 let error = error.clone();
@@ -352,6 +551,17 @@ impl App {
                     self.handle_output(wgpu, output);
                 }
             }
+            UserEvent::SvgDetail(detail) => {
+                if let Some(detail) = detail.take()
+                    && let Some(view) = self.image_view.as_mut()
+                {
+                    view.set_detail(wgpu, detail);
+                }
+            }
+            UserEvent::Thumbnail(path, size, thumbnail) => {
+                self.gallery.thumbnails.receive(path, *size, thumbnail);
+                self.filmstrip.thumbnails.receive(path, *size, thumbnail);
+            }
             UserEvent::RepaintRequest(request_repaint_info) => {
                 self.delay = self.delay.min(request_repaint_info.delay);
             }
@@ -382,6 +592,9 @@ impl App {
             {
                 self.bottom_bar_size += BOTTOM_BAR_SIZE * wgpu.scale_factor as f32;
             }
+            if self.filmstrip_visible() {
+                self.bottom_bar_size += FILMSTRIP_SIZE * wgpu.scale_factor as f32;
+            }
         } else {
             self.top_bar_size = 0.0;
             self.bottom_bar_size = 0.0;
@@ -395,18 +608,24 @@ impl App {
         }
 
         self.resize_ui(ui);
-        self.preferences_ui(ui);
+        self.preferences_ui(wgpu, ui);
         self.help_ui(ui);
         self.color_ui(ui);
         self.color_space_ui(ui);
+        self.exposure_ui(ui);
+        self.raw_development_ui(ui);
         self.metadata_ui(ui);
         self.crop_ui(ui);
+        self.frame_editor_ui(wgpu, ui);
 
         if wgpu.window.fullscreen().is_none() && !self.zen_mode {
             self.menu_bar(wgpu, ui);
             self.bottom_bar(ui);
+            self.filmstrip(ui);
             self.gif_player_bar(ui);
         }
+        self.exr_layers_ui(wgpu, ui);
+        self.psd_layers_ui(wgpu, ui);
 
         self.main_area(wgpu, ui);
 
@@ -420,8 +639,28 @@ impl App {
             if input.consume_shortcut(&KeyboardShortcut {
                 modifiers: Modifiers::NONE,
                 logical_key: Space,
-            }) {
-                todo!();
+            }) && focused
+                && let Some(ref mut view) = self.image_view
+            {
+                view.toggle_playing();
+            }
+
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: Comma,
+            }) && focused
+                && let Some(ref mut view) = self.image_view
+            {
+                view.step_frame(false);
+            }
+
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: Period,
+            }) && focused
+                && let Some(ref mut view) = self.image_view
+            {
+                view.step_frame(true);
             }
 
             if input.consume_shortcut(&KeyboardShortcut {
@@ -432,12 +671,29 @@ impl App {
                 && let Some(ref path) = view.path
             {
                 delete(
-                    path.clone(),
+                    vec![path.clone()],
                     self.dialog_manager.get_proxy(),
                     self.proxy.clone(),
                 );
             }
 
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: G,
+            }) && focused
+            {
+                self.toggle_gallery();
+            }
+
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: T,
+            }) && focused
+            {
+                let mut preferences = PREFERENCES.lock().unwrap();
+                preferences.filmstrip = !preferences.filmstrip;
+            }
+
             if input.consume_shortcut(&KeyboardShortcut {
                 modifiers: Modifiers::CTRL,
                 logical_key: I,
@@ -579,6 +835,32 @@ impl App {
                 self.queue(Op::Prev);
             }
 
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: PageDown,
+            }) && self.view_available()
+                && !self.image_view.as_ref().unwrap().cropping()
+                && focused
+            {
+                let view = self.image_view.as_ref().unwrap();
+                if view.page + 1 < view.len_pages() {
+                    self.queue(Op::Page(view.page + 1));
+                }
+            }
+
+            if input.consume_shortcut(&KeyboardShortcut {
+                modifiers: Modifiers::NONE,
+                logical_key: PageUp,
+            }) && self.view_available()
+                && !self.image_view.as_ref().unwrap().cropping()
+                && focused
+            {
+                let view = self.image_view.as_ref().unwrap();
+                if view.page > 0 {
+                    self.queue(Op::Page(view.page - 1));
+                }
+            }
+
             if input.consume_shortcut(&KeyboardShortcut {
                 modifiers: Modifiers::CTRL,
                 logical_key: W,
@@ -612,8 +894,13 @@ impl App {
                 }
                 self.help_visible = false;
                 self.color_visible = false;
+                self.exposure_visible = false;
+                self.exr_layers_visible = false;
+                self.psd_layers_visible = false;
+                self.raw_visible = false;
                 self.metadata_visible = false;
                 self.resize.visible = false;
+                self.frame_editor.visible = false;
             }
 
             if input.consume_shortcut(&KeyboardShortcut {
@@ -680,6 +967,13 @@ impl App {
     pub fn main_area(&mut self, wgpu: &WgpuState, ui: &mut egui::Ui) {
         let frame = egui::Frame::dark_canvas(&Style::default()).multiply_with_opacity(0.0);
         egui::CentralPanel::default().frame(frame).show(ui, |ui| {
+            if self.gallery.visible {
+                self.gallery_ui(ui);
+                // Only the shortcuts that do not act on the image being viewed.
+                self.handle_input(wgpu, ui, false);
+                return;
+            }
+
             if self.image_view.is_none() {
                 ui.centered_and_justified(|ui| {
                     ui.label(
@@ -700,6 +994,14 @@ impl App {
                 let has_image = self.image_view.is_some();
                 response.context_menu(|ui| {
                     context_menu_open = true;
+                    if ui
+                        .add_enabled(has_image, egui::Button::new("Gallery").shortcut_text("G"))
+                        .clicked()
+                    {
+                        self.toggle_gallery();
+                    }
+                    ui.separator();
+
                     if ui
                         .add_enabled(has_image, egui::Button::new("Next"))
                         .clicked()
@@ -765,7 +1067,7 @@ impl App {
                             && let Some(ref path) = view.path
                         {
                             delete(
-                                path.clone(),
+                                vec![path.clone()],
                                 self.dialog_manager.get_proxy(),
                                 self.proxy.clone(),
                             );
@@ -891,8 +1193,32 @@ impl App {
                             .small_button(egui::RichText::new(icon).size(11.0))
                             .clicked()
                         {
-                            view.playing = !view.playing;
+                            view.toggle_playing();
                         }
+
+                        egui::ComboBox::new("playback speed combobox", "")
+                            .selected_text(format!("{}x", view.speed))
+                            .width(60.0)
+                            .show_ui(ui, |ui| {
+                                for speed in SPEEDS {
+                                    ui.selectable_value(
+                                        &mut view.speed,
+                                        speed,
+                                        format!("{speed}x"),
+                                    );
+                                }
+                            });
+
+                        egui::ComboBox::new("loop mode combobox", "")
+                            .selected_text(view.loop_mode.as_ref())
+                            .width(80.0)
+                            .show_ui(ui, |ui| {
+                                for mode in LoopMode::ALL {
+                                    ui.selectable_value(&mut view.loop_mode, mode, mode.as_ref());
+                                }
+                            });
+
+                        ui.label(format!("{} ms", view.frame_delay().as_millis()));
                     });
 
                     ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
@@ -936,8 +1262,15 @@ impl App {
                         ui.label(self.current_filename.to_string());
                         ui.label(format!("{} x {}", image.size.x, image.size.y));
                         ui.label(format!("Zoom: {}%", (image.scale * 100.0).round()));
+                        let pages = image.len_pages();
+                        if pages > 1 {
+                            ui.label(format!("Page: {}/{}", image.page + 1, pages));
+                        }
 
                         let g = image.image_data.read();
+                        if g.as_ref().unwrap().is_preview() {
+                            ui.label("Preview");
+                        }
                         let buf = g.as_ref().unwrap().frames[0].buffer();
                         let mut color_space = color_type_to_str(buf.color()).to_string();
 
@@ -1032,7 +1365,7 @@ impl App {
             });
     }
 
-    pub fn update(&mut self, _wgpu: &WgpuState) -> (bool, Duration) {
+    pub fn update(&mut self, wgpu: &WgpuState) -> (bool, Duration) {
         self.delay = Duration::MAX;
         if let Some(ref mut image) = self.image_view {
             self.delay = self.delay.min(image.animate());
@@ -1076,9 +1409,18 @@ impl App {
             }
         }
 
+        if let Some(ref mut image) = self.image_view {
+            let window = Vector2::new(wgpu.config.width as f32, wgpu.config.height as f32);
+            image.update_detail(window, &self.proxy);
+        }
+
         self.op_queue
             .image_list
             .set_sort_order(preferences.sort_order, preferences.sort_direction);
+        self.op_queue.set_cache_budget(preferences.cache_budget());
+        self.op_queue
+            .cache
+            .set_preview_size(wgpu.config.width, wgpu.config.height);
 
         self.enter = false;
         (self.exit.load(Ordering::Relaxed), self.delay)
@@ -1450,11 +1792,19 @@ impl App {
             mouse_position: Vector2::zero(),
             current_filename: String::new(),
             resize: Resize::default(),
+            frame_editor: FrameEditor::default(),
+            gallery: Gallery::default(),
+            filmstrip: Filmstrip::default(),
             help_visible: false,
             color_visible: false,
             color_space_visible: false,
+            exposure_visible: false,
+            exr_layers_visible: false,
+            psd_layers_visible: false,
+            raw_visible: false,
             metadata_visible: false,
             preferences_visible: false,
+            monitor_profile_edit: None,
             resize_mode: ResizeMode::Original,
             enter: false,
             zen_mode,
@@ -1463,16 +1813,20 @@ impl App {
 }
 
 pub fn delete(
-    path: std::path::PathBuf,
+    paths: Vec<std::path::PathBuf>,
     dialog_proxy: DialogProxy,
     proxy: EventLoopProxy<UserEvent>,
 ) {
+    let message = match paths.len() {
+        1 => String::from("Are you sure you want to move this to trash?"),
+        n => format!("Are you sure you want to move these {n} images to trash?"),
+    };
     dialog_proxy.spawn_dialog("Move to trash", move |ui, enter| {
-        ui.label("Are you sure you want to move this to trash?");
+        ui.label(&message);
 
         ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
             if ui.button("Yes").clicked() {
-                let _ = proxy.send_event(UserEvent::QueueDelete(path.clone()));
+                let _ = proxy.send_event(UserEvent::QueueDelete(paths.clone()));
                 return Some(());
             }
 
@@ -1482,7 +1836,7 @@ pub fn delete(
 
             if *enter {
                 *enter = false;
-                let _ = proxy.send_event(UserEvent::QueueDelete(path.clone()));
+                let _ = proxy.send_event(UserEvent::QueueDelete(paths.clone()));
                 return Some(());
             }
 
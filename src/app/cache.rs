@@ -1,68 +1,129 @@
 use std::{
-    num::NonZeroUsize,
     path::PathBuf,
     sync::{
-        Arc, Mutex,
-        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
     },
 };
 
+use image::imageops::FilterType;
 use lru::LruCache;
 
-use crate::util::ImageData;
+use crate::util::{Image, ImageData};
 
+/// Decoded images kept in memory, the least recently used ones are evicted when the byte
+/// budget is exceeded.
 pub struct Cache {
     lru: Mutex<LruCache<PathBuf, Arc<ImageData>>>,
     total_size: AtomicUsize,
-    max_size: usize,
+    max_size: AtomicUsize,
+    /// Width and height of the window, images too large for the budget are scaled down
+    /// to fit it.
+    preview_size: AtomicU64,
 }
 
 impl Cache {
     pub fn new(max_size: usize) -> Cache {
         Self {
-            lru: Mutex::new(LruCache::new(NonZeroUsize::new(30).unwrap())),
+            lru: Mutex::new(LruCache::unbounded()),
             total_size: AtomicUsize::new(0),
-            max_size,
+            max_size: AtomicUsize::new(max_size),
+            preview_size: AtomicU64::new(0),
         }
     }
 
+    /// Changes the budget, evicting images until they fit it.
+    pub fn set_max_size(&self, max_size: usize) {
+        if self.max_size.swap(max_size, Ordering::SeqCst) > max_size {
+            let mut guard = self.lru.lock().unwrap();
+            self.evict(&mut guard, 0);
+        }
+    }
+
+    pub fn set_preview_size(&self, width: u32, height: u32) {
+        let size = (width as u64) << 32 | height as u64;
+        self.preview_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Stores an image, or a copy scaled down to the window if the image alone is larger
+    /// than the budget.
     pub fn put(&self, path: PathBuf, mut image: Arc<ImageData>) {
         Arc::make_mut(&mut image);
-        let size: usize = image
-            .frames
-            .iter()
-            .map(|image| image.buffer().as_bytes().len())
-            .sum();
-        if size >= self.max_size {
-            return;
+        let max_size = self.max_size.load(Ordering::SeqCst);
+        let mut size = image.memory_size();
+        if size > max_size {
+            match self.downscale(&image) {
+                Some(preview) if preview.memory_size() <= max_size => {
+                    size = preview.memory_size();
+                    image = Arc::new(preview);
+                }
+                _ => return,
+            }
         }
 
+        let path = path.canonicalize().unwrap_or(path);
         let mut guard = self.lru.lock().unwrap();
-        while size + self.total_size.load(Ordering::SeqCst) > self.max_size {
-            let removed = guard.pop_lru();
-            match removed {
-                Some((_, value)) => {
-                    let removed: usize = value
-                        .frames
-                        .iter()
-                        .map(|image| image.buffer().as_bytes().len())
-                        .sum();
-                    self.total_size.fetch_sub(removed, Ordering::SeqCst);
+        if let Some(old) = guard.pop(&path) {
+            self.total_size
+                .fetch_sub(old.memory_size(), Ordering::SeqCst);
+        }
+        self.evict(&mut guard, size);
+        self.total_size.fetch_add(size, Ordering::SeqCst);
+        guard.put(path, image);
+    }
+
+    /// Evicts the least recently used images until `size` more bytes fit the budget.
+    fn evict(&self, lru: &mut LruCache<PathBuf, Arc<ImageData>>, size: usize) {
+        let max_size = self.max_size.load(Ordering::SeqCst);
+        while size + self.total_size.load(Ordering::SeqCst) > max_size {
+            match lru.pop_lru() {
+                Some((_, removed)) => {
+                    self.total_size
+                        .fetch_sub(removed.memory_size(), Ordering::SeqCst);
                 }
                 None => break,
             }
         }
+    }
 
-        self.total_size.fetch_add(size, Ordering::SeqCst);
-        guard.put(path.canonicalize().unwrap_or(path), image);
+    /// A copy of every frame scaled down to the window, `None` if the image already fits.
+    /// Pages, layers and sensor data are left out, the full image is loaded again when it
+    /// is opened.
+    fn downscale(&self, image: &ImageData) -> Option<ImageData> {
+        let size = self.preview_size.load(Ordering::Relaxed);
+        let (width, height) = ((size >> 32) as u32, size as u32);
+        let buffer = image.frames[0].buffer();
+        if width == 0 || height == 0 || (buffer.width() <= width && buffer.height() <= height) {
+            return None;
+        }
+
+        let frames = image
+            .frames
+            .iter()
+            .map(|frame| {
+                let buffer = frame.buffer().resize(width, height, FilterType::Triangle);
+                Image::with_delay(buffer, frame.delay)
+            })
+            .collect();
+        Some(ImageData {
+            raw_metadata: image.raw_metadata.clone(),
+            preview: image.preview,
+            downscaled: true,
+            ..ImageData::new(frames, image.metadata.clone())
+        })
     }
 
     #[allow(clippy::ptr_arg)]
     pub fn pop(&self, path: &PathBuf) {
-        self.lru
+        let removed = self
+            .lru
             .lock()
             .unwrap()
             .pop(&path.canonicalize().unwrap_or_else(|_| path.clone()));
+        if let Some(removed) = removed {
+            self.total_size
+                .fetch_sub(removed.memory_size(), Ordering::SeqCst);
+        }
     }
 
     #[allow(clippy::ptr_arg)]
@@ -87,3 +148,47 @@ impl Cache {
         self.lru.lock().unwrap().clear();
     }
 }
+
+/// The physical memory of the machine in bytes, `None` where it cannot be read.
+pub fn total_memory() -> Option<u64> {
+    static TOTAL: OnceLock<Option<u64>> = OnceLock::new();
+    *TOTAL.get_or_init(read_total_memory)
+}
+
+#[cfg(target_os = "linux")]
+fn read_total_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(windows)]
+fn read_total_memory() -> Option<u64> {
+    use winapi::um::sysinfoapi::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status = MEMORYSTATUSEX {
+        dwLength: size_of::<MEMORYSTATUSEX>() as u32,
+        ..unsafe { std::mem::zeroed() }
+    };
+    (unsafe { GlobalMemoryStatusEx(&mut status) } != 0).then_some(status.ullTotalPhys)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_total_memory() -> Option<u64> {
+    let name = if cfg!(target_os = "macos") {
+        "hw.memsize"
+    } else {
+        "hw.physmem"
+    };
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", name])
+        .output()
+        .ok()?;
+    String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_total_memory() -> Option<u64> {
+    None
+}
@@ -0,0 +1,99 @@
+use cgmath::{EuclideanSpace, Point2};
+use egui::{Button, Slider};
+use simp::io::ToneMapOperator;
+
+use super::{App, op_queue::Op};
+use crate::util::p2;
+
+impl App {
+    /// Exposure and tone mapping are only shown until they are applied to the image.
+    pub fn exposure_ui(&mut self, ctx: &egui::Context) {
+        if self.exposure_visible && self.image_view.is_some() {
+            let mut open = true;
+            let mut closed = false;
+            egui::Window::new("Exposure")
+                .id(egui::Id::new("exposure window"))
+                .collapsible(false)
+                .resizable(false)
+                .pivot(egui::Align2::CENTER_CENTER)
+                .default_pos(p2(Point2::from_vec(self.size / 2.0)))
+                .auto_sized()
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    egui::Grid::new("exposure grid").show(ui, |ui| {
+                        let view = self.image_view.as_mut().unwrap();
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Exposure: ");
+                        });
+                        ui.add(
+                            Slider::new(&mut view.exposure, -10.0..=10.0)
+                                .step_by(0.1)
+                                .suffix(" EV"),
+                        );
+                        ui.end_row();
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Gamma: ");
+                        });
+                        ui.add(Slider::new(&mut view.gamma, 0.2..=5.0));
+                        ui.end_row();
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Tone map: ");
+                        });
+                        egui::ComboBox::new("tone map combobox", "")
+                            .selected_text(view.tonemap.as_ref())
+                            .show_ui(ui, |ui| {
+                                for operator in ToneMapOperator::ALL {
+                                    ui.selectable_value(
+                                        &mut view.tonemap,
+                                        operator,
+                                        operator.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.end_row();
+
+                        ui.with_layout(
+                            egui::Layout::top_down_justified(egui::Align::Center),
+                            |ui| {
+                                if ui
+                                    .add_enabled(self.view_available(), Button::new("Cancel"))
+                                    .clicked()
+                                {
+                                    closed = true;
+                                }
+                            },
+                        );
+
+                        ui.with_layout(
+                            egui::Layout::top_down_justified(egui::Align::Center),
+                            |ui| {
+                                if ui
+                                    .add_enabled(self.view_available(), Button::new("Apply"))
+                                    .clicked()
+                                    || self.enter
+                                {
+                                    let view = self.image_view.as_ref().unwrap();
+                                    self.queue(Op::ToneMap {
+                                        exposure: view.exposure,
+                                        gamma: view.gamma,
+                                        operator: view.tonemap,
+                                    });
+                                    closed = true;
+                                    self.enter = false;
+                                }
+                            },
+                        );
+                    });
+                });
+            self.exposure_visible = open && !closed;
+            if !self.exposure_visible
+                && let Some(view) = self.image_view.as_mut()
+            {
+                view.exposure = 0.0;
+                view.gamma = 1.0;
+                view.tonemap = ToneMapOperator::Clamp;
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use egui::ScrollArea;
+use simp::io::ExrSelection;
+
+use super::{App, op_queue::Op, save_image};
+use crate::WgpuState;
+
+impl App {
+    pub fn exr_layers_ui(&mut self, wgpu: &WgpuState, ui: &mut egui::Ui) {
+        if !self.exr_layers_visible {
+            return;
+        }
+        let Some(view) = self.image_view.as_ref() else {
+            return;
+        };
+        let Some(exr) = view.image_data.read().unwrap().exr.clone() else {
+            return;
+        };
+        let current = view.exr_selection;
+
+        let mut selected = None;
+        let mut save = false;
+        egui::Panel::left("exr layers panel")
+            .resizable(true)
+            .default_size(220.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Layers");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("❌").clicked() {
+                            self.exr_layers_visible = false;
+                        }
+                    });
+                });
+                ui.separator();
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    for (index, layer) in exr.layers.iter().enumerate() {
+                        let layer_selection = ExrSelection {
+                            layer: index,
+                            channel: None,
+                        };
+                        let name = if layer.name.is_empty() {
+                            "(default)"
+                        } else {
+                            &layer.name
+                        };
+                        if ui
+                            .selectable_label(current == layer_selection, name)
+                            .clicked()
+                        {
+                            selected = Some(layer_selection);
+                        }
+
+                        ui.indent(index, |ui| {
+                            for (channel_index, channel) in layer.channels.iter().enumerate() {
+                                let channel_selection = ExrSelection {
+                                    layer: index,
+                                    channel: Some(channel_index),
+                                };
+                                if ui
+                                    .selectable_label(current == channel_selection, &channel.name)
+                                    .clicked()
+                                {
+                                    selected = Some(channel_selection);
+                                }
+                            }
+                        });
+                    }
+                });
+
+                ui.separator();
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Save layer as").clicked() {
+                            save = true;
+                        }
+                    },
+                );
+            });
+
+        if let Some(selection) = selected
+            && selection != current
+            && self.view_available()
+        {
+            self.queue(Op::ExrLayer(selection));
+        }
+
+        if save {
+            let layer = &exr.layers[current.layer];
+            let mut name = Path::new(&self.current_filename)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if !layer.name.is_empty() {
+                name = format!("{name}.{}", layer.name);
+            }
+            if let Some(channel) = current.channel {
+                name = format!("{name}.{}", layer.channels[channel].name);
+            }
+            name.push_str(".exr");
+            save_image::open(name, self.proxy.clone(), wgpu);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use egui::{Rect, Sense, Stroke, StrokeKind, Vec2, pos2};
+use simp::io::thumbnail::ThumbnailSize;
+
+use super::{
+    App,
+    op_queue::Op,
+    preferences::PREFERENCES,
+    thumbnails::{Thumbnails, paint_thumbnail},
+};
+
+pub const FILMSTRIP_SIZE: f32 = 64.0;
+const PADDING: f32 = 4.0;
+
+/// A strip of thumbnails of the images around the current one.
+pub struct Filmstrip {
+    pub thumbnails: Thumbnails,
+}
+
+impl Default for Filmstrip {
+    fn default() -> Self {
+        Self {
+            thumbnails: Thumbnails::new(ThumbnailSize::Normal),
+        }
+    }
+}
+
+impl App {
+    pub fn filmstrip_visible(&self) -> bool {
+        PREFERENCES.lock().unwrap().filmstrip && self.image_view.is_some() && !self.gallery.visible
+    }
+
+    pub fn filmstrip(&mut self, ui: &mut egui::Ui) {
+        if !self.filmstrip_visible() {
+            self.filmstrip.thumbnails.clear();
+            return;
+        }
+
+        let (count, save) = {
+            let preferences = PREFERENCES.lock().unwrap();
+            (
+                preferences.filmstrip_images as usize,
+                preferences.save_thumbnails,
+            )
+        };
+        let paths = self.op_queue.image_list.paths();
+        let current = self.image_view.as_ref().and_then(|view| view.path.as_ref());
+        let index = current.and_then(|current| paths.iter().position(|path| path == current));
+        let shown = match index {
+            Some(index) => {
+                &paths[index.saturating_sub(count)..(index + count + 1).min(paths.len())]
+            }
+            None => &[][..],
+        };
+        self.filmstrip.thumbnails.retain(shown);
+        self.filmstrip.thumbnails.upload(ui.ctx());
+
+        let mut jump = None;
+        egui::Panel::bottom("filmstrip")
+            .exact_size(FILMSTRIP_SIZE)
+            .show(ui, |ui| {
+                let Some(index) = index else {
+                    return;
+                };
+
+                // The current image stays in the middle, the others are placed around it.
+                let bounds = ui.max_rect();
+                let cell = FILMSTRIP_SIZE - PADDING * 2.0;
+                let center = bounds.center();
+                let first = index.saturating_sub(count);
+                for (i, path) in shown.iter().enumerate() {
+                    let offset = (first + i) as f32 - index as f32;
+                    let rect = Rect::from_center_size(
+                        pos2(center.x + offset * (cell + PADDING), center.y),
+                        Vec2::splat(cell),
+                    );
+                    if !bounds.contains_rect(rect) {
+                        continue;
+                    }
+
+                    let response = ui
+                        .interact(rect, ui.id().with(path), Sense::click())
+                        .on_hover_text(path.file_name().unwrap_or_default().to_string_lossy());
+                    let painter = ui.painter_at(rect);
+                    let visuals = ui.visuals();
+                    if offset == 0.0 {
+                        painter.rect_filled(rect, 4.0, visuals.selection.bg_fill);
+                        painter.rect_stroke(
+                            rect,
+                            4.0,
+                            Stroke::new(2.0, visuals.selection.stroke.color),
+                            StrokeKind::Inside,
+                        );
+                    } else if response.hovered() {
+                        painter.rect_filled(rect, 4.0, visuals.widgets.hovered.bg_fill);
+                    }
+
+                    let thumbnail =
+                        self.filmstrip
+                            .thumbnails
+                            .get(path, &self.op_queue.thumbnailer, save);
+                    paint_thumbnail(&painter, rect.shrink(PADDING), thumbnail);
+
+                    if response.clicked() && offset != 0.0 {
+                        jump = Some(path.clone());
+                    }
+                }
+            });
+
+        if let Some(path) = jump
+            && self.view_available()
+        {
+            self.queue(Op::LoadPath(path, true));
+        }
+    }
+}
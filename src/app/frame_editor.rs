@@ -0,0 +1,145 @@
+use std::{path::Path, time::Duration};
+
+use cgmath::{EuclideanSpace, Point2};
+use egui::{Button, DragValue};
+
+use super::{App, op_queue::Op, save_image};
+use crate::{WgpuState, util::p2};
+
+pub struct FrameEditor {
+    pub visible: bool,
+    /// First and last frame to delete, both included.
+    pub start: usize,
+    pub end: usize,
+    /// Delay in milliseconds.
+    pub delay: u64,
+}
+
+impl Default for FrameEditor {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            start: 0,
+            end: 0,
+            delay: 100,
+        }
+    }
+}
+
+impl App {
+    pub fn frame_editor_ui(&mut self, wgpu: &WgpuState, ctx: &egui::Context) {
+        if !self.frame_editor.visible {
+            return;
+        }
+        // A still image has no frames to edit.
+        let Some(view) = self
+            .image_view
+            .as_ref()
+            .filter(|view| view.len_frames() > 1)
+        else {
+            self.frame_editor.visible = false;
+            return;
+        };
+
+        let last = view.len_frames() - 1;
+        let index = view.index;
+        let available = self.view_available();
+        let editor = &mut self.frame_editor;
+        editor.end = editor.end.min(last);
+        editor.start = editor.start.min(editor.end);
+
+        let mut open = true;
+        let mut op = None;
+        let mut extract = false;
+        egui::Window::new("Frames")
+            .id(egui::Id::new("frame editor window"))
+            .collapsible(false)
+            .resizable(false)
+            .pivot(egui::Align2::CENTER_CENTER)
+            .default_pos(p2(Point2::from_vec(self.size / 2.0)))
+            .auto_sized()
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("frame editor grid").show(ui, |ui| {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Current frame: ");
+                    });
+                    ui.label(format!("{index} of {last}"));
+                    ui.end_row();
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Delete frames: ");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut editor.start).range(0..=editor.end));
+                        ui.label("to");
+                        ui.add(DragValue::new(&mut editor.end).range(editor.start..=last));
+                        // Deleting every frame would leave nothing to show.
+                        let all = editor.start == 0 && editor.end == last;
+                        if ui
+                            .add_enabled(available && !all, Button::new("Delete"))
+                            .clicked()
+                        {
+                            op = Some(Op::DeleteFrames(editor.start, editor.end));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Delay: ");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(&mut editor.delay)
+                                .range(0..=65535)
+                                .suffix(" ms"),
+                        );
+                        let delay = Duration::from_millis(editor.delay);
+                        if ui
+                            .add_enabled(available, Button::new("Set current"))
+                            .clicked()
+                        {
+                            op = Some(Op::FrameDelay(Some(index), delay));
+                        }
+                        if ui.add_enabled(available, Button::new("Set all")).clicked() {
+                            op = Some(Op::FrameDelay(None, delay));
+                        }
+                    });
+                    ui.end_row();
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(available, Button::new("Duplicate"))
+                        .clicked()
+                    {
+                        op = Some(Op::DuplicateFrame(index));
+                    }
+                    if ui.add_enabled(available, Button::new("Reverse")).clicked() {
+                        op = Some(Op::ReverseFrames);
+                    }
+                    if ui
+                        .add_enabled(available, Button::new("Extract current"))
+                        .clicked()
+                    {
+                        extract = true;
+                    }
+                });
+            });
+
+        if let Some(op) = op {
+            self.queue(op);
+        }
+        if extract {
+            let name = Path::new(&self.current_filename)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let name = format!("{name}.{index}.png");
+            save_image::open_frame(name, index, self.proxy.clone(), wgpu);
+        }
+        self.frame_editor.visible = open;
+    }
+}
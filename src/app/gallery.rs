@@ -0,0 +1,316 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use egui::{Align2, FontId, Key, Modifiers, Rect, Sense, Stroke, StrokeKind, Vec2, pos2};
+
+use super::{
+    App, delete,
+    op_queue::Op,
+    preferences::PREFERENCES,
+    thumbnails::{Thumbnails, paint_thumbnail},
+};
+
+const PADDING: f32 = 6.0;
+const LABEL_HEIGHT: f32 = 18.0;
+
+/// A grid of thumbnails of every image in the current dir.
+#[derive(Default)]
+pub struct Gallery {
+    pub visible: bool,
+    /// The image moved around with the keyboard and opened with enter.
+    cursor: Option<PathBuf>,
+    /// The fixed end of a range selected with shift.
+    anchor: Option<PathBuf>,
+    selected: HashSet<PathBuf>,
+    pub thumbnails: Thumbnails,
+    scroll_to_cursor: bool,
+    /// Height of the visible part of the grid in the last frame.
+    viewport: f32,
+    offset: f32,
+}
+
+impl Gallery {
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.thumbnails.clear();
+    }
+
+    fn select_range(&mut self, paths: &[PathBuf], from: usize, to: usize) {
+        self.selected = paths[from.min(to)..=from.max(to)].iter().cloned().collect();
+    }
+}
+
+impl App {
+    pub fn toggle_gallery(&mut self) {
+        if self.gallery.visible {
+            self.gallery.hide();
+            return;
+        }
+        if self.op_queue.image_list.paths().is_empty() {
+            return;
+        }
+
+        let current = self.image_view.as_ref().and_then(|view| view.path.clone());
+        let gallery = &mut self.gallery;
+        gallery.visible = true;
+        gallery.selected = current.iter().cloned().collect();
+        gallery.anchor = current.clone();
+        gallery.cursor = current;
+        gallery.scroll_to_cursor = true;
+    }
+
+    /// Opens an image from the gallery, the one being looked at is kept as it is.
+    fn open_from_gallery(&mut self, path: PathBuf) {
+        self.gallery.hide();
+        let current = self.image_view.as_ref().and_then(|view| view.path.as_ref());
+        if current != Some(&path) {
+            self.queue(Op::LoadPath(path, true));
+        }
+    }
+
+    pub fn gallery_ui(&mut self, ui: &mut egui::Ui) {
+        let paths = self.op_queue.image_list.paths();
+        if paths.is_empty() {
+            self.gallery.hide();
+            return;
+        }
+
+        let preferences = PREFERENCES.lock().unwrap().clone();
+        self.gallery.thumbnails.set_size(preferences.thumbnail_size);
+        let ctx = ui.ctx().clone();
+        self.gallery.thumbnails.upload(&ctx);
+
+        // Thumbnails are drawn at their real size on screen.
+        let thumbnail_size = self.gallery.thumbnails.size.pixels() as f32 / ctx.pixels_per_point();
+        let cell = Vec2::new(
+            thumbnail_size + PADDING * 2.0,
+            thumbnail_size + LABEL_HEIGHT + PADDING * 2.0,
+        );
+        let columns = ((ui.available_width() / cell.x) as usize).max(1);
+        let rows = paths.len().div_ceil(columns);
+        let page = ((self.gallery.viewport / cell.y) as usize).max(1) * columns;
+
+        let position = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .and_then(|path| paths.iter().position(|other| other == path))
+        };
+        let anchor = position(&self.gallery.anchor);
+
+        let focused = ctx.memory(|memory| memory.focused().is_none());
+        if focused {
+            let cursor = position(&self.gallery.cursor);
+            self.gallery_input(ui, &paths, cursor, anchor, columns, page);
+        }
+        if !self.gallery.visible {
+            return;
+        }
+
+        let cursor = position(&self.gallery.cursor);
+        let mut scroll_area = egui::ScrollArea::vertical().auto_shrink(false);
+        if self.gallery.scroll_to_cursor
+            && let Some(cursor) = cursor
+        {
+            self.gallery.scroll_to_cursor = false;
+            let top = (cursor / columns) as f32 * cell.y;
+            let bottom = top + cell.y;
+            // The viewport is unknown before the first frame.
+            if top < self.gallery.offset || self.gallery.viewport == 0.0 {
+                scroll_area = scroll_area.vertical_scroll_offset(top);
+            } else if bottom > self.gallery.offset + self.gallery.viewport {
+                scroll_area = scroll_area.vertical_scroll_offset(bottom - self.gallery.viewport);
+            }
+        }
+
+        let mut clicked = None;
+        let mut open = None;
+        let mut trash = false;
+        // The cells touch, show_rows also needs to know there is no spacing between rows.
+        ui.spacing_mut().item_spacing = Vec2::ZERO;
+        let output = scroll_area.show_rows(ui, cell.y, rows, |ui, range| {
+            let len = range.len();
+            for (row, chunk) in paths
+                .chunks(columns)
+                .enumerate()
+                .skip(range.start)
+                .take(len)
+            {
+                ui.horizontal(|ui| {
+                    for (i, path) in chunk.iter().enumerate() {
+                        let index = row * columns + i;
+                        let (rect, response) = ui.allocate_exact_size(cell, Sense::click());
+                        let painter = ui.painter_at(rect);
+                        let visuals = ui.visuals();
+                        if self.gallery.selected.contains(path) {
+                            painter.rect_filled(rect, 4.0, visuals.selection.bg_fill);
+                        } else if response.hovered() {
+                            painter.rect_filled(rect, 4.0, visuals.widgets.hovered.bg_fill);
+                        }
+                        if Some(index) == cursor {
+                            painter.rect_stroke(
+                                rect.shrink(1.0),
+                                4.0,
+                                Stroke::new(2.0, visuals.selection.stroke.color),
+                                StrokeKind::Inside,
+                            );
+                        }
+
+                        let area = Rect::from_min_size(
+                            rect.min + Vec2::splat(PADDING),
+                            Vec2::splat(thumbnail_size),
+                        );
+                        let thumbnail = self.gallery.thumbnails.get(
+                            path,
+                            &self.op_queue.thumbnailer,
+                            preferences.save_thumbnails,
+                        );
+                        paint_thumbnail(&painter, area, thumbnail);
+
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        painter.text(
+                            pos2(rect.center().x, area.max.y + LABEL_HEIGHT / 2.0),
+                            Align2::CENTER_CENTER,
+                            name,
+                            FontId::proportional(12.0),
+                            visuals.text_color(),
+                        );
+
+                        if response.double_clicked() {
+                            open = Some(path.clone());
+                        } else if response.clicked() || response.secondary_clicked() {
+                            clicked = Some((index, response.secondary_clicked()));
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Open").clicked() {
+                                open = Some(path.clone());
+                                ui.close();
+                            }
+                            if ui
+                                .add(egui::Button::new("Move to trash").shortcut_text("Delete"))
+                                .clicked()
+                            {
+                                trash = true;
+                                ui.close();
+                            }
+                        });
+                    }
+                });
+            }
+        });
+        self.gallery.viewport = output.inner_rect.height();
+        self.gallery.offset = output.state.offset.y;
+
+        if let Some((index, secondary)) = clicked {
+            let modifiers = ui.input(|input| input.modifiers);
+            let path = paths[index].clone();
+            let gallery = &mut self.gallery;
+            if secondary && gallery.selected.contains(&path) {
+                // Keep the selection so the context menu acts on all of it.
+            } else if modifiers.shift {
+                gallery.select_range(&paths, anchor.unwrap_or(index), index);
+            } else if modifiers.command {
+                if !gallery.selected.remove(&path) {
+                    gallery.selected.insert(path.clone());
+                }
+                gallery.anchor = Some(path.clone());
+            } else {
+                gallery.selected = HashSet::from([path.clone()]);
+                gallery.anchor = Some(path.clone());
+            }
+            gallery.cursor = Some(path);
+        }
+        if trash {
+            self.trash_selection(&paths);
+        }
+        if let Some(path) = open {
+            self.open_from_gallery(path);
+        }
+    }
+
+    fn gallery_input(
+        &mut self,
+        ui: &egui::Ui,
+        paths: &[PathBuf],
+        cursor: Option<usize>,
+        anchor: Option<usize>,
+        columns: usize,
+        page: usize,
+    ) {
+        let last = paths.len() - 1;
+        let mut moved = None;
+        let mut open = false;
+        let mut trash = false;
+        let mut close = false;
+        ui.input_mut(|input| {
+            // Shift is ignored when matching keys, it extends the selection instead.
+            let shift = input.modifiers.shift;
+            let current = cursor.unwrap_or(0);
+            let moves = [
+                (Key::ArrowLeft, current.saturating_sub(1)),
+                (Key::ArrowRight, (current + 1).min(last)),
+                (Key::ArrowUp, current.saturating_sub(columns)),
+                (Key::ArrowDown, (current + columns).min(last)),
+                (Key::PageUp, current.saturating_sub(page)),
+                (Key::PageDown, (current + page).min(last)),
+                (Key::Home, 0),
+                (Key::End, last),
+            ];
+            for (key, index) in moves {
+                if input.consume_key(Modifiers::NONE, key) {
+                    // The first key press only shows where the cursor is.
+                    moved = Some((if cursor.is_some() { index } else { 0 }, shift));
+                }
+            }
+
+            if input.consume_key(Modifiers::COMMAND, Key::A) {
+                self.gallery.selected = paths.iter().cloned().collect();
+            }
+            if input.consume_key(Modifiers::NONE, Key::Space)
+                && let Some(cursor) = cursor
+                && !self.gallery.selected.remove(&paths[cursor])
+            {
+                self.gallery.selected.insert(paths[cursor].clone());
+            }
+            open = input.consume_key(Modifiers::NONE, Key::Enter);
+            trash = input.consume_key(Modifiers::NONE, Key::Delete);
+            close = input.consume_key(Modifiers::NONE, Key::Escape)
+                || input.consume_key(Modifiers::NONE, Key::G);
+        });
+
+        if let Some((index, shift)) = moved {
+            let gallery = &mut self.gallery;
+            if shift {
+                gallery.select_range(paths, anchor.unwrap_or(index), index);
+                if anchor.is_none() {
+                    gallery.anchor = Some(paths[index].clone());
+                }
+            } else {
+                gallery.selected = HashSet::from([paths[index].clone()]);
+                gallery.anchor = Some(paths[index].clone());
+            }
+            gallery.cursor = Some(paths[index].clone());
+            gallery.scroll_to_cursor = true;
+        }
+        if trash {
+            self.trash_selection(paths);
+        }
+        if close {
+            self.gallery.hide();
+        } else if open && let Some(cursor) = self.gallery.cursor.clone() {
+            self.open_from_gallery(cursor);
+        }
+    }
+
+    fn trash_selection(&self, paths: &[PathBuf]) {
+        let selected: Vec<_> = paths
+            .iter()
+            .filter(|path| self.gallery.selected.contains(*path))
+            .cloned()
+            .collect();
+        if !selected.is_empty() {
+            delete(
+                selected,
+                self.dialog_manager.get_proxy(),
+                self.proxy.clone(),
+            );
+        }
+    }
+}
@@ -44,6 +44,15 @@ impl App {
                                 ("100% - 900% Zoom", "Ctrl + 1 - 9"),
                                 ("Previous image", "A or L or Left Arrow"),
                                 ("Next image", "D or H or Right Arrow"),
+                                ("Previous page", "Page Up"),
+                                ("Next page", "Page Down"),
+                                ("Play or pause animation", "Space"),
+                                ("Previous frame", ","),
+                                ("Next frame", "."),
+                                ("Toggle gallery", "G"),
+                                ("Toggle filmstrip", "T"),
+                                ("Select in gallery", "Shift + Arrows or Ctrl + A"),
+                                ("Open from gallery", "Enter"),
                                 ("Toggle zen mode", "Ctrl + I"),
                             ];
 
@@ -10,7 +10,10 @@ use std::{
 
 use winit::event_loop::EventLoopProxy;
 
-use super::op_queue::{LoadingInfo, prefetch};
+use super::{
+    op_queue::{Developer, LoadingInfo},
+    prefetch::{AHEAD, BEHIND, Prefetcher},
+};
 use crate::{
     app::{
         cache::Cache,
@@ -76,14 +79,19 @@ impl PartialEq for ImageListEntry {
 }
 
 type List = Arc<Mutex<Option<Vec<ImageListEntry>>>>;
+/// The paths of the list, rebuilt whenever the list changes so the gallery and filmstrip
+/// can borrow them every frame.
+type Paths = Arc<Mutex<Arc<[PathBuf]>>>;
 
 pub struct ImageList {
     list: List,
+    paths: Paths,
     index: Arc<AtomicUsize>,
     path: Option<PathBuf>,
     cache: Arc<Cache>,
-    proxy: EventLoopProxy<UserEvent>,
-    loading_info: Arc<Mutex<LoadingInfo>>,
+    prefetcher: Prefetcher,
+    /// Whether the last image was reached with next rather than prev.
+    forward: bool,
     sort_order: Arc<Mutex<(SortOrder, SortDirection)>>,
 }
 
@@ -92,20 +100,23 @@ impl ImageList {
         cache: Arc<Cache>,
         proxy: EventLoopProxy<UserEvent>,
         loading_info: Arc<Mutex<LoadingInfo>>,
+        developer: Developer,
     ) -> Self {
         Self {
             list: Arc::new(Mutex::new(None)),
+            paths: Arc::new(Mutex::new(Arc::from([]))),
             index: Arc::new(AtomicUsize::new(0)),
             path: None,
-            proxy,
+            prefetcher: Prefetcher::new(cache.clone(), proxy, loading_info, developer),
+            forward: true,
             cache,
-            loading_info,
             sort_order: Arc::new(Mutex::new((SortOrder::default(), SortDirection::default()))),
         }
     }
 
     pub fn clear(&mut self) {
         *self.list.lock().unwrap() = None;
+        *self.paths.lock().unwrap() = Arc::from([]);
         self.path = None;
         self.index.store(0, Ordering::SeqCst)
     }
@@ -121,6 +132,7 @@ impl ImageList {
 
         let index = self.index.clone();
         let list = self.list.clone();
+        let paths = self.paths.clone();
         thread::spawn(move || {
             let current = index.load(Ordering::SeqCst);
             let mut lock = list.lock().unwrap();
@@ -129,6 +141,7 @@ impl ImageList {
             };
             let current_entry = list[current].clone();
             sort_list(list, sort_order, sort_direction);
+            *paths.lock().unwrap() = list_paths(list);
             for (i, list_entry) in list.iter().enumerate() {
                 if list_entry.path == current_entry.path {
                     index.store(i, Ordering::SeqCst);
@@ -139,14 +152,6 @@ impl ImageList {
     }
 
     pub fn change_dir(&mut self, path: impl AsRef<Path>) {
-        if let Some(ref current) = self.path {
-            let new = path.as_ref().parent();
-            if let Some(new) = new
-                && current == new
-            {
-                return;
-            }
-        }
         let path_buf = path.as_ref().to_path_buf();
         let mut dir_path = path_buf.clone();
         dir_path.pop();
@@ -154,17 +159,16 @@ impl ImageList {
             dir_path = PathBuf::from(".");
         }
 
-        if let Some(ref p) = self.path
-            && *p == dir_path
-            && self.list.lock().unwrap().is_some()
-        {
+        if self.path.as_ref() == Some(&dir_path) {
+            // Images opened some other way than next and prev, like from the gallery, move
+            // the position in the list.
             let lock = self.list.lock().unwrap();
-            if let Some(ref dirs) = *lock {
-                for (index, list_entry) in dirs.iter().enumerate() {
-                    if list_entry.path == path_buf {
-                        self.index.store(index, Ordering::SeqCst);
-                    }
-                }
+            if let Some(ref list) = *lock
+                && let Some(index) = list.iter().position(|entry| entry.path == path_buf)
+                && index != self.index.load(Ordering::SeqCst)
+            {
+                self.index.store(index, Ordering::SeqCst);
+                prefetch(&self.prefetcher, list, index, self.forward);
             }
             return;
         }
@@ -172,11 +176,11 @@ impl ImageList {
         self.path = Some(dir_path.clone());
 
         let t_list = self.list.clone();
+        let t_paths = self.paths.clone();
         let t_index = self.index.clone();
         let t_sort_order = self.sort_order.clone();
-        let proxy = self.proxy.clone();
-        let cache = self.cache.clone();
-        let loading_info = self.loading_info.clone();
+        let prefetcher = self.prefetcher.clone();
+        let forward = self.forward;
         let mut list = vec![ImageListEntry::from_path(path_buf.clone())];
         thread::spawn(move || {
             let dirs = std::fs::read_dir(dir_path).unwrap();
@@ -217,23 +221,11 @@ impl ImageList {
                 }
             }
 
-            let next = list[next_index(t_index.load(Ordering::SeqCst), list.len())].clone();
-            prefetch(
-                &next.path,
-                cache.clone(),
-                proxy.clone(),
-                loading_info.clone(),
-            );
+            prefetch(&prefetcher, &list, t_index.load(Ordering::SeqCst), forward);
 
-            let prev = list[prev_index(t_index.load(Ordering::SeqCst), list.len())].clone();
-            prefetch(
-                &prev.path,
-                cache.clone(),
-                proxy.clone(),
-                loading_info.clone(),
-            );
-
-            *t_list.lock().unwrap() = Some(list);
+            let mut lock = t_list.lock().unwrap();
+            *t_paths.lock().unwrap() = list_paths(&list);
+            *lock = Some(list);
         });
     }
 
@@ -244,13 +236,12 @@ impl ImageList {
             if list.len() <= self.index.load(Ordering::SeqCst) {
                 self.index.store(0, Ordering::SeqCst);
             }
+            self.forward = true;
             prefetch(
-                list[next_index(self.index.load(Ordering::SeqCst), list.len())]
-                    .path
-                    .clone(),
-                self.cache.clone(),
-                self.proxy.clone(),
-                self.loading_info.clone(),
+                &self.prefetcher,
+                list,
+                self.index.load(Ordering::SeqCst),
+                true,
             );
             Some(list[self.index.load(Ordering::SeqCst)].path.clone())
         } else {
@@ -266,13 +257,12 @@ impl ImageList {
             } else {
                 self.index.fetch_sub(1, Ordering::SeqCst);
             }
+            self.forward = false;
             prefetch(
-                list[prev_index(self.index.load(Ordering::SeqCst), list.len())]
-                    .path
-                    .clone(),
-                self.cache.clone(),
-                self.proxy.clone(),
-                self.loading_info.clone(),
+                &self.prefetcher,
+                list,
+                self.index.load(Ordering::SeqCst),
+                false,
             );
             Some(list[self.index.load(Ordering::SeqCst)].path.clone())
         } else {
@@ -280,32 +270,60 @@ impl ImageList {
         }
     }
 
-    /// Removes path from list and returns the path to the new current image in the dir.
+    /// Removes paths from list and returns the path to the new current image in the dir.
     /// Will return None if there are no more images in the current dir.
-    pub fn trash(&mut self, path: &PathBuf) -> Option<PathBuf> {
-        self.cache.pop(path);
-        let mut lock = self.list.lock().unwrap();
-        if let Some(ref mut list) = *lock {
-            let index = self.index.load(Ordering::SeqCst);
-            let p = list[index].clone();
-            if p.path == *path {
-                list.remove(index);
-                return list.get(index).map(|entry| &entry.path).cloned();
-            }
+    pub fn trash(&mut self, paths: &[PathBuf]) -> Option<PathBuf> {
+        for path in paths {
+            self.cache.pop(path);
         }
+        let mut lock = self.list.lock().unwrap();
+        let list = lock.as_mut()?;
+        // The current image moves up by the number of images removed in front of it.
+        let index = self.index.load(Ordering::SeqCst).min(list.len());
+        let removed = list[..index]
+            .iter()
+            .filter(|entry| paths.contains(&entry.path))
+            .count();
+        list.retain(|entry| !paths.contains(&entry.path));
+        *self.paths.lock().unwrap() = list_paths(list);
+        let index = index - removed;
+        self.index.store(index, Ordering::SeqCst);
+        list.get(index).map(|entry| entry.path.clone())
+    }
 
-        None
+    /// Every image in the current dir in the order they are browsed.
+    pub fn paths(&self) -> Arc<[PathBuf]> {
+        self.paths.lock().unwrap().clone()
     }
 }
 
-fn next_index(index: usize, len: usize) -> usize {
-    let next = index + 1;
-    if len <= next { 0 } else { next }
+fn list_paths(list: &[ImageListEntry]) -> Arc<[PathBuf]> {
+    list.iter().map(|entry| entry.path.clone()).collect()
 }
 
-fn prev_index(index: usize, len: usize) -> usize {
-    let current = index;
-    if current == 0 { len - 1 } else { current - 1 }
+/// Queues the images around `index` with more of them in the direction of travel. The
+/// list wraps around like browsing does.
+fn prefetch(prefetcher: &Prefetcher, list: &[ImageListEntry], index: usize, forward: bool) {
+    let len = list.len();
+    if len == 0 {
+        return;
+    }
+
+    // Nearest first, alternating between ahead and behind.
+    let mut indices = vec![index];
+    for distance in 1..=AHEAD.max(BEHIND) {
+        let next = (index + distance) % len;
+        let prev = (index + len - distance % len) % len;
+        let (ahead, behind) = if forward { (next, prev) } else { (prev, next) };
+        if distance <= AHEAD && !indices.contains(&ahead) {
+            indices.push(ahead);
+        }
+        if distance <= BEHIND && !indices.contains(&behind) {
+            indices.push(behind);
+        }
+    }
+    indices.remove(0);
+    prefetcher.schedule(indices.into_iter().map(|i| list[i].path.clone()));
 }
 
 fn sort_list(list: &mut [ImageListEntry], sort_order: SortOrder, sort_direction: SortDirection) {
@@ -6,17 +6,22 @@ use std::{
     time::{Duration, Instant},
 };
 
-use cgmath::{Deg, Matrix4, Ortho, Vector2, Vector3, Vector4};
-use image::GenericImageView;
+use cgmath::{Deg, Matrix4, Ortho, SquareMatrix, Vector2, Vector3, Vector4};
+use image::{DynamicImage, GenericImageView};
 use num_traits::Zero;
+use simp::io::{ExrSelection, PsdVisibility, RawSettings, Svg, ToneMapOperator};
+use wgpu::Limits;
 use winit::event_loop::EventLoopProxy;
 
-use self::mosaic::Mosaic;
-use super::op_queue::{Output, UserEventLoopProxyExt};
+use self::mosaic::{Mosaic, Tile};
+use super::{
+    op_queue::{Output, UserEventLoopProxyExt},
+    undo_stack::Snapshot,
+};
 use crate::{
     WgpuState, max,
     rect::Rect,
-    util::{Image, ImageData, UserEvent, matrix::OPENGL_TO_WGPU_MATRIX},
+    util::{ImageData, UserEvent, matrix::OPENGL_TO_WGPU_MATRIX},
 };
 
 pub mod crop_renderer;
@@ -26,8 +31,65 @@ pub mod mosaic;
 mod crop;
 use crop::Crop;
 
+pub mod color_management;
+use color_management::ColorTransform;
 mod texture;
 
+/// How far outside the window in screen pixels a detail is rendered, so small drags
+/// do not need a new one.
+const DETAIL_MARGIN: f32 = 256.0;
+
+/// Playback speeds offered in the gif player bar.
+pub const SPEEDS: [f32; 7] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 4.0];
+
+/// What happens when an animation reaches its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Starts over, as many times as the file asks for.
+    #[default]
+    Loop,
+    /// Plays backwards to the first frame and then forwards again.
+    PingPong,
+    /// Stops on the last frame.
+    Once,
+}
+
+impl LoopMode {
+    pub const ALL: [Self; 3] = [Self::Loop, Self::PingPong, Self::Once];
+}
+
+impl AsRef<str> for LoopMode {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Loop => "Loop",
+            Self::PingPong => "Ping-pong",
+            Self::Once => "Once",
+        }
+    }
+}
+
+/// A part of an svg rasterized again at the zoom it is shown at.
+pub struct Detail {
+    tile: Tile,
+    /// In the pixel space of the mosaic.
+    region: Rect,
+    scale: f32,
+}
+
+impl Detail {
+    pub fn tile(&self) -> &Tile {
+        &self.tile
+    }
+}
+
+/// A detail rendered in the background, uploaded once it reaches the event loop.
+pub struct DetailImage {
+    svg: Arc<Svg>,
+    region: Rect,
+    scale: f32,
+    image: Option<DynamicImage>,
+}
+
 pub struct ImageView {
     pub mosaic: Vec<Mosaic>,
     pub size: Vector2<f32>,
@@ -46,8 +108,33 @@ pub struct ImageView {
     pub saturation: f32,
     pub grayscale: bool,
     pub invert: bool,
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    pub gamma: f32,
+    pub tonemap: ToneMapOperator,
+    /// The OpenEXR layer currently in `image_data.frames`.
+    pub exr_selection: ExrSelection,
+    /// Which layers of a Photoshop file are in `image_data.frames`.
+    pub psd_visibility: PsdVisibility,
+    /// The settings a camera raw file in `image_data.frames` was developed with.
+    pub raw_settings: RawSettings,
+    /// Settings being edited in the RAW window, developed once they differ from `raw_settings`.
+    pub raw_draft: RawSettings,
+    /// The page of a multi-page file currently in `image_data.frames`.
+    pub page: usize,
     pub crop: Crop,
     pub playing: bool,
+    /// Multiplier for the frame delays.
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+    /// Set while a ping-pong animation plays backwards.
+    backwards: bool,
+    /// How many times the animation has played to the end since it was started.
+    plays: u32,
+    pub detail: Option<Detail>,
+    /// The detail covers the window at the current zoom and is drawn instead of the mosaic.
+    pub show_detail: bool,
+    detail_pending: bool,
 }
 
 impl ImageView {
@@ -55,7 +142,26 @@ impl ImageView {
         let frames = &image_data.frames;
         let image = frames[0].buffer();
         let (width, height) = image.dimensions();
-        let mosaic = Mosaic::from_images(wgpu, image_data.clone());
+        let exr_selection = image_data
+            .exr
+            .as_ref()
+            .map(|exr| exr.default_selection())
+            .unwrap_or_default();
+        let mosaic = Mosaic::from_images(
+            wgpu,
+            image_data.clone(),
+            stretch(&image_data, exr_selection),
+        );
+        let psd_visibility = image_data
+            .psd
+            .as_ref()
+            .map(|psd| psd.default_visibility())
+            .unwrap_or_default();
+        let raw_settings = image_data
+            .raw
+            .as_ref()
+            .map(|raw| raw.settings)
+            .unwrap_or_default();
 
         Self {
             mosaic,
@@ -76,7 +182,22 @@ impl ImageView {
             saturation: 0.0,
             grayscale: false,
             invert: false,
+            exposure: 0.0,
+            gamma: 1.0,
+            tonemap: ToneMapOperator::Clamp,
+            exr_selection,
+            psd_visibility,
+            raw_settings,
+            raw_draft: raw_settings,
+            page: 0,
             playing: true,
+            speed: 1.0,
+            loop_mode: LoopMode::Loop,
+            backwards: false,
+            plays: 0,
+            detail: None,
+            show_detail: false,
+            detail_pending: false,
         }
     }
 
@@ -129,6 +250,9 @@ impl ImageView {
             saturation: self.saturation,
             grayscale: self.grayscale as u32,
             invert: self.invert as u32,
+            exposure: self.exposure,
+            tonemap: self.tonemap.index(),
+            gamma: self.gamma,
         }
     }
 
@@ -180,17 +304,16 @@ impl ImageView {
         if !self.playing || frames.len() <= 1 {
             return Duration::MAX;
         }
+        let len = frames.len();
+        let loop_count = guard.raw_metadata.loop_count;
 
         let now = Instant::now();
         let time_passed = now.duration_since(self.last_frame);
-        let delay = frames[self.index].delay;
+        let delay = frames[self.index].delay.div_f32(self.speed);
+        drop(guard);
 
         if time_passed > delay {
-            self.index += 1;
-            if self.index >= frames.len() {
-                self.index = 0;
-            }
-
+            self.advance(len, loop_count);
             self.last_frame = now;
 
             delay
@@ -199,51 +322,261 @@ impl ImageView {
         }
     }
 
-    pub fn len_frames(&mut self) -> usize {
+    /// Moves to the next frame in the current loop mode. `loop_count` is how many
+    /// times the file asks to be played, 0 or `None` is forever.
+    fn advance(&mut self, len: usize, loop_count: Option<u32>) {
+        let last = len - 1;
+        match self.loop_mode {
+            LoopMode::Loop if self.index >= last => {
+                self.plays += 1;
+                match loop_count {
+                    Some(count) if count > 0 && self.plays >= count => self.playing = false,
+                    _ => self.index = 0,
+                }
+            }
+            LoopMode::Once if self.index >= last => {
+                self.plays += 1;
+                self.playing = false;
+            }
+            LoopMode::PingPong if self.backwards => {
+                if self.index == 0 {
+                    self.backwards = false;
+                    self.index = 1.min(last);
+                } else {
+                    self.index -= 1;
+                }
+            }
+            LoopMode::PingPong if self.index >= last => {
+                self.backwards = true;
+                self.index = last.saturating_sub(1);
+            }
+            _ => self.index += 1,
+        }
+    }
+
+    /// Starts or pauses playback, an animation that has stopped at its end starts over.
+    pub fn toggle_playing(&mut self) {
+        if !self.playing && self.plays > 0 && self.index + 1 >= self.len_frames() {
+            self.index = 0;
+            self.plays = 0;
+        }
+        self.playing = !self.playing;
+        self.last_frame = Instant::now();
+    }
+
+    /// Pauses and shows the next or previous frame, wrapping around at the ends.
+    pub fn step_frame(&mut self, forward: bool) {
+        let len = self.len_frames();
+        if len < 2 {
+            return;
+        }
+        self.playing = false;
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+    }
+
+    /// The delay of the frame being shown.
+    pub fn frame_delay(&self) -> Duration {
+        let guard = self.image_data.read().unwrap();
+        guard.frames[self.index.min(guard.frames.len() - 1)].delay
+    }
+
+    pub fn len_frames(&self) -> usize {
         let guard = self.image_data.read().unwrap();
         guard.frames.len()
     }
 
+    /// The number of pages in a multi-page file, 1 for everything else.
+    pub fn len_pages(&self) -> usize {
+        let guard = self.image_data.read().unwrap();
+        guard.pages.as_ref().map(|pages| pages.len()).unwrap_or(1)
+    }
+
+    /// Writes the shown, possibly edited, page back to the pages before another one is
+    /// shown, so switching back and saving all pages keep the edits.
+    pub fn store_page(&mut self) {
+        let mut guard = self.image_data.write().unwrap();
+        if guard.pages.is_none() {
+            return;
+        }
+        let image_data = Arc::make_mut(&mut *guard);
+        if let Some(pages) = image_data.pages.as_mut()
+            && let Some(page) = Arc::make_mut(pages).get_mut(self.page)
+        {
+            *page = image_data.frames[0].clone();
+        }
+    }
+
     pub fn crop(&self, cut: Rect, proxy: EventLoopProxy<UserEvent>) {
         let rotation = self.rotation;
         let image_data = self.image_data.clone();
         let old_rotation = self.rotation;
         thread::spawn(move || {
-            let mut new_frames = Vec::new();
-            let guard = image_data.read().unwrap();
-            let frames = &guard.frames;
-            for frame in frames {
-                let buffer = match rotation {
-                    0 => frame.buffer().clone(),
-                    1 => frame.buffer().rotate90(),
-                    2 => frame.buffer().rotate180(),
-                    3 => frame.buffer().rotate270(),
-                    _ => unreachable!(),
-                };
-
-                let image = buffer.crop_imm(
-                    cut.x() as u32,
-                    cut.y() as u32,
-                    cut.width() as u32,
-                    cut.height() as u32,
-                );
-
-                new_frames.push(Image::with_delay(image, frame.delay));
-            }
-
+            let position = Vector2::new(cut.x() as u32, cut.y() as u32);
+            let size = Vector2::new(cut.width() as u32, cut.height() as u32);
+            let new_frames = image_data.read().unwrap().crop(position, size, rotation);
             proxy.send_output(Output::Crop(new_frames, old_rotation));
         });
     }
 
-    pub fn swap_frames(&mut self, wgpu: &WgpuState, frames: &mut Vec<Image>) {
+    /// An edited svg is a bitmap from then on, so its document goes along with the frames
+    /// it was rendered to and comes back when they are.
+    pub fn swap_frames(&mut self, wgpu: &WgpuState, snapshot: &mut Snapshot) {
         let mut guard = self.image_data.write().unwrap();
-        mem::swap(&mut Arc::make_mut(&mut *guard).frames, frames);
+        let image_data = Arc::make_mut(&mut *guard);
+        mem::swap(&mut image_data.frames, &mut snapshot.frames);
+        mem::swap(&mut image_data.svg, &mut snapshot.svg);
+        self.detail = None;
+        self.show_detail = false;
+        self.index = self.index.min(guard.frames.len() - 1);
         let (width, height) = guard.frames[0].buffer().dimensions();
         drop(guard);
-        self.mosaic = Mosaic::from_images(wgpu, self.image_data.read().unwrap().clone());
+        self.reload_textures(wgpu);
         self.size = Vector2::new(width as f32, height as f32);
     }
 
+    /// Uploads the frames again, e.g. after the color management preferences changed.
+    pub fn reload_textures(&mut self, wgpu: &WgpuState) {
+        let image_data = self.image_data.read().unwrap().clone();
+        let stretch = stretch(&image_data, self.exr_selection);
+        self.mosaic = Mosaic::from_images(wgpu, image_data, stretch);
+    }
+
+    /// The part of the image inside the window, in the pixel space of the mosaic.
+    fn visible_region(&self, window: Vector2<f32>) -> Option<Rect> {
+        let position = self.position - self.scaled() / 2.0;
+        let scale = Matrix4::from_scale(self.scale);
+        let translation = Matrix4::from_translation(Vector3::new(position.x, position.y, 0.0));
+        let matrix = translation * scale * self.get_flip_mat() * self.get_rotation_mat();
+        let inverse = matrix.invert()?;
+
+        let corners = [
+            Vector4::new(0.0, 0.0, 0.0, 1.0),
+            Vector4::new(window.x, 0.0, 0.0, 1.0),
+            Vector4::new(0.0, window.y, 0.0, 1.0),
+            Vector4::new(window.x, window.y, 0.0, 1.0),
+        ];
+        let mut start = self.size;
+        let mut end = Vector2::<f32>::zero();
+        for corner in corners {
+            let corner = inverse * corner;
+            start.x = start.x.min(corner.x);
+            start.y = start.y.min(corner.y);
+            end.x = end.x.max(corner.x);
+            end.y = end.y.max(corner.y);
+        }
+        start.x = start.x.max(0.0);
+        start.y = start.y.max(0.0);
+        end.x = end.x.min(self.size.x);
+        end.y = end.y.min(self.size.y);
+
+        if end.x <= start.x || end.y <= start.y {
+            return None;
+        }
+        Some(Rect::new(start, end - start))
+    }
+
+    /// Keeps an svg sharp when zoomed in past 100% by rendering the part of it inside
+    /// the window again in the background.
+    pub fn update_detail(&mut self, window: Vector2<f32>, proxy: &EventLoopProxy<UserEvent>) {
+        self.show_detail = false;
+        if self.scale <= 1.0 {
+            return;
+        }
+        let Some(svg) = self.image_data.read().unwrap().svg.clone() else {
+            return;
+        };
+        let Some(visible) = self.visible_region(window) else {
+            return;
+        };
+        if let Some(detail) = &self.detail
+            && detail.scale == self.scale
+            && detail.region.contains(&visible)
+        {
+            self.show_detail = true;
+            return;
+        }
+        if self.detail_pending {
+            return;
+        }
+
+        let margin = DETAIL_MARGIN / self.scale;
+        let start = Vector2::new(
+            (visible.left() - margin).max(0.0).floor(),
+            (visible.top() - margin).max(0.0).floor(),
+        );
+        let end = Vector2::new(
+            (visible.right() + margin).min(self.size.x).ceil(),
+            (visible.bottom() + margin).min(self.size.y).ceil(),
+        );
+        let region = Rect::new(start, end - start);
+        let output = (
+            (region.width() * self.scale).round() as u32,
+            (region.height() * self.scale).round() as u32,
+        );
+        let limit = Limits::default().max_texture_dimension_2d;
+        if output.0 > limit || output.1 > limit {
+            return;
+        }
+
+        self.detail_pending = true;
+        let raster = (self.size.x as u32, self.size.y as u32);
+        let scale = self.scale;
+        let proxy = proxy.clone();
+        thread::spawn(move || {
+            let image = svg.render_region(
+                raster,
+                (region.x(), region.y(), region.width(), region.height()),
+                output,
+            );
+            let detail = DetailImage {
+                svg,
+                region,
+                scale,
+                image,
+            };
+            let _ = proxy.send_event(UserEvent::SvgDetail(Some(detail)));
+        });
+    }
+
+    pub fn set_detail(&mut self, wgpu: &WgpuState, detail: DetailImage) {
+        self.detail_pending = false;
+        let current = self.image_data.read().unwrap().svg.clone();
+        if !current.is_some_and(|svg| Arc::ptr_eq(&svg, &detail.svg)) {
+            return;
+        }
+        let Some(image) = detail.image else {
+            return;
+        };
+        let region = detail.region;
+        // Exposure and tone mapping come from the uniform shared with the mosaic.
+        let transform =
+            ColorTransform::new(self.image_data.read().unwrap().raw_metadata.icc.as_deref());
+        let tile = Tile::new(
+            wgpu,
+            &image,
+            transform.as_deref(),
+            region.position,
+            region.position + region.size,
+        );
+        self.detail = Some(Detail {
+            tile,
+            region,
+            scale: detail.scale,
+        });
+    }
+
+    /// Writes the sidecar of a camera raw file in the background.
+    pub fn store_raw_settings(&self) {
+        if let Some(raw) = self.image_data.read().unwrap().raw.clone() {
+            let settings = self.raw_settings;
+            thread::spawn(move || raw.store_settings(&settings));
+        }
+    }
+
     pub fn rotation(&self) -> i32 {
         self.rotation
     }
@@ -305,3 +638,12 @@ impl ImageView {
             .handle_drag(ui, self.position, self.rotated_size(), self.scale);
     }
 }
+
+/// Single OpenEXR channels such as depth are shown stretched to their range, the frames
+/// keep the values so they can be saved.
+fn stretch(image_data: &ImageData, selection: ExrSelection) -> bool {
+    image_data
+        .exr
+        .as_ref()
+        .is_some_and(|exr| exr.is_grayscale(selection))
+}
@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    num::NonZeroUsize,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
+};
+
+use lru::LruCache;
+use moxcms::{
+    ColorProfile, DataColorSpace, Layout, Transform8BitExecutor, TransformF32Executor,
+    TransformOptions,
+};
+
+use crate::app::preferences::PREFERENCES;
+
+/// The embedded profile of an image and the path of the monitor profile.
+type ProfilePair = (Option<Vec<u8>>, String);
+
+/// Building a transform takes far longer than converting a tile with it. Only the last few
+/// are kept since every distinct embedded profile is a new key.
+static TRANSFORMS: LazyLock<Mutex<LruCache<ProfilePair, Arc<ColorTransform>>>> =
+    LazyLock::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(8).unwrap())));
+
+/// Converts rgba pixels from the embedded profile of an image to the display profile.
+pub struct ColorTransform {
+    source: ColorProfile,
+    target: ColorProfile,
+    rgba8: OnceLock<Option<Arc<Transform8BitExecutor>>>,
+    rgba_f32: OnceLock<Option<Arc<TransformF32Executor>>>,
+}
+
+impl ColorTransform {
+    /// Returns `None` when color management is turned off or there is nothing to convert,
+    /// i.e. an image without a profile shown on a monitor without a configured profile.
+    pub fn new(icc: Option<&[u8]>) -> Option<Arc<Self>> {
+        let (color_managed, monitor_profile) = {
+            let preferences = PREFERENCES.lock().unwrap();
+            (
+                preferences.color_managed,
+                preferences.monitor_profile.clone(),
+            )
+        };
+        if !color_managed {
+            return None;
+        }
+
+        let key = (icc.map(<[u8]>::to_vec), monitor_profile);
+        let mut transforms = TRANSFORMS.lock().unwrap();
+        if let Some(transform) = transforms.get(&key) {
+            return Some(transform.clone());
+        }
+        let transform = Arc::new(Self::build(icc, &key.1)?);
+        transforms.put(key, transform.clone());
+        Some(transform)
+    }
+
+    fn build(icc: Option<&[u8]>, monitor_profile: &str) -> Option<Self> {
+        let source = icc
+            .and_then(|icc| ColorProfile::new_from_slice(icc).ok())
+            .filter(|profile| profile.color_space == DataColorSpace::Rgb);
+        let target = if monitor_profile.is_empty() {
+            None
+        } else {
+            read_profile(monitor_profile)
+        };
+        if source.is_none() && target.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            source: source.unwrap_or_else(ColorProfile::new_srgb),
+            target: target.unwrap_or_else(ColorProfile::new_srgb),
+            rgba8: OnceLock::new(),
+            rgba_f32: OnceLock::new(),
+        })
+    }
+
+    pub fn apply(&self, pixels: &mut [u8]) {
+        let transform = self.rgba8.get_or_init(|| {
+            self.source
+                .create_transform_8bit(
+                    Layout::Rgba,
+                    &self.target,
+                    Layout::Rgba,
+                    TransformOptions::default(),
+                )
+                .ok()
+        });
+        let Some(transform) = transform else {
+            return;
+        };
+        let source = pixels.to_vec();
+        let _ = transform.transform(&source, pixels);
+    }
+
+    /// Same as [`ColorTransform::apply`] for gamma encoded values in the 0 to 1 range.
+    pub fn apply_f32(&self, pixels: &mut [f32]) {
+        let transform = self.rgba_f32.get_or_init(|| {
+            self.source
+                .create_transform_f32(
+                    Layout::Rgba,
+                    &self.target,
+                    Layout::Rgba,
+                    TransformOptions::default(),
+                )
+                .ok()
+        });
+        let Some(transform) = transform else {
+            return;
+        };
+        let source = pixels.to_vec();
+        let _ = transform.transform(&source, pixels);
+    }
+}
+
+/// Reads an rgb icc profile from a file, `None` if it cannot be used for the monitor.
+pub fn read_profile(path: &str) -> Option<ColorProfile> {
+    fs::read(path)
+        .ok()
+        .and_then(|icc| ColorProfile::new_from_slice(&icc).ok())
+        .filter(|profile| profile.color_space == DataColorSpace::Rgb)
+}
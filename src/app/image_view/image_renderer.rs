@@ -58,6 +58,9 @@ pub struct Uniform {
     pub saturation: f32,
     pub grayscale: u32,
     pub invert: u32,
+    pub exposure: f32,
+    pub tonemap: u32,
+    pub gamma: f32,
 }
 
 impl Default for Uniform {
@@ -71,6 +74,9 @@ impl Default for Uniform {
             saturation: Default::default(),
             grayscale: Default::default(),
             invert: Default::default(),
+            exposure: Default::default(),
+            tonemap: Default::default(),
+            gamma: 1.0,
         }
     }
 }
@@ -216,6 +222,16 @@ impl Renderer {
         let mosaic = &image_view.mosaic[image_view.index];
         rpass.set_index_buffer(mosaic.indices.slice(..), wgpu::IndexFormat::Uint32);
 
+        if image_view.show_detail
+            && let Some(detail) = &image_view.detail
+        {
+            let tile = detail.tile();
+            rpass.set_bind_group(1, &tile.texture.diffuse_bind_group, &[]);
+            rpass.set_vertex_buffer(0, tile.vertices.slice(..));
+            rpass.draw_indexed(0..6, 0, 0..1);
+            return;
+        }
+
         for tile in &mosaic.tiles {
             rpass.set_bind_group(1, &tile.texture.diffuse_bind_group, &[]);
             rpass.set_vertex_buffer(0, tile.vertices.slice(..));
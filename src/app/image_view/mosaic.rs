@@ -4,7 +4,7 @@ use cgmath::Vector2;
 use image::{DynamicImage, SubImage};
 use wgpu::{Limits, util::DeviceExt};
 
-use super::{image_renderer::Vertex, texture};
+use super::{color_management::ColorTransform, image_renderer::Vertex, texture};
 use crate::{WgpuState, util::ImageData};
 
 pub struct Tile {
@@ -12,13 +12,43 @@ pub struct Tile {
     pub texture: texture::Texture,
 }
 
+impl Tile {
+    /// Uploads a single image covering `start` to `end` in the pixel space of the mosaic.
+    pub fn new(
+        wgpu: &WgpuState,
+        image: &DynamicImage,
+        transform: Option<&ColorTransform>,
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+    ) -> Self {
+        let mut encoder = wgpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let vertices = get_vertex_buffer(wgpu, start.x, start.y, end.x, end.y);
+        let texture = texture::Texture::from_image(
+            &mut encoder,
+            &wgpu.device,
+            &wgpu.queue,
+            image,
+            transform,
+            None,
+            None,
+        );
+        wgpu.queue.submit(iter::once(encoder.finish()));
+        Self { vertices, texture }
+    }
+}
+
 pub struct Mosaic {
     pub tiles: Vec<Tile>,
     pub indices: wgpu::Buffer,
 }
 
 impl Mosaic {
-    pub fn from_images(wgpu: &WgpuState, images: Arc<ImageData>) -> Vec<Self> {
+    /// `stretch` shows float frames stretched to their range, see [`texture::value_range`].
+    pub fn from_images(wgpu: &WgpuState, images: Arc<ImageData>, stretch: bool) -> Vec<Self> {
         let limit = Limits::default().max_texture_dimension_2d;
 
         let mut encoder = wgpu
@@ -27,10 +57,13 @@ impl Mosaic {
                 label: Some("Render Encoder"),
             });
 
+        let transform = ColorTransform::new(images.raw_metadata.icc.as_deref());
+
         let mut output = Vec::new();
         for image in &images.frames {
             let mut tiles = Vec::new();
             let image = &image.image;
+            let range = stretch.then(|| texture::value_range(image)).flatten();
 
             let tile_width = (image.width() / limit) + 1;
             let tile_height = (image.height() / limit) + 1;
@@ -61,6 +94,8 @@ impl Mosaic {
                             &wgpu.device,
                             &wgpu.queue,
                             image,
+                            transform.as_deref(),
+                            range,
                             None,
                         )
                     } else {
@@ -71,6 +106,8 @@ impl Mosaic {
                             &wgpu.device,
                             &wgpu.queue,
                             &sub_image,
+                            transform.as_deref(),
+                            range,
                             None,
                         )
                     };
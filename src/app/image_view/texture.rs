@@ -1,6 +1,10 @@
 use std::{borrow::Cow, cmp};
 
-use image::GenericImageView;
+use half::f16;
+use image::{DynamicImage, GenericImageView};
+use simp::io::tonemap::{is_linear, srgb_to_linear};
+
+use super::color_management::ColorTransform;
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -10,47 +14,85 @@ pub struct Texture {
     pub diffuse_bind_group: wgpu::BindGroup,
 }
 
-/*fn to_u8<T: Copy + bytemuck::Pod>(input: Vec<T>) -> Vec<u8> {
-    use std::mem;
-    let cap = input.capacity() * mem::size_of::<T>();
-    let len = input.len() * mem::size_of::<T>();
-    let ptr = input.as_ptr() as *mut u8;
-    mem::forget(input);
-    unsafe { Vec::from_raw_parts(ptr, len, cap) }
-}*/
+/// 16 bit and float images are uploaded as half floats so they keep their precision
+/// and float images keep values above 1 for the exposure control.
+fn is_high_precision(img: &DynamicImage) -> bool {
+    matches!(
+        img,
+        DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+            | DynamicImage::ImageRgb32F(_)
+            | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// The lowest and highest finite value of the color channels of a float image.
+pub fn value_range(img: &DynamicImage) -> Option<(f32, f32)> {
+    let values: &[f32] = match img {
+        DynamicImage::ImageRgb32F(image) => image.as_raw(),
+        DynamicImage::ImageRgba32F(image) => image.as_raw(),
+        _ => return None,
+    };
+    let channels = img.color().channel_count() as usize;
+    let (min, max) = values
+        .chunks_exact(channels)
+        .flat_map(|pixel| &pixel[..3])
+        .filter(|value| value.is_finite())
+        .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    (min <= max).then_some((min, max))
+}
 
 impl Texture {
+    /// `range` stretches the values of a float image to black and white, for data such as
+    /// depth that is far outside of 0 to 1.
     pub fn from_image(
         command_encoder: &mut wgpu::CommandEncoder,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        img: &image::DynamicImage,
+        img: &DynamicImage,
+        transform: Option<&ColorTransform>,
+        range: Option<(f32, f32)>,
         label: Option<&str>,
     ) -> Self {
         let (width, height) = img.dimensions();
         let mip_level_count = (cmp::max(width, height).ilog2() + 1).min(10);
 
-        // TODO maybe reenable this sometime
-        /*#[rustfmt::skip]
-        let (bytes_per_pixel, format, bytes):  (_, _, Cow<[u8]>) = match img {
-            image::DynamicImage::ImageLuma8(_) => (4, wgpu::TextureFormat::Rgba8UnormSrgb, img.to_rgba8().into_raw().into()),
-            image::DynamicImage::ImageLumaA8(_) => (4, wgpu::TextureFormat::Rgba8UnormSrgb, img.to_rgba8().into_raw().into()),
-            image::DynamicImage::ImageRgb8(_) => (4, wgpu::TextureFormat::Rgba8UnormSrgb, img.to_rgba8().into_raw().into()),
-            image::DynamicImage::ImageRgba8(_) => (4, wgpu::TextureFormat::Rgba8UnormSrgb, img.as_bytes().into()),
-            image::DynamicImage::ImageLuma16(_) => (16, wgpu::TextureFormat::Rgba32Float, to_u8(img.to_rgba32f().into_raw()).into()),
-            image::DynamicImage::ImageLumaA16(_) => (16, wgpu::TextureFormat::Rgba32Float, to_u8(img.to_rgba32f().into_raw()).into()),
-            image::DynamicImage::ImageRgb16(_) => (16, wgpu::TextureFormat::Rgba32Float, to_u8(img.to_rgba32f().into_raw()).into()),
-            image::DynamicImage::ImageRgba16(_) => (16, wgpu::TextureFormat::Rgba32Float, img.as_bytes().into()),
-            image::DynamicImage::ImageRgb32F(_) => (16, wgpu::TextureFormat::Rgba32Float, to_u8(img.to_rgba32f().into_raw()).into()),
-            image::DynamicImage::ImageRgba32F(_) => (16, wgpu::TextureFormat::Rgba32Float, img.as_bytes().into()),
-            _ => (4, wgpu::TextureFormat::Rgba8UnormSrgb, img.to_rgba8().into_raw().into()),
-        };*/
-
-        let (bytes_per_pixel, format, bytes): (_, _, Cow<[u8]>) = (
-            4,
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            img.to_rgba8().into_raw().into(),
-        );
+        // The texture holds linear values, the shader converts them back after adjusting exposure.
+        let (bytes_per_pixel, format, bytes): (_, _, Cow<[u8]>) = if is_high_precision(img) {
+            let mut rgba = img.to_rgba32f().into_raw();
+            if let Some((min, max)) = range {
+                let scale = if max > min { 1.0 / (max - min) } else { 1.0 };
+                for pixel in rgba.chunks_exact_mut(4) {
+                    for value in &mut pixel[..3] {
+                        *value = srgb_to_linear(((*value - min) * scale).clamp(0.0, 1.0));
+                    }
+                }
+            } else if !is_linear(img) {
+                if let Some(transform) = transform {
+                    transform.apply_f32(&mut rgba);
+                }
+                for pixel in rgba.chunks_exact_mut(4) {
+                    for value in &mut pixel[..3] {
+                        *value = srgb_to_linear(*value);
+                    }
+                }
+            }
+            let bytes = rgba
+                .into_iter()
+                .flat_map(|value| f16::from_f32(value.min(f16::MAX.to_f32())).to_le_bytes())
+                .collect::<Vec<u8>>();
+            (8, wgpu::TextureFormat::Rgba16Float, bytes.into())
+        } else {
+            let mut rgba = img.to_rgba8().into_raw();
+            if let Some(transform) = transform {
+                transform.apply(&mut rgba);
+            }
+            (4, wgpu::TextureFormat::Rgba8UnormSrgb, rgba.into())
+        };
 
         let size = wgpu::Extent3d {
             width,
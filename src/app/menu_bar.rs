@@ -1,6 +1,8 @@
 use std::fs;
 
-use super::{App, TOP_BAR_SIZE, load_image, new_window, op_queue::Op, save_image};
+use super::{
+    App, TOP_BAR_SIZE, load_image, new_window, op_queue::Op, preferences::PREFERENCES, save_image,
+};
 use crate::{WgpuState, util::UserEvent};
 
 impl App {
@@ -23,6 +25,25 @@ impl App {
                             ui.close();
                         }
 
+                        if ui
+                            .add_enabled(
+                                self.image_view.is_some(),
+                                egui::Button::new("Gallery").shortcut_text("G"),
+                            )
+                            .clicked()
+                        {
+                            self.toggle_gallery();
+                            ui.close();
+                        }
+
+                        let mut filmstrip = PREFERENCES.lock().unwrap().filmstrip;
+                        if ui
+                            .add(egui::Checkbox::new(&mut filmstrip, "Filmstrip"))
+                            .changed()
+                        {
+                            PREFERENCES.lock().unwrap().filmstrip = filmstrip;
+                        }
+
                         if ui
                             .add_enabled(
                                 self.image_view.is_some(),
@@ -38,6 +59,40 @@ impl App {
                             ui.close();
                         }
 
+                        if ui
+                            .add_enabled(
+                                self.image_view
+                                    .as_ref()
+                                    .is_some_and(|view| view.len_pages() > 1),
+                                egui::Button::new("Save all pages as"),
+                            )
+                            .clicked()
+                        {
+                            save_image::open_pages(
+                                self.current_filename.clone(),
+                                self.proxy.clone(),
+                                wgpu,
+                            );
+                            ui.close();
+                        }
+
+                        if ui
+                            .add_enabled(
+                                self.image_view.as_ref().is_some_and(|view| {
+                                    view.image_data.read().unwrap().svg.is_some()
+                                }),
+                                egui::Button::new("Export svg at size"),
+                            )
+                            .clicked()
+                        {
+                            save_image::open_svg_export(
+                                self.current_filename.clone(),
+                                self.proxy.clone(),
+                                wgpu,
+                            );
+                            ui.close();
+                        }
+
                         ui.separator();
 
                         if ui
@@ -243,6 +298,68 @@ impl App {
                             ui.close();
                         }
 
+                        if ui
+                            .add_enabled(self.image_view.is_some(), egui::Button::new("Exposure"))
+                            .clicked()
+                        {
+                            self.exposure_visible = true;
+                            ui.close();
+                        }
+
+                        if ui
+                            .add_enabled(
+                                self.image_view.is_some()
+                                    && self
+                                        .image_view
+                                        .as_ref()
+                                        .unwrap()
+                                        .image_data
+                                        .read()
+                                        .unwrap()
+                                        .exr
+                                        .is_some(),
+                                egui::Button::new("EXR layers"),
+                            )
+                            .clicked()
+                        {
+                            self.exr_layers_visible = true;
+                            ui.close();
+                        }
+
+                        if ui
+                            .add_enabled(
+                                self.image_view.as_ref().is_some_and(|view| {
+                                    view.image_data.read().unwrap().psd.is_some()
+                                }),
+                                egui::Button::new("PSD layers"),
+                            )
+                            .clicked()
+                        {
+                            self.psd_layers_visible = true;
+                            ui.close();
+                        }
+
+                        // Only the embedded preview of a raw file may have been loaded, the
+                        // file is developed when the window is opened and shows it then.
+                        let (raw, preview) =
+                            self.image_view.as_ref().map_or((false, false), |view| {
+                                let image_data = view.image_data.read().unwrap();
+                                (image_data.raw.is_some(), image_data.preview)
+                            });
+                        if ui
+                            .add_enabled(
+                                raw || (preview && self.view_available()),
+                                egui::Button::new("RAW development"),
+                            )
+                            .clicked()
+                        {
+                            if !raw {
+                                self.queue(Op::Develop);
+                            }
+                            self.raw_visible = true;
+                            ui.close();
+                        }
+
                         if ui
                             .add_enabled(
                                 self.view_available(),
@@ -265,6 +382,19 @@ impl App {
                             ui.close();
                         }
 
+                        if ui
+                            .add_enabled(
+                                self.image_view
+                                    .as_ref()
+                                    .is_some_and(|view| view.len_frames() > 1),
+                                egui::Button::new("Frames"),
+                            )
+                            .clicked()
+                        {
+                            self.frame_editor.visible = true;
+                            ui.close();
+                        }
+
                         ui.separator();
 
                         if ui
@@ -299,7 +429,7 @@ impl App {
                                 && let Some(ref path) = view.path
                             {
                                 super::delete(
-                                    path.clone(),
+                                    vec![path.clone()],
                                     self.dialog_manager.get_proxy(),
                                     self.proxy.clone(),
                                 );
@@ -1,30 +1,28 @@
 use std::{
-    collections::HashSet,
+    collections::{HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Condvar, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
 use cgmath::Vector2;
-use image::{
-    ColorType, DynamicImage,
-    imageops::{
-        FilterType,
-        colorops::{contrast_in_place, huerotate_in_place},
-    },
-};
+use image::{ColorType, imageops::FilterType};
+use simp::io::{ExrSelection, PsdVisibility, RawSettings, ToneMapOperator};
 use winit::event_loop::EventLoopProxy;
 
-use self::imageops::{adjust_saturation_in_place, brighten_in_place};
 use super::{
     cache::Cache,
     clipboard,
     dialog_manager::DialogProxy,
     image_list::ImageList,
     image_view::ImageView,
-    load_image::{load_from_bytes, load_uncached},
-    save_image,
+    load_image::{develop_raw, load_from_bytes, load_uncached},
+    preferences::{PREFERENCES, RawPreview},
+    prefetch::{AHEAD, BEHIND},
+    save_image::{self, SaveKind},
+    thumbnails::Thumbnailer,
 };
 use crate::{
     app::undo_stack::UndoStack,
@@ -32,7 +30,7 @@ use crate::{
     util::{Image, ImageData, UserEvent, extensions::EXTENSIONS},
 };
 
-mod imageops;
+mod transform;
 
 #[derive(Debug)]
 pub enum Op {
@@ -41,6 +39,8 @@ pub enum Op {
     Next,
     Prev,
     Save(PathBuf),
+    SavePages(PathBuf),
+    ExportSvg(PathBuf),
     Resize(Vector2<u32>, FilterType),
     Color {
         hue: f32,
@@ -50,17 +50,63 @@ pub enum Op {
         grayscale: bool,
         invert: bool,
     },
+    ToneMap {
+        exposure: f32,
+        gamma: f32,
+        operator: ToneMapOperator,
+    },
     Crop(Rect),
     ColorSpace(ColorType),
+    ExrLayer(ExrSelection),
+    Page(usize),
+    PsdVisibility(PsdVisibility),
+    RawDevelop(RawSettings),
     FlipHorizontal,
     FlipVertical,
     Rotate(i32),
+    /// Removes the frames from the first to the second index, both included.
+    DeleteFrames(usize, usize),
+    DuplicateFrame(usize),
+    ReverseFrames,
+    /// Sets the delay of one frame, or of all frames when the index is `None`.
+    FrameDelay(Option<usize>, Duration),
+    /// Saves one frame as a new image.
+    ExtractFrame(PathBuf, usize),
+    /// Loads the full image of the preview being shown whatever the raw preview preference.
+    Develop,
     Undo,
     Redo,
     Close,
     Copy,
     Paste,
-    Delete(PathBuf),
+    Delete(Vec<PathBuf>),
+}
+
+impl Op {
+    /// Edits and saves, a preview would lose the edits once the full image replaces it or
+    /// save its own pixels.
+    fn needs_full_image(&self) -> bool {
+        matches!(
+            self,
+            Op::Save(..)
+                | Op::SavePages(..)
+                | Op::ExportSvg(..)
+                | Op::ExtractFrame(..)
+                | Op::Resize(..)
+                | Op::Color { .. }
+                | Op::ToneMap { .. }
+                | Op::Crop(..)
+                | Op::ColorSpace(..)
+                | Op::ExrLayer(..)
+                | Op::Page(..)
+                | Op::PsdVisibility(..)
+                | Op::RawDevelop(..)
+                | Op::DeleteFrames(..)
+                | Op::DuplicateFrame(..)
+                | Op::ReverseFrames
+                | Op::FrameDelay(..)
+        )
+    }
 }
 
 pub enum Output {
@@ -70,8 +116,18 @@ pub enum Output {
     FlipVertical,
     Resize(Vec<Image>),
     Color(Vec<Image>),
+    ToneMap(Vec<Image>),
     Crop(Vec<Image>, i32),
     ColorSpace(Vec<Image>),
+    ExrLayer(Vec<Image>, ExrSelection),
+    Page(Vec<Image>, usize),
+    PsdVisibility(Vec<Image>, PsdVisibility),
+    RawDevelop(Vec<Image>, RawSettings),
+    /// Frames edited in the frame editor, the index is the frame to show afterwards.
+    Frames {
+        frames: Vec<Image>,
+        index: usize,
+    },
     Undo,
     Redo,
     Close,
@@ -93,17 +149,57 @@ impl UserEventLoopProxyExt for EventLoopProxy<UserEvent> {
 #[derive(Default)]
 pub struct LoadingInfo {
     target_file: Option<PathBuf>,
+    /// When the target file was navigated to.
+    opened: Option<Instant>,
     loading: HashSet<PathBuf>,
+    /// The file last opened, developed raw files are only shown if they are still this one.
+    current: Option<PathBuf>,
+    /// Raw files being developed after their embedded preview was loaded.
+    developing: HashSet<PathBuf>,
+    /// The images around the current one that are prefetched, their develops are kept.
+    prefetching: HashSet<PathBuf>,
+}
+
+impl LoadingInfo {
+    /// Whether an image that was navigated to less than `limit` ago is still being loaded.
+    pub fn is_opening(&self, limit: Duration) -> bool {
+        self.target_file.is_some() && self.opened.is_some_and(|opened| opened.elapsed() < limit)
+    }
+
+    /// Replaces the images around the current one.
+    pub fn set_prefetching(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.prefetching = paths.into_iter().collect();
+    }
+
+    /// Marks an image as no longer being decoded and wakes up those waiting for it.
+    fn loaded(&mut self, path: &Path) {
+        self.loading.remove(path);
+        LOADED.notify_all();
+    }
+}
+
+/// Notified whenever an image stops being decoded, used with the loading info lock.
+static LOADED: Condvar = Condvar::new();
+
+/// Blocks until nobody is decoding the image anymore.
+pub fn wait_for_load(path: &Path, loading_info: &Mutex<LoadingInfo>) {
+    let guard = loading_info.lock().unwrap();
+    let _guard = LOADED
+        .wait_while(guard, |info| info.loading.contains(path))
+        .unwrap();
 }
 
 pub struct OpQueue {
     working: bool,
     loading_info: Arc<Mutex<LoadingInfo>>,
+    developer: Developer,
     proxy: EventLoopProxy<UserEvent>,
     dialog_proxy: DialogProxy,
     stack: UndoStack,
     pub cache: Arc<Cache>,
     pub image_list: ImageList,
+    pub thumbnailer: Thumbnailer,
+    no_cache: bool,
 }
 
 impl OpQueue {
@@ -112,24 +208,57 @@ impl OpQueue {
         dialog_proxy: DialogProxy,
         no_cache: bool,
     ) -> Self {
-        const CACHE_SIZE: usize = 1_000_000_000;
-        let cache = Arc::new(Cache::new(if no_cache { 0 } else { CACHE_SIZE }));
+        let budget = if no_cache {
+            0
+        } else {
+            PREFERENCES.lock().unwrap().cache_budget()
+        };
+        let cache = Arc::new(Cache::new(budget));
         let loading_info = Arc::new(Mutex::new(LoadingInfo::default()));
+        let developer = Developer::new(cache.clone(), proxy.clone(), loading_info.clone());
 
         Self {
             working: false,
-            image_list: ImageList::new(cache.clone(), proxy.clone(), loading_info.clone()),
+            image_list: ImageList::new(
+                cache.clone(),
+                proxy.clone(),
+                loading_info.clone(),
+                developer.clone(),
+            ),
+            thumbnailer: Thumbnailer::new(cache.clone(), proxy.clone(), loading_info.clone()),
             loading_info,
+            developer,
             dialog_proxy,
             stack: UndoStack::new(),
             proxy,
             cache,
+            no_cache,
+        }
+    }
+
+    /// Changes the memory budget of the cache, unless it was disabled on the command line.
+    pub fn set_cache_budget(&self, bytes: usize) {
+        if !self.no_cache {
+            self.cache.set_max_size(bytes);
         }
     }
 
     pub fn queue(&mut self, op: Op, view: Option<&ImageView>) {
         if !self.working {
             self.working = true;
+            if op.needs_full_image()
+                && let Some(view) = view
+                && view.image_data.read().unwrap().is_preview()
+            {
+                if let Some(path) = &view.path {
+                    self.developer.queue(path, path, false);
+                }
+                self.proxy.send_output(Output::Done);
+                let _ = self.proxy.send_event(UserEvent::ErrorMessage(String::from(
+                    "The full image is still loading, try again once it is shown",
+                )));
+                return;
+            }
             match op {
                 Op::LoadPath(path, use_cache) => {
                     self.load(path, use_cache, self.stack.is_edited());
@@ -158,10 +287,41 @@ impl OpQueue {
                             self.proxy.clone(),
                             self.dialog_proxy.clone(),
                             path,
-                            view.image_data.clone(),
-                            view.rotation(),
-                            view.horizontal_flip,
-                            view.vertical_flip,
+                            view,
+                            SaveKind::Frames,
+                        )
+                    }
+                }
+                Op::SavePages(path) => {
+                    if let Some(view) = view {
+                        save_image::save(
+                            self.proxy.clone(),
+                            self.dialog_proxy.clone(),
+                            path,
+                            view,
+                            SaveKind::Pages,
+                        )
+                    }
+                }
+                Op::ExportSvg(path) => {
+                    if let Some(view) = view {
+                        save_image::save(
+                            self.proxy.clone(),
+                            self.dialog_proxy.clone(),
+                            path,
+                            view,
+                            SaveKind::SvgExport,
+                        )
+                    }
+                }
+                Op::ExtractFrame(path, index) => {
+                    if let Some(view) = view {
+                        save_image::save(
+                            self.proxy.clone(),
+                            self.dialog_proxy.clone(),
+                            path,
+                            view,
+                            SaveKind::Frame(index),
                         )
                     }
                 }
@@ -174,6 +334,12 @@ impl OpQueue {
                 Op::FlipVertical => {
                     self.proxy.send_output(Output::FlipVertical);
                 }
+                Op::Develop => {
+                    if let Some(path) = view.and_then(|view| view.path.as_ref()) {
+                        self.developer.queue(path, path, false);
+                    }
+                    self.proxy.send_output(Output::Done);
+                }
                 Op::Undo => {
                     self.proxy.send_output(Output::Undo);
                 }
@@ -185,35 +351,7 @@ impl OpQueue {
                     let dialog_proxy = self.dialog_proxy.clone();
                     if self.stack.is_edited() {
                         thread::spawn(move || {
-                            let close = dialog_proxy
-                                .spawn_dialog("Unsaved changes", move |ui, enter| {
-                                    ui.label(
-                            "You have unsaved changes are you sure you want to close this image?",
-                        );
-                                    ui.with_layout(
-                                        egui::Layout::left_to_right(egui::Align::LEFT),
-                                        |ui| {
-                                            if ui.button("Ok").clicked() {
-                                                return Some(true);
-                                            }
-
-                                            if ui.button("Cancel").clicked() {
-                                                return Some(false);
-                                            }
-
-                                            if *enter {
-                                                *enter = false;
-                                                return Some(true);
-                                            }
-
-                                            None
-                                        },
-                                    )
-                                    .inner
-                                })
-                                .wait()
-                                .unwrap_or(false);
-                            if close {
+                            if confirm_close(&dialog_proxy) {
                                 proxy.send_output(Output::Close);
                             } else {
                                 proxy.send_output(Output::Done);
@@ -227,44 +365,17 @@ impl OpQueue {
                     let image_data = view.as_ref().unwrap().image_data.clone();
                     let proxy = self.proxy.clone();
                     thread::spawn(move || {
-                        let guard = image_data.read().unwrap();
-                        let mut new = Vec::new();
-                        for image in guard.frames.iter() {
-                            let buffer = image.buffer();
-                            let buffer = match color_type {
-                                ColorType::L8 => DynamicImage::ImageLuma8(buffer.to_luma8()),
-                                ColorType::La8 => {
-                                    DynamicImage::ImageLumaA8(buffer.to_luma_alpha8())
-                                }
-                                ColorType::Rgb8 => DynamicImage::ImageRgb8(buffer.to_rgb8()),
-                                ColorType::Rgba8 => DynamicImage::ImageRgba8(buffer.to_rgba8()),
-                                ColorType::L16 => DynamicImage::ImageLuma16(buffer.to_luma16()),
-                                ColorType::La16 => {
-                                    DynamicImage::ImageLumaA16(buffer.to_luma_alpha16())
-                                }
-                                ColorType::Rgb16 => DynamicImage::ImageRgb16(buffer.to_rgb16()),
-                                ColorType::Rgba16 => DynamicImage::ImageRgba16(buffer.to_rgba16()),
-                                ColorType::Rgb32F => DynamicImage::ImageRgb32F(buffer.to_rgb32f()),
-                                ColorType::Rgba32F => {
-                                    DynamicImage::ImageRgba32F(buffer.to_rgba32f())
-                                }
-                                _ => panic!("unknown color type this a bug"),
-                            };
-                            new.push(Image::with_delay(buffer, image.delay));
+                        match image_data.read().unwrap().color_space(color_type) {
+                            Some(new) => proxy.send_output(Output::ColorSpace(new)),
+                            None => proxy.send_output(Output::Done),
                         }
-                        proxy.send_output(Output::ColorSpace(new));
                     });
                 }
                 Op::Resize(size, resample) => {
                     let image_data = view.as_ref().unwrap().image_data.clone();
                     let proxy = self.proxy.clone();
                     thread::spawn(move || {
-                        let guard = image_data.read().unwrap();
-                        let mut new = Vec::new();
-                        for image in guard.frames.iter() {
-                            let buffer = image.buffer().resize_exact(size.x, size.y, resample);
-                            new.push(Image::with_delay(buffer, image.delay));
-                        }
+                        let new = image_data.read().unwrap().resize(size, resample);
                         proxy.send_output(Output::Resize(new));
                     });
                 }
@@ -279,37 +390,93 @@ impl OpQueue {
                     let image_data = view.as_ref().unwrap().image_data.clone();
                     let proxy = self.proxy.clone();
                     thread::spawn(move || {
-                        let guard = image_data.read().unwrap();
-                        let mut new: Vec<_> = guard.frames.clone();
-                        drop(guard);
-                        for image in &mut new {
-                            let buffer = image.buffer_mut();
-
-                            if hue != 0.0 {
-                                huerotate_in_place(buffer, hue as i32);
-                            }
-
-                            if contrast != 0.0 {
-                                contrast_in_place(buffer, contrast);
-                            }
-
-                            if saturation != 0.0 {
-                                adjust_saturation_in_place(buffer, saturation as f64);
-                            }
-
-                            if brightness != 0.0 {
-                                brighten_in_place(buffer, brightness as f64);
-                            }
-
-                            if grayscale {
-                                *buffer = DynamicImage::ImageLumaA8(imageops::grayscale(buffer));
-                            }
-
-                            if invert {
-                                buffer.invert();
+                        let image_data = image_data.read().unwrap().clone();
+                        let new = image_data
+                            .color(hue, saturation, contrast, brightness, grayscale, invert);
+                        proxy.send_output(Output::Color(new));
+                    });
+                }
+                Op::ToneMap {
+                    exposure,
+                    gamma,
+                    operator,
+                } => {
+                    let image_data = view.as_ref().unwrap().image_data.clone();
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        let new = image_data.tone_map(exposure, gamma, operator);
+                        proxy.send_output(Output::ToneMap(new));
+                    });
+                }
+                Op::ExrLayer(selection) => {
+                    let image_data = view.as_ref().unwrap().image_data.clone();
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        match image_data.exr_layer(selection) {
+                            Some(frames) => proxy.send_output(Output::ExrLayer(frames, selection)),
+                            None => proxy.send_output(Output::Done),
+                        }
+                    });
+                }
+                Op::Page(page) => {
+                    let image_data = view.as_ref().unwrap().image_data.clone();
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        match image_data.page(page) {
+                            Some(frames) => proxy.send_output(Output::Page(frames, page)),
+                            None => proxy.send_output(Output::Done),
+                        }
+                    });
+                }
+                Op::PsdVisibility(visibility) => {
+                    let image_data = view.as_ref().unwrap().image_data.clone();
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        match image_data.psd_composite(&visibility) {
+                            Some(frames) => {
+                                proxy.send_output(Output::PsdVisibility(frames, visibility))
                             }
+                            None => proxy.send_output(Output::Done),
                         }
-                        proxy.send_output(Output::Color(new));
+                    });
+                }
+                Op::RawDevelop(settings) => {
+                    let image_data = view.as_ref().unwrap().image_data.clone();
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        match image_data.raw_develop(&settings) {
+                            Some(frames) => proxy.send_output(Output::RawDevelop(frames, settings)),
+                            None => proxy.send_output(Output::Done),
+                        }
+                    });
+                }
+                op @ (Op::DeleteFrames(..)
+                | Op::DuplicateFrame(..)
+                | Op::ReverseFrames
+                | Op::FrameDelay(..)) => {
+                    let view = view.unwrap();
+                    let image_data = view.image_data.clone();
+                    let current = view.index;
+                    let proxy = self.proxy.clone();
+                    thread::spawn(move || {
+                        let image_data = image_data.read().unwrap().clone();
+                        let Some(frames) = op.apply(&image_data) else {
+                            proxy.send_output(Output::Done);
+                            return;
+                        };
+                        let last = frames.len() - 1;
+                        let index = match op {
+                            Op::DeleteFrames(start, _) => start.min(last),
+                            Op::DuplicateFrame(index) => index + 1,
+                            Op::ReverseFrames => last - current.min(last),
+                            _ => current,
+                        };
+                        proxy.send_output(Output::Frames { frames, index });
                     });
                 }
                 Op::Crop(rect) => {
@@ -321,15 +488,23 @@ impl OpQueue {
                 Op::Paste => {
                     clipboard::paste(self.proxy.clone());
                 }
-                Op::Delete(path) => match trash::delete(&path) {
-                    Ok(_) => match self.image_list.trash(&path) {
-                        Some(path) => {
-                            self.load(path, true, false);
-                        }
-                        None => {
-                            self.proxy.send_output(Output::Close);
+                Op::Delete(paths) => match trash::delete_all(&paths) {
+                    Ok(_) => {
+                        let current = view
+                            .and_then(|view| view.path.as_ref())
+                            .is_some_and(|path| paths.contains(path));
+                        match self.image_list.trash(&paths) {
+                            _ if !current => {
+                                self.proxy.send_output(Output::Done);
+                            }
+                            Some(path) => {
+                                self.load(path, true, false);
+                            }
+                            None => {
+                                self.proxy.send_output(Output::Close);
+                            }
                         }
-                    },
+                    }
                     Err(error) => {
                         let _ = self
                             .proxy
@@ -354,25 +529,34 @@ impl OpQueue {
             Err(_) => path_buf,
         };
 
-        {
-            let mut guard = self.loading_info.lock().unwrap();
-            guard.target_file = Some(path_buf.clone());
-            if guard.loading.contains(&path_buf) {
-                return;
-            } else {
-                guard.loading.insert(path_buf.clone());
-            }
-        }
-
-        if !use_cache {
-            self.cache.pop(&path_buf);
-        }
-
         let cache = self.cache.clone();
         let proxy = self.proxy.clone();
         let loading_info = self.loading_info.clone();
+        let developer = self.developer.clone();
         let dialog_proxy = self.dialog_proxy.clone();
         thread::spawn(move || {
+            // Asked before the image is marked as loading so nothing waits on the dialog.
+            if edited_prompt && !confirm_close(&dialog_proxy) {
+                proxy.send_output(Output::Done);
+                return;
+            }
+
+            {
+                let mut guard = loading_info.lock().unwrap();
+                guard.target_file = Some(path_buf.clone());
+                guard.opened = Some(Instant::now());
+                guard.current = Some(path_buf.clone());
+                // A prefetch is decoding it already and shows it once it is done now that
+                // it is the target.
+                if !guard.loading.insert(path_buf.clone()) {
+                    return;
+                }
+            }
+
+            if !use_cache {
+                cache.pop(&path_buf);
+            }
+
             let mut path = path_buf.clone();
             if path.is_dir() {
                 let mut paths = Vec::new();
@@ -401,56 +585,29 @@ impl OpQueue {
                 }
             }
 
-            if edited_prompt {
-                let close = dialog_proxy
-                    .spawn_dialog("Unsaved changes", move |ui, enter| {
-                        ui.label(
-                            "You have unsaved changes are you sure you want to close this image?",
-                        );
-
-                        ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
-                            if ui.button("Ok").clicked() {
-                                return Some(true);
-                            }
-
-                            if ui.button("Cancel").clicked() {
-                                return Some(false);
-                            }
-
-                            if *enter {
-                                *enter = false;
-                                return Some(true);
-                            }
-
-                            None
-                        })
-                        .inner
-                    })
-                    .wait()
-                    .unwrap_or(false);
-                if !close {
-                    proxy.send_output(Output::Done);
-                    return;
-                }
-            }
-
             if let Some(images) = cache.get(&path_buf) {
                 let mut guard = loading_info.lock().unwrap();
-                guard.loading.remove(&path_buf);
+                guard.loaded(&path_buf);
                 guard.target_file = None;
+                if images.is_preview() {
+                    develop(&images, &path_buf, &path, &developer, false);
+                }
                 proxy.send_output(Output::ImageLoaded(images, Some(path_buf)));
                 return;
             }
 
             let res = load_uncached(&path);
             let mut guard = loading_info.lock().unwrap();
-            guard.loading.remove(&path_buf);
+            guard.loaded(&path_buf);
             guard.target_file = None;
 
             match res {
                 Ok(images) => {
                     let images = Arc::new(images);
                     cache.put(path_buf.clone(), images.clone());
+                    if images.preview {
+                        develop(&images, &path_buf, &path, &developer, false);
+                    }
                     proxy.send_output(Output::ImageLoaded(images, Some(path)));
                 }
                 Err(error) => {
@@ -488,49 +645,214 @@ impl OpQueue {
     }
 }
 
-pub fn prefetch(
-    path: impl AsRef<Path>,
+/// Asks whether the unsaved changes can be thrown away, blocks until it is answered.
+fn confirm_close(dialog_proxy: &DialogProxy) -> bool {
+    dialog_proxy
+        .spawn_dialog("Unsaved changes", move |ui, enter| {
+            ui.label("You have unsaved changes are you sure you want to close this image?");
+
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
+                if ui.button("Ok").clicked() {
+                    return Some(true);
+                }
+
+                if ui.button("Cancel").clicked() {
+                    return Some(false);
+                }
+
+                if *enter {
+                    *enter = false;
+                    return Some(true);
+                }
+
+                None
+            })
+            .inner
+        })
+        .wait()
+        .unwrap_or(false)
+}
+
+/// Marks an image as being decoded. Returns false if it is already cached or someone else
+/// is decoding it, then it will end up in the cache without doing anything.
+pub fn claim(path_buf: &Path, cache: &Cache, loading_info: &Mutex<LoadingInfo>) -> bool {
+    let path_buf = path_buf.to_path_buf();
+    if cache.contains(&path_buf) {
+        return false;
+    }
+    loading_info.lock().unwrap().loading.insert(path_buf)
+}
+
+/// Decodes an image marked with [`claim`] into the cache and shows it if it was navigated
+/// to in the meantime. The embedded preview of a raw file is only developed in the
+/// background if `develop_preview` is set or the file is being shown.
+pub fn load_claimed(
+    path_buf: &Path,
+    cache: &Arc<Cache>,
+    proxy: &EventLoopProxy<UserEvent>,
+    loading_info: &Arc<Mutex<LoadingInfo>>,
+    developer: &Developer,
+    develop_preview: bool,
+) -> Option<Arc<ImageData>> {
+    let path_buf = path_buf.to_path_buf();
+    let res = load_uncached(&path_buf);
+    let mut guard = loading_info.lock().unwrap();
+    guard.loaded(&path_buf);
+    let target = guard.target_file.as_ref() == Some(&path_buf);
+
+    let images = match res {
+        Ok(images) => {
+            let images = Arc::new(images);
+            cache.put(path_buf.clone(), images.clone());
+            if images.preview && (develop_preview || target) {
+                develop(&images, &path_buf, &path_buf, developer, !target);
+            }
+            if target {
+                proxy.send_output(Output::ImageLoaded(images.clone(), Some(path_buf.clone())));
+            }
+            Some(images)
+        }
+        Err(error) => {
+            if target {
+                proxy.send_output(Output::Done);
+                let _ = proxy.send_event(UserEvent::ErrorMessage(error.to_string()));
+            }
+            None
+        }
+    };
+
+    if target {
+        guard.target_file = None;
+    }
+    images
+}
+
+/// Loads the full image in the background when only a preview was loaded, the embedded one
+/// of a raw file or a downscaled copy from the cache, and shows it if it is still the file
+/// being looked at. `path_buf` is the cache key, `background` is set for prefetched images.
+fn develop(
+    images: &ImageData,
+    path_buf: &Path,
+    path: &Path,
+    developer: &Developer,
+    background: bool,
+) {
+    if !images.downscaled && PREFERENCES.lock().unwrap().raw_preview != RawPreview::PreviewThenFull
+    {
+        return;
+    }
+    developer.queue(path_buf, path, background);
+}
+
+/// How many raw files are developed at once, each one takes a lot of memory and the
+/// pipeline already uses every core.
+const DEVELOP_WORKERS: usize = 2;
+/// Most develops waiting for a worker, enough for the current image and those prefetched
+/// around it.
+const MAX_DEVELOPS: usize = AHEAD + BEHIND + 1;
+
+struct DevelopJob {
+    /// The cache key.
+    path_buf: PathBuf,
+    path: PathBuf,
+}
+
+struct DevelopShared {
+    /// The most recently requested develop of an image being looked at is at the front and
+    /// those of prefetched images are at the back.
+    queue: Mutex<VecDeque<DevelopJob>>,
+    condvar: Condvar,
     cache: Arc<Cache>,
     proxy: EventLoopProxy<UserEvent>,
     loading_info: Arc<Mutex<LoadingInfo>>,
-) {
-    let path_buf = path.as_ref().to_path_buf();
+}
 
-    if cache.contains(&path_buf) {
-        return;
+/// Develops raw files and full sized images on a few background threads. Develops of
+/// images that were browsed past are dropped before they start.
+#[derive(Clone)]
+pub struct Developer {
+    shared: Arc<DevelopShared>,
+}
+
+impl Developer {
+    pub fn new(
+        cache: Arc<Cache>,
+        proxy: EventLoopProxy<UserEvent>,
+        loading_info: Arc<Mutex<LoadingInfo>>,
+    ) -> Self {
+        let shared = Arc::new(DevelopShared {
+            queue: Mutex::new(VecDeque::new()),
+            condvar: Condvar::new(),
+            cache,
+            proxy,
+            loading_info,
+        });
+        for _ in 0..DEVELOP_WORKERS {
+            let shared = shared.clone();
+            thread::spawn(move || develop_work(&shared));
+        }
+        Self { shared }
     }
 
-    {
-        let mut guard = loading_info.lock().unwrap();
-        if guard.loading.contains(&path_buf) {
-            return;
+    /// Like [`develop`] without looking at the preferences. Doesn't take the loading info
+    /// lock since the caller can be holding it.
+    fn queue(&self, path_buf: &Path, path: &Path, background: bool) {
+        let job = DevelopJob {
+            path_buf: path_buf.to_path_buf(),
+            path: path.to_path_buf(),
+        };
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.retain(|queued| queued.path_buf != job.path_buf);
+        if background {
+            queue.push_back(job);
         } else {
-            guard.loading.insert(path_buf.clone());
+            queue.push_front(job);
         }
+        queue.truncate(MAX_DEVELOPS);
+        self.shared.condvar.notify_one();
     }
+}
 
-    thread::spawn(move || {
-        let res = load_uncached(&path_buf);
-        let mut guard = loading_info.lock().unwrap();
-        guard.loading.remove(&path_buf);
-
-        match res {
-            Ok(images) => {
-                let images = Arc::new(images);
-                cache.put(path_buf.clone(), images.clone());
-                if guard.target_file.as_ref() == Some(&path_buf) {
-                    proxy.send_output(Output::ImageLoaded(images, Some(path_buf.clone())));
-                }
-            }
-            Err(error) => {
-                if guard.target_file.as_ref() == Some(&path_buf) {
-                    let _ = proxy.send_event(UserEvent::ErrorMessage(error.to_string()));
+fn develop_work(shared: &DevelopShared) {
+    loop {
+        let DevelopJob { path_buf, path } = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                match queue.pop_front() {
+                    Some(job) => break job,
+                    None => queue = shared.condvar.wait(queue).unwrap(),
                 }
             }
         };
 
-        if guard.target_file.as_ref() == Some(&path_buf) {
-            guard.target_file = None;
+        // Queued twice and developed in the meantime.
+        if shared
+            .cache
+            .get(&path_buf)
+            .is_some_and(|images| !images.is_preview())
+        {
+            continue;
+        }
+        {
+            let mut guard = shared.loading_info.lock().unwrap();
+            let wanted =
+                guard.current.as_ref() == Some(&path_buf) || guard.prefetching.contains(&path_buf);
+            if !wanted || !guard.developing.insert(path_buf.clone()) {
+                continue;
+            }
+        }
+
+        let res = develop_raw(&path);
+        let mut guard = shared.loading_info.lock().unwrap();
+        guard.developing.remove(&path_buf);
+        if let Ok(images) = res {
+            let images = Arc::new(images);
+            shared.cache.put(path_buf.clone(), images.clone());
+            if guard.current.as_ref() == Some(&path_buf) {
+                shared
+                    .proxy
+                    .send_output(Output::ImageLoaded(images, Some(path)));
+            }
         }
-    });
+    }
 }
@@ -0,0 +1,76 @@
+use cgmath::Vector2;
+use simp::io::COLOR_SPACES;
+
+use super::Op;
+use crate::util::{Image, ImageData};
+
+impl Op {
+    /// Checks the operation against `image_data`. The gui only builds ones that fit, those of
+    /// the batch runner come from the command line.
+    pub fn validate(&self, image_data: &ImageData) -> Result<(), String> {
+        let Some(frame) = image_data.frames.first() else {
+            return Err(String::from("the image has no frames"));
+        };
+        let (width, height) = (frame.buffer().width(), frame.buffer().height());
+        match *self {
+            Op::Crop(cut) => {
+                let fits = cut.x() >= 0.0
+                    && cut.y() >= 0.0
+                    && cut.width() >= 1.0
+                    && cut.height() >= 1.0
+                    && cut.x() + cut.width() <= width as f32
+                    && cut.y() + cut.height() <= height as f32;
+                if !fits {
+                    return Err(format!(
+                        "crop {}:{}:{}x{} does not fit the {width}x{height} image",
+                        cut.x(),
+                        cut.y(),
+                        cut.width(),
+                        cut.height()
+                    ));
+                }
+            }
+            Op::ColorSpace(color_type) if !COLOR_SPACES.contains(&color_type) => {
+                return Err(format!("cannot convert to {color_type:?}"));
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Applies an image editing operation to the pixels.
+    /// Returns `None` for operations that do not edit the image.
+    pub fn apply(&self, image_data: &ImageData) -> Option<Vec<Image>> {
+        let frames = match *self {
+            Op::Resize(size, resample) => image_data.resize(size, resample),
+            Op::Color {
+                hue,
+                saturation,
+                contrast,
+                brightness,
+                grayscale,
+                invert,
+            } => image_data.color(hue, saturation, contrast, brightness, grayscale, invert),
+            Op::ToneMap {
+                exposure,
+                gamma,
+                operator,
+            } => image_data.tone_map(exposure, gamma, operator),
+            Op::Crop(cut) => image_data.crop(
+                Vector2::new(cut.x() as u32, cut.y() as u32),
+                Vector2::new(cut.width() as u32, cut.height() as u32),
+                0,
+            ),
+            Op::ColorSpace(color_type) => image_data.color_space(color_type)?,
+            Op::FlipHorizontal => image_data.flip_horizontal(),
+            Op::FlipVertical => image_data.flip_vertical(),
+            Op::Rotate(rotation) => image_data.rotate(rotation),
+            Op::DeleteFrames(start, end) => image_data.delete_frames(start, end)?,
+            Op::DuplicateFrame(index) => image_data.duplicate_frame(index)?,
+            Op::ReverseFrames => image_data.reverse_frames(),
+            Op::FrameDelay(index, delay) => image_data.frame_delay(index, delay),
+            _ => return None,
+        };
+        Some(frames)
+    }
+}
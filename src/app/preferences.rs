@@ -2,9 +2,13 @@ use std::sync::Mutex;
 
 use cgmath::{EuclideanSpace, Point2};
 use serde::{Deserialize, Serialize};
+use simp::io::{
+    AvifBitDepth, GifOptions, GifRepeat, JpegOptions, JpegSubsampling, PngCompression, PngFilter,
+    PngOptions, TiffCompression, TiffOptions, WebpOptions, thumbnail::ThumbnailSize,
+};
 
-use super::App;
-use crate::util::p2;
+use super::{App, cache::total_memory, image_view::color_management::read_profile};
+use crate::{WgpuState, util::p2};
 
 pub static PREFERENCES: Mutex<Preferences> = Mutex::new(Preferences::new());
 
@@ -60,19 +64,87 @@ impl SortDirection {
     }
 }
 
+/// How camera raw files with an embedded JPEG preview are opened.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum RawPreview {
+    PreviewOnly,
+    #[default]
+    PreviewThenFull,
+    FullOnly,
+}
+
+impl AsRef<str> for RawPreview {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::PreviewOnly => "Preview only",
+            Self::PreviewThenFull => "Preview then full",
+            Self::FullOnly => "Full only",
+        }
+    }
+}
+
+/// How the memory budget of the image cache is set.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum CacheLimit {
+    #[default]
+    PercentOfMemory,
+    Megabytes,
+}
+
+impl AsRef<str> for CacheLimit {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::PercentOfMemory => "Percent of memory",
+            Self::Megabytes => "Fixed size",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Preferences {
     pub open_in_fullscreen: bool,
     pub auto_center: bool,
     pub min_svg_size: u32,
     pub zoom_speed: f32,
+    pub color_managed: bool,
+    /// Path to the icc profile of the monitor, empty to display in sRGB.
+    pub monitor_profile: String,
     pub jpeg_quality: u8,
+    pub jpeg_subsampling: JpegSubsampling,
+    pub jpeg_progressive: bool,
     pub webp_lossy: bool,
     pub webp_quality: f32,
+    pub webp_method: u8,
     pub jxl_lossy: bool,
     pub jxl_quality: f32,
+    pub jxl_effort: u8,
+    pub avif_quality: u8,
+    pub avif_speed: u8,
+    pub avif_bit_depth: AvifBitDepth,
+    pub heif_lossy: bool,
+    pub heif_quality: u8,
+    pub png_compression: PngCompression,
+    pub png_filter: PngFilter,
+    pub tiff_compression: TiffCompression,
+    pub gif_speed: u8,
+    pub gif_dithering: bool,
+    pub gif_global_palette: bool,
+    pub gif_transparency_threshold: u8,
+    pub gif_repeat: GifRepeat,
+    pub gif_drop_duplicates: bool,
     pub sort_order: SortOrder,
     pub sort_direction: SortDirection,
+    pub raw_preview: RawPreview,
+    /// Write a thumbnail of every opened image to the shared thumbnail cache.
+    pub save_thumbnails: bool,
+    pub thumbnail_size: ThumbnailSize,
+    pub filmstrip: bool,
+    /// How many images before and after the current one the filmstrip shows.
+    pub filmstrip_images: u8,
+    pub cache_limit: CacheLimit,
+    pub cache_percent: u8,
+    pub cache_megabytes: u32,
 }
 
 impl Preferences {
@@ -82,13 +154,41 @@ impl Preferences {
             auto_center: true,
             min_svg_size: 1000,
             zoom_speed: 1.0,
+            color_managed: true,
+            monitor_profile: String::new(),
             jpeg_quality: 80,
+            jpeg_subsampling: JpegSubsampling::Yuv444,
+            jpeg_progressive: false,
             webp_lossy: false,
             webp_quality: 80.0,
+            webp_method: 4,
             jxl_lossy: true,
             jxl_quality: 1.0,
+            jxl_effort: 8,
+            avif_quality: 80,
+            avif_speed: 6,
+            avif_bit_depth: AvifBitDepth::Ten,
+            heif_lossy: true,
+            heif_quality: 80,
+            png_compression: PngCompression::Fast,
+            png_filter: PngFilter::Adaptive,
+            tiff_compression: TiffCompression::Uncompressed,
+            gif_speed: 10,
+            gif_dithering: true,
+            gif_global_palette: false,
+            gif_transparency_threshold: 128,
+            gif_repeat: GifRepeat::Source,
+            gif_drop_duplicates: true,
             sort_order: SortOrder::MetadataTime,
             sort_direction: SortDirection::Forward,
+            raw_preview: RawPreview::PreviewThenFull,
+            save_thumbnails: true,
+            thumbnail_size: ThumbnailSize::Large,
+            filmstrip: false,
+            filmstrip_images: 5,
+            cache_limit: CacheLimit::PercentOfMemory,
+            cache_percent: 10,
+            cache_megabytes: 1000,
         }
     }
 
@@ -96,6 +196,92 @@ impl Preferences {
         self.jpeg_quality = self.jpeg_quality.clamp(1, 100);
         self.webp_quality = self.webp_quality.clamp(0.0, 100.0);
         self.jxl_quality = self.jxl_quality.clamp(0.0, 15.0);
+        self.webp_method = self.webp_method.min(6);
+        self.jxl_effort = self.jxl_effort.clamp(1, 10);
+        self.avif_quality = self.avif_quality.clamp(1, 100);
+        self.avif_speed = self.avif_speed.clamp(1, 10);
+        self.heif_quality = self.heif_quality.min(100);
+        self.gif_speed = self.gif_speed.clamp(1, 30);
+        self.filmstrip_images = self.filmstrip_images.clamp(1, 20);
+        self.cache_percent = self.cache_percent.clamp(1, 80);
+    }
+
+    /// The memory budget of the image cache in bytes, 1 GB if the memory of the machine
+    /// is unknown.
+    pub fn cache_budget(&self) -> usize {
+        const MEGABYTE: u64 = 1_000_000;
+        let bytes = match (self.cache_limit, total_memory()) {
+            (CacheLimit::PercentOfMemory, Some(total)) => total / 100 * self.cache_percent as u64,
+            (CacheLimit::PercentOfMemory, None) => 1000 * MEGABYTE,
+            (CacheLimit::Megabytes, _) => self.cache_megabytes as u64 * MEGABYTE,
+        };
+        usize::try_from(bytes).unwrap_or(usize::MAX)
+    }
+
+    pub fn jpeg_options(&self) -> JpegOptions {
+        JpegOptions {
+            quality: self.jpeg_quality,
+            subsampling: self.jpeg_subsampling,
+            progressive: self.jpeg_progressive,
+        }
+    }
+
+    pub fn png_options(&self) -> PngOptions {
+        PngOptions {
+            compression: self.png_compression,
+            filter: self.png_filter,
+        }
+    }
+
+    pub fn webp_options(&self) -> WebpOptions {
+        WebpOptions {
+            lossy: self.webp_lossy,
+            quality: self.webp_quality,
+            method: self.webp_method,
+        }
+    }
+
+    #[cfg(feature = "jxl")]
+    pub fn jxl_options(&self) -> simp::io::JxlOptions {
+        simp::io::JxlOptions {
+            lossy: self.jxl_lossy,
+            distance: self.jxl_quality,
+            effort: self.jxl_effort,
+        }
+    }
+
+    #[cfg(feature = "avif")]
+    pub fn avif_options(&self) -> simp::io::AvifOptions {
+        simp::io::AvifOptions {
+            quality: self.avif_quality,
+            speed: self.avif_speed,
+            bit_depth: self.avif_bit_depth,
+        }
+    }
+
+    #[cfg(feature = "heif")]
+    pub fn heif_options(&self) -> simp::io::HeifOptions {
+        simp::io::HeifOptions {
+            lossy: self.heif_lossy,
+            quality: self.heif_quality,
+        }
+    }
+
+    pub fn gif_options(&self) -> GifOptions {
+        GifOptions {
+            speed: self.gif_speed,
+            dithering: self.gif_dithering,
+            global_palette: self.gif_global_palette,
+            transparency_threshold: self.gif_transparency_threshold,
+            repeat: self.gif_repeat,
+            drop_duplicates: self.gif_drop_duplicates,
+        }
+    }
+
+    pub fn tiff_options(&self) -> TiffOptions {
+        TiffOptions {
+            compression: self.tiff_compression,
+        }
     }
 }
 
@@ -106,7 +292,7 @@ impl Default for Preferences {
 }
 
 impl App {
-    pub fn preferences_ui(&mut self, ctx: &egui::Context) {
+    pub fn preferences_ui(&mut self, wgpu: &WgpuState, ctx: &egui::Context) {
         if self.preferences_visible {
             let mut preferences = PREFERENCES.lock().unwrap().clone();
 
@@ -145,12 +331,264 @@ impl App {
                         ui.add(egui::Slider::new(&mut preferences.min_svg_size, 0..=10000));
                         ui.end_row();
 
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("RAW preview: ");
+                        });
+                        egui::ComboBox::new("raw preview combobox", "")
+                            .selected_text(preferences.raw_preview.as_ref())
+                            .show_ui(ui, |ui| {
+                                let modes = [
+                                    RawPreview::PreviewOnly,
+                                    RawPreview::PreviewThenFull,
+                                    RawPreview::FullOnly,
+                                ];
+                                for mode in modes {
+                                    ui.selectable_value(
+                                        &mut preferences.raw_preview,
+                                        mode,
+                                        mode.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Save thumbnails: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.save_thumbnails, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Thumbnail size: ");
+                        });
+                        egui::ComboBox::new("thumbnail size combobox", "")
+                            .selected_text(preferences.thumbnail_size.as_ref())
+                            .show_ui(ui, |ui| {
+                                for size in ThumbnailSize::ALL {
+                                    ui.selectable_value(
+                                        &mut preferences.thumbnail_size,
+                                        size,
+                                        size.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Show filmstrip: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.filmstrip, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Filmstrip images on each side: ");
+                        });
+                        ui.add(egui::Slider::new(&mut preferences.filmstrip_images, 1..=20));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Image cache limit: ");
+                        });
+                        egui::ComboBox::new("cache limit combobox", "")
+                            .selected_text(preferences.cache_limit.as_ref())
+                            .show_ui(ui, |ui| {
+                                for limit in [CacheLimit::PercentOfMemory, CacheLimit::Megabytes] {
+                                    ui.selectable_value(
+                                        &mut preferences.cache_limit,
+                                        limit,
+                                        limit.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Image cache size: ");
+                        });
+                        match preferences.cache_limit {
+                            CacheLimit::PercentOfMemory => ui.add(
+                                egui::Slider::new(&mut preferences.cache_percent, 1..=80)
+                                    .suffix("%"),
+                            ),
+                            CacheLimit::Megabytes => ui.add(
+                                egui::Slider::new(&mut preferences.cache_megabytes, 0..=65536)
+                                    .logarithmic(true)
+                                    .suffix(" MB"),
+                            ),
+                        };
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Color managed: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.color_managed, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("Monitor icc profile: ");
+                        });
+                        let mut monitor_profile = self
+                            .monitor_profile_edit
+                            .take()
+                            .unwrap_or_else(|| preferences.monitor_profile.clone());
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut monitor_profile).hint_text("sRGB"),
+                        );
+                        // Applied on enter or when clicking elsewhere, a path that is not a
+                        // usable profile leaves the old one in place.
+                        if response.has_focus() {
+                            self.monitor_profile_edit = Some(monitor_profile);
+                        } else if response.lost_focus()
+                            && (monitor_profile.is_empty()
+                                || read_profile(&monitor_profile).is_some())
+                        {
+                            preferences.monitor_profile = monitor_profile;
+                        }
+                        ui.end_row();
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
                             ui.label("JPEG quality: ");
                         });
                         ui.add(egui::Slider::new(&mut preferences.jpeg_quality, 1..=100));
                         ui.end_row();
 
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("JPEG chroma subsampling: ");
+                        });
+                        egui::ComboBox::new("jpeg subsampling combobox", "")
+                            .selected_text(preferences.jpeg_subsampling.as_ref())
+                            .show_ui(ui, |ui| {
+                                let subsamplings = [
+                                    JpegSubsampling::Yuv444,
+                                    JpegSubsampling::Yuv422,
+                                    JpegSubsampling::Yuv420,
+                                ];
+                                for subsampling in subsamplings {
+                                    ui.selectable_value(
+                                        &mut preferences.jpeg_subsampling,
+                                        subsampling,
+                                        subsampling.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("JPEG progressive: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.jpeg_progressive, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("PNG compression: ");
+                        });
+                        egui::ComboBox::new("png compression combobox", "")
+                            .selected_text(preferences.png_compression.as_ref())
+                            .show_ui(ui, |ui| {
+                                let compressions = [
+                                    PngCompression::Uncompressed,
+                                    PngCompression::Fast,
+                                    PngCompression::Default,
+                                    PngCompression::Best,
+                                ];
+                                for compression in compressions {
+                                    ui.selectable_value(
+                                        &mut preferences.png_compression,
+                                        compression,
+                                        compression.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("PNG filter: ");
+                        });
+                        egui::ComboBox::new("png filter combobox", "")
+                            .selected_text(preferences.png_filter.as_ref())
+                            .show_ui(ui, |ui| {
+                                let filters = [
+                                    PngFilter::NoFilter,
+                                    PngFilter::Sub,
+                                    PngFilter::Up,
+                                    PngFilter::Avg,
+                                    PngFilter::Paeth,
+                                    PngFilter::Adaptive,
+                                ];
+                                for filter in filters {
+                                    ui.selectable_value(
+                                        &mut preferences.png_filter,
+                                        filter,
+                                        filter.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("TIFF compression: ");
+                        });
+                        egui::ComboBox::new("tiff compression combobox", "")
+                            .selected_text(preferences.tiff_compression.as_ref())
+                            .show_ui(ui, |ui| {
+                                let compressions = [
+                                    TiffCompression::Uncompressed,
+                                    TiffCompression::Lzw,
+                                    TiffCompression::Deflate,
+                                    TiffCompression::Packbits,
+                                ];
+                                for compression in compressions {
+                                    ui.selectable_value(
+                                        &mut preferences.tiff_compression,
+                                        compression,
+                                        compression.as_ref(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF quantizer speed: ");
+                        });
+                        ui.add(egui::Slider::new(&mut preferences.gif_speed, 1..=30));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF dithering: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.gif_dithering, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF global palette: ");
+                        });
+                        ui.add(egui::Checkbox::new(&mut preferences.gif_global_palette, ""));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF transparency threshold: ");
+                        });
+                        ui.add(egui::Slider::new(
+                            &mut preferences.gif_transparency_threshold,
+                            0..=255,
+                        ));
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF repeat: ");
+                        });
+                        gif_repeat_ui(ui, &mut preferences.gif_repeat);
+                        ui.end_row();
+
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("GIF drop identical frames: ");
+                        });
+                        ui.add(egui::Checkbox::new(
+                            &mut preferences.gif_drop_duplicates,
+                            "",
+                        ));
+                        ui.end_row();
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
                             ui.label("WebP lossy compression: ");
                         });
@@ -166,6 +604,12 @@ impl App {
                         ));
                         ui.end_row();
 
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("WebP method: ");
+                        });
+                        ui.add(egui::Slider::new(&mut preferences.webp_method, 0..=6));
+                        ui.end_row();
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
                             ui.label("JPEG XL lossy compression: ");
                         });
@@ -173,11 +617,63 @@ impl App {
                         ui.end_row();
 
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
-                            ui.label("JPEG XL quality: ");
+                            ui.label("JPEG XL distance: ");
                         });
                         ui.add(egui::Slider::new(&mut preferences.jxl_quality, 0.0..=15.0));
                         ui.end_row();
 
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                            ui.label("JPEG XL effort: ");
+                        });
+                        ui.add(egui::Slider::new(&mut preferences.jxl_effort, 1..=10));
+                        ui.end_row();
+
+                        #[cfg(feature = "avif")]
+                        {
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                ui.label("AVIF quality: ");
+                            });
+                            ui.add(egui::Slider::new(&mut preferences.avif_quality, 1..=100));
+                            ui.end_row();
+
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                ui.label("AVIF speed: ");
+                            });
+                            ui.add(egui::Slider::new(&mut preferences.avif_speed, 1..=10));
+                            ui.end_row();
+
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                ui.label("AVIF bit depth: ");
+                            });
+                            egui::ComboBox::new("avif bit depth combobox", "")
+                                .selected_text(preferences.avif_bit_depth.as_ref())
+                                .show_ui(ui, |ui| {
+                                    for bit_depth in [AvifBitDepth::Eight, AvifBitDepth::Ten] {
+                                        ui.selectable_value(
+                                            &mut preferences.avif_bit_depth,
+                                            bit_depth,
+                                            bit_depth.as_ref(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                        }
+
+                        #[cfg(feature = "heif")]
+                        {
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                ui.label("HEIC lossy compression: ");
+                            });
+                            ui.add(egui::Checkbox::new(&mut preferences.heif_lossy, ""));
+                            ui.end_row();
+
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                                ui.label("HEIC quality: ");
+                            });
+                            ui.add(egui::Slider::new(&mut preferences.heif_quality, 0..=100));
+                            ui.end_row();
+                        }
+
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
                             ui.label("Sort order: ");
                         });
@@ -230,9 +726,41 @@ impl App {
                     });
                 });
 
-            *PREFERENCES.lock().unwrap() = preferences;
+            let mut guard = PREFERENCES.lock().unwrap();
+            let color_changed = guard.color_managed != preferences.color_managed
+                || guard.monitor_profile != preferences.monitor_profile;
+            *guard = preferences;
+            drop(guard);
+
+            if color_changed && let Some(ref mut view) = self.image_view {
+                view.reload_textures(wgpu);
+            }
 
             self.preferences_visible = open;
         }
     }
 }
+
+/// A combo box for the gif repeat mode with the count next to it when one is picked.
+pub fn gif_repeat_ui(ui: &mut egui::Ui, repeat: &mut GifRepeat) {
+    ui.horizontal(|ui| {
+        let count = match *repeat {
+            GifRepeat::Finite(count) => count,
+            _ => 1,
+        };
+        egui::ComboBox::new(ui.id().with("gif repeat combobox"), "")
+            .selected_text(repeat.as_ref())
+            .show_ui(ui, |ui| {
+                for option in [
+                    GifRepeat::Source,
+                    GifRepeat::Infinite,
+                    GifRepeat::Finite(count),
+                ] {
+                    ui.selectable_value(repeat, option, option.as_ref());
+                }
+            });
+        if let GifRepeat::Finite(count) = repeat {
+            ui.add(egui::DragValue::new(count).suffix(" times"));
+        }
+    });
+}
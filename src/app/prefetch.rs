@@ -0,0 +1,117 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use super::{
+    cache::Cache,
+    op_queue::{Developer, LoadingInfo, claim, load_claimed},
+};
+use crate::util::UserEvent;
+
+/// How many images are prefetched in the direction of travel.
+pub const AHEAD: usize = 3;
+/// How many images are prefetched behind the current one.
+pub const BEHIND: usize = 1;
+/// Longest prefetching waits for the image being opened, so a slow decode does not hold it
+/// up for good.
+const MAX_PAUSE: Duration = Duration::from_secs(2);
+
+struct Shared {
+    /// The most important image is at the front.
+    queue: Mutex<VecDeque<PathBuf>>,
+    condvar: Condvar,
+    cache: Arc<Cache>,
+    proxy: EventLoopProxy<UserEvent>,
+    loading_info: Arc<Mutex<LoadingInfo>>,
+    developer: Developer,
+}
+
+/// Decodes the images around the current one into the cache on a few background threads.
+#[derive(Clone)]
+pub struct Prefetcher {
+    shared: Arc<Shared>,
+}
+
+impl Prefetcher {
+    pub fn new(
+        cache: Arc<Cache>,
+        proxy: EventLoopProxy<UserEvent>,
+        loading_info: Arc<Mutex<LoadingInfo>>,
+        developer: Developer,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            condvar: Condvar::new(),
+            cache,
+            proxy,
+            loading_info,
+            developer,
+        });
+
+        // Keep a core free for the image that is being opened.
+        let workers = thread::available_parallelism()
+            .map(|n| n.get() - 1)
+            .unwrap_or(1)
+            .clamp(1, 4);
+        for _ in 0..workers {
+            let shared = shared.clone();
+            thread::spawn(move || work(&shared));
+        }
+
+        Self { shared }
+    }
+
+    /// Replaces the queued images, most important first. Images that are already being
+    /// decoded are finished and end up in the cache, queued develops of the others are
+    /// dropped.
+    pub fn schedule(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        let paths: Vec<_> = paths.into_iter().collect();
+        let mut queue = self.shared.queue.lock().unwrap();
+        self.shared
+            .loading_info
+            .lock()
+            .unwrap()
+            .set_prefetching(paths.iter().cloned());
+        queue.clear();
+        queue.extend(paths);
+        self.shared.condvar.notify_all();
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let path = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                // The image being opened gets the cores to itself, prefetching continues
+                // once it is shown.
+                if shared.loading_info.lock().unwrap().is_opening(MAX_PAUSE) {
+                    let timeout = Duration::from_millis(10);
+                    queue = shared.condvar.wait_timeout(queue, timeout).unwrap().0;
+                    continue;
+                }
+                match queue.pop_front() {
+                    Some(path) => break path,
+                    None => queue = shared.condvar.wait(queue).unwrap(),
+                }
+            }
+        };
+
+        if claim(&path, &shared.cache, &shared.loading_info) {
+            load_claimed(
+                &path,
+                &shared.cache,
+                &shared.proxy,
+                &shared.loading_info,
+                &shared.developer,
+                true,
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use egui::ScrollArea;
+use simp::io::{PsdLayers, RawMetadata, SaveOptions, save_frames};
+use winit::event_loop::EventLoopProxy;
+
+use super::{App, op_queue::Op, preferences::PREFERENCES};
+use crate::{
+    WgpuState,
+    util::{Image, UserEvent},
+};
+
+const INDENT: f32 = 16.0;
+
+impl App {
+    pub fn psd_layers_ui(&mut self, wgpu: &WgpuState, ui: &mut egui::Ui) {
+        if !self.psd_layers_visible {
+            return;
+        }
+        let Some(view) = self.image_view.as_ref() else {
+            return;
+        };
+        let Some(psd) = view.image_data.read().unwrap().psd.clone() else {
+            return;
+        };
+        let current = view.psd_visibility.clone();
+        let stem = Path::new(&self.current_filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        let mut visibility = current.clone();
+        let mut export = None;
+        let mut export_all = false;
+        egui::Panel::left("psd layers panel")
+            .resizable(true)
+            .default_size(220.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Layers");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("❌").clicked() {
+                            self.psd_layers_visible = false;
+                        }
+                    });
+                });
+                ui.separator();
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    // Groups hold a contiguous range of layers so a group row is shown
+                    // whenever the first layer inside it is reached.
+                    let mut open: Vec<u32> = Vec::new();
+                    for (index, layer) in psd.layers.iter().enumerate() {
+                        let mut path = psd.ancestors(layer.group);
+                        path.reverse();
+                        let common = open.iter().zip(&path).take_while(|(a, b)| a == b).count();
+                        for (depth, id) in path.iter().enumerate().skip(common) {
+                            let Some(group) = psd.groups.get(id) else {
+                                continue;
+                            };
+                            ui.horizontal(|ui| {
+                                ui.add_space(depth as f32 * INDENT);
+                                let mut shown = visibility.groups.get(id).copied().unwrap_or(true);
+                                if ui.checkbox(&mut shown, "").changed() {
+                                    visibility.groups.insert(*id, shown);
+                                }
+                                ui.strong(&group.name);
+                            });
+                        }
+                        open = path;
+
+                        ui.horizontal(|ui| {
+                            ui.add_space(open.len() as f32 * INDENT);
+                            if let Some(shown) = visibility.layers.get_mut(index) {
+                                ui.checkbox(shown, "");
+                            }
+                            ui.label(&layer.name);
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if ui
+                                        .small_button("💾")
+                                        .on_hover_text("Export layer as PNG")
+                                        .clicked()
+                                    {
+                                        export = Some(index);
+                                    }
+                                },
+                            );
+                        });
+                    }
+                });
+
+                ui.separator();
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Export all layers").clicked() {
+                            export_all = true;
+                        }
+                    },
+                );
+            });
+
+        if visibility != current && self.view_available() {
+            self.queue(Op::PsdVisibility(visibility));
+        }
+
+        if let Some(index) = export {
+            let psd = psd.clone();
+            let name = format!("{stem}.{}.png", file_name(&psd.layers[index].name));
+            let dialog = rfd::FileDialog::new()
+                .set_file_name(name)
+                .set_parent(&wgpu.window)
+                .add_filter("PNG", &["png"]);
+            let proxy = self.proxy.clone();
+            thread::spawn(move || {
+                if let Some(mut path) = dialog.save_file() {
+                    path.set_extension("png");
+                    export_layers(&psd, vec![(index, path)], &proxy);
+                }
+            });
+        }
+
+        if export_all {
+            let dialog = rfd::FileDialog::new().set_parent(&wgpu.window);
+            let proxy = self.proxy.clone();
+            thread::spawn(move || {
+                if let Some(folder) = dialog.pick_folder() {
+                    let width = psd.layers.len().to_string().len();
+                    let layers = psd
+                        .layers
+                        .iter()
+                        .enumerate()
+                        .map(|(index, layer)| {
+                            let name = format!(
+                                "{stem}-{:0width$}-{}.png",
+                                index + 1,
+                                file_name(&layer.name)
+                            );
+                            (index, folder.join(name))
+                        })
+                        .collect();
+                    export_layers(&psd, layers, &proxy);
+                }
+            });
+        }
+    }
+}
+
+fn export_layers(
+    psd: &Arc<PsdLayers>,
+    layers: Vec<(usize, PathBuf)>,
+    proxy: &EventLoopProxy<UserEvent>,
+) {
+    let options = PREFERENCES.lock().unwrap().png_options();
+    for (index, path) in layers {
+        let Some(image) = psd.layer_image(index) else {
+            continue;
+        };
+        let frames = vec![Image::new(image)];
+        let options = SaveOptions::Png(options);
+        if let Err(error) = save_frames(&path, frames, &RawMetadata::default(), options) {
+            let _ = proxy.send_event(UserEvent::ErrorMessage(error.to_string()));
+            return;
+        }
+    }
+}
+
+/// Layer names can contain characters that are not allowed in file names.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,139 @@
+use cgmath::{EuclideanSpace, Point2};
+use egui::{Button, Slider};
+use simp::io::{RawSettings, WhiteBalance};
+
+use super::{App, op_queue::Op};
+use crate::util::p2;
+
+impl App {
+    /// The image is developed again every time a setting is let go of.
+    pub fn raw_development_ui(&mut self, ctx: &egui::Context) {
+        if !self.raw_visible {
+            return;
+        }
+        let Some(raw) = self
+            .image_view
+            .as_ref()
+            .and_then(|view| view.image_data.read().unwrap().raw.clone())
+        else {
+            return;
+        };
+
+        let mut open = true;
+        let mut closed = false;
+        egui::Window::new("RAW development")
+            .id(egui::Id::new("raw development window"))
+            .collapsible(false)
+            .resizable(false)
+            .pivot(egui::Align2::CENTER_CENTER)
+            .default_pos(p2(Point2::from_vec(self.size / 2.0)))
+            .auto_sized()
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("raw development grid").show(ui, |ui| {
+                    let view = self.image_view.as_mut().unwrap();
+                    let settings = &mut view.raw_draft;
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("White balance: ");
+                    });
+                    egui::ComboBox::new("white balance combobox", "")
+                        .selected_text(settings.white_balance.as_ref())
+                        .show_ui(ui, |ui| {
+                            for white_balance in WhiteBalance::ALL {
+                                ui.selectable_value(
+                                    &mut settings.white_balance,
+                                    white_balance,
+                                    white_balance.as_ref(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    let custom = settings.white_balance == WhiteBalance::Custom;
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Temperature: ");
+                    });
+                    ui.add_enabled(
+                        custom,
+                        Slider::new(&mut settings.temperature, RawSettings::TEMPERATURE)
+                            .step_by(50.0)
+                            .suffix(" K"),
+                    );
+                    ui.end_row();
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Tint: ");
+                    });
+                    ui.add_enabled(
+                        custom,
+                        Slider::new(&mut settings.tint, RawSettings::TINT).step_by(0.01),
+                    );
+                    ui.end_row();
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Exposure: ");
+                    });
+                    ui.add(
+                        Slider::new(&mut settings.exposure, RawSettings::EXPOSURE)
+                            .step_by(0.1)
+                            .suffix(" EV"),
+                    );
+                    ui.end_row();
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Highlight recovery: ");
+                    });
+                    ui.add(Slider::new(&mut settings.highlight_recovery, 0.0..=1.0));
+                    ui.end_row();
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                        ui.label("Bit depth: ");
+                    });
+                    egui::ComboBox::new("bit depth combobox", "")
+                        .selected_text(format!("{} bit", settings.bit_depth))
+                        .show_ui(ui, |ui| {
+                            for bit_depth in [8, 16] {
+                                ui.selectable_value(
+                                    &mut settings.bit_depth,
+                                    bit_depth,
+                                    format!("{bit_depth} bit"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+                    ui.end_row();
+
+                    ui.with_layout(
+                        egui::Layout::top_down_justified(egui::Align::Center),
+                        |ui| {
+                            if ui.button("Reset").clicked() {
+                                *settings = RawSettings {
+                                    temperature: raw.as_shot.0,
+                                    tint: raw.as_shot.1,
+                                    ..Default::default()
+                                };
+                            }
+                        },
+                    );
+
+                    ui.with_layout(
+                        egui::Layout::top_down_justified(egui::Align::Center),
+                        |ui| {
+                            if ui.add(Button::new("Close")).clicked() || self.enter {
+                                closed = true;
+                                self.enter = false;
+                            }
+                        },
+                    );
+                });
+            });
+        self.raw_visible = open && !closed;
+
+        // Developing takes a while so it waits until a slider is let go of.
+        let view = self.image_view.as_ref().unwrap();
+        if view.raw_draft != view.raw_settings
+            && self.view_available()
+            && !ctx.input(|input| input.pointer.any_down())
+        {
+            self.queue(Op::RawDevelop(view.raw_draft));
+        }
+    }
+}
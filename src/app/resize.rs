@@ -1,6 +1,9 @@
 use cgmath::Vector2;
 use image::imageops::FilterType;
 
+/// The filter resizing starts out with, in the resize window and in batch operations.
+pub const DEFAULT_RESAMPLE: FilterType = FilterType::Nearest;
+
 #[derive(Clone)]
 pub struct Resize {
     pub visible: bool,
@@ -14,7 +17,7 @@ impl Default for Resize {
     fn default() -> Self {
         Self {
             visible: false,
-            resample: FilterType::Nearest,
+            resample: DEFAULT_RESAMPLE,
             width: String::from("0"),
             height: String::from("0"),
             maintain_aspect_ratio: true,
@@ -1,27 +1,85 @@
 use std::{
-    path::PathBuf,
-    sync::{Arc, RwLock},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     thread,
 };
 
 use image::{
-    ImageFormat,
+    GenericImageView,
     imageops::{flip_horizontal_in_place, flip_vertical_in_place},
 };
+use simp::io::{
+    GifOptions, JpegOptions, JpegSubsampling, SaveOptions, WebpOptions, rotate_frames, save_frames,
+    save_frames_as_pages,
+};
 use winit::event_loop::EventLoopProxy;
 
 use super::{
     dialog_manager::DialogProxy,
+    image_view::ImageView,
     op_queue::{Output, UserEventLoopProxyExt},
-    preferences::PREFERENCES,
+    preferences::{PREFERENCES, gif_repeat_ui},
 };
 use crate::{
     WgpuState,
-    image_io::save::{gif, jpeg, save_with_format, webp, webp_animation},
-    util::{Image, ImageData, UserEvent},
+    util::{Image, UserEvent},
 };
 
 pub fn open(name: String, proxy: EventLoopProxy<UserEvent>, wgpu: &WgpuState) {
+    let dialog = dialog(name, wgpu);
+    thread::spawn(move || {
+        if let Some(path) = dialog.save_file() {
+            let _ = proxy.send_event(UserEvent::QueueSave(path));
+        }
+    });
+}
+
+/// Same as [`open`] but saves every page of a multi-page image.
+pub fn open_pages(name: String, proxy: EventLoopProxy<UserEvent>, wgpu: &WgpuState) {
+    let dialog = dialog(name, wgpu);
+    thread::spawn(move || {
+        if let Some(path) = dialog.save_file() {
+            let _ = proxy.send_event(UserEvent::QueueSavePages(path));
+        }
+    });
+}
+
+/// Same as [`open`] but asks for the size to render an svg at.
+pub fn open_svg_export(name: String, proxy: EventLoopProxy<UserEvent>, wgpu: &WgpuState) {
+    // There is no svg encoder so a png is suggested.
+    let name = Path::new(&name).with_extension("png");
+    let dialog = dialog(name.to_string_lossy().into_owned(), wgpu);
+    thread::spawn(move || {
+        if let Some(path) = dialog.save_file() {
+            let _ = proxy.send_event(UserEvent::QueueExportSvg(path));
+        }
+    });
+}
+
+/// Same as [`open`] but saves a single frame of an animation as a new image.
+pub fn open_frame(name: String, index: usize, proxy: EventLoopProxy<UserEvent>, wgpu: &WgpuState) {
+    let dialog = dialog(name, wgpu);
+    thread::spawn(move || {
+        if let Some(path) = dialog.save_file() {
+            let _ = proxy.send_event(UserEvent::QueueExtractFrame(path, index));
+        }
+    });
+}
+
+/// What [`save`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveKind {
+    /// The frames as they are shown.
+    Frames,
+    /// Every page of a multi-page image.
+    Pages,
+    /// An unedited svg rendered again at a size picked in a dialog.
+    SvgExport,
+    /// One frame of an animation, the animation itself stays unsaved.
+    Frame(usize),
+}
+
+fn dialog(name: String, wgpu: &WgpuState) -> rfd::FileDialog {
     let dialog = rfd::FileDialog::new()
         .set_file_name(name)
         .set_parent(&wgpu.window)
@@ -38,23 +96,26 @@ pub fn open(name: String, proxy: EventLoopProxy<UserEvent>, wgpu: &WgpuState) {
         .add_filter("QOI", &["qoi"])
         .add_filter("HDR", &["hdr"])
         .add_filter("EXR", &["exr"]);
-
-    thread::spawn(move || {
-        if let Some(path) = dialog.save_file() {
-            let _ = proxy.send_event(UserEvent::QueueSave(path));
-        }
-    });
+    #[cfg(feature = "avif")]
+    let dialog = dialog.add_filter("AVIF", &["avif"]);
+    #[cfg(feature = "heif")]
+    let dialog = dialog.add_filter("HEIC", &["heic", "heif"]);
+    dialog
 }
 
 pub fn save(
     proxy: EventLoopProxy<UserEvent>,
     dialog_proxy: DialogProxy,
     mut path: PathBuf,
-    image_data: Arc<RwLock<Arc<ImageData>>>,
-    rotation: i32,
-    horizontal_flip: bool,
-    vertical_flip: bool,
+    view: &ImageView,
+    kind: SaveKind,
 ) {
+    let image_data = view.image_data.clone();
+    let rotation = view.rotation();
+    let horizontal_flip = view.horizontal_flip;
+    let vertical_flip = view.vertical_flip;
+    let current_page = view.page;
+
     let os_str = path.extension();
     let ext = match os_str {
         Some(ext) => ext.to_string_lossy().to_string().to_lowercase(),
@@ -64,18 +125,43 @@ pub fn save(
 
     thread::spawn(move || {
         let guard = image_data.read().unwrap();
-        let old_frames = &guard.frames;
-        let mut frames = Vec::new();
-        for frame in old_frames {
-            let buffer = match rotation {
-                0 => frame.buffer().clone(),
-                1 => frame.buffer().rotate270(),
-                2 => frame.buffer().rotate180(),
-                3 => frame.buffer().rotate90(),
-                _ => unreachable!("image is rotated more then 360 degrees"),
-            };
-            frames.push(Image::with_delay(buffer, frame.delay));
-        }
+        // The displayed page may have been edited so it is taken from the frames.
+        let old_frames: Vec<&Image> = match (kind, &guard.pages) {
+            (SaveKind::Frame(index), _) => guard.frames.get(index).into_iter().collect(),
+            (SaveKind::Pages, Some(pages)) => pages
+                .iter()
+                .enumerate()
+                .map(|(index, page)| {
+                    if index == current_page {
+                        &guard.frames[0]
+                    } else {
+                        page
+                    }
+                })
+                .collect(),
+            _ => guard.frames.iter().collect(),
+        };
+        let metadata = guard.raw_metadata.clone();
+
+        // Exporting rasterizes the svg again instead of scaling the displayed raster.
+        let rasterized;
+        let old_frames = match guard.svg.clone() {
+            Some(svg) if kind == SaveKind::SvgExport => {
+                let Some(image) =
+                    get_svg_size(dialog_proxy.clone(), guard.frames[0].buffer().dimensions())
+                        .and_then(|(width, height)| svg.render(width, height))
+                else {
+                    proxy.send_output(Output::Done);
+                    return;
+                };
+                rasterized = Image::new(image);
+                vec![&rasterized]
+            }
+            _ => old_frames,
+        };
+
+        // Rotated like the batch runner does so both save the same pixels.
+        let mut frames = rotate_frames(old_frames, rotation);
 
         for frame in frames.iter_mut() {
             if horizontal_flip {
@@ -87,68 +173,146 @@ pub fn save(
             }
         }
 
-        let res = match ext.as_str() {
-            "hdr" => save_with_format(path, &frames[0], ImageFormat::Hdr),
-            "png" => save_with_format(path, &frames[0], ImageFormat::Png),
-            "qoi" => save_with_format(path, &frames[0], ImageFormat::Qoi),
+        let options = match ext.as_str() {
             "jpg" | "jpeg" | "jpe" | "jif" | "jfif" => {
-                let quality = match get_jpeg_quality(dialog_proxy.clone()) {
-                    Some(quality) => quality,
-                    None => {
-                        proxy.send_output(Output::Done);
-                        return;
-                    }
-                };
-
-                jpeg(path, &frames[0], quality)
-            }
-            "ico" => save_with_format(path, &frames[0], ImageFormat::Ico),
-            "tga" => save_with_format(path, &frames[0], ImageFormat::Tga),
-            "ff" | "farbfeld" => save_with_format(path, &frames[0], ImageFormat::Farbfeld),
-            "tiff" | "tif" => save_with_format(path, &frames[0], ImageFormat::Tiff),
-            "gif" => gif(path, frames),
-            "webp" => {
-                let (quality, lossy) = match get_webp_quality(dialog_proxy.clone()) {
-                    Some(quality) => quality,
-                    None => {
-                        proxy.send_output(Output::Done);
-                        return;
-                    }
-                };
-
-                if frames.len() > 1 {
-                    webp_animation(path, frames, quality, lossy)
-                } else {
-                    webp(path, &frames[0], quality, lossy)
-                }
+                get_jpeg_options(dialog_proxy.clone()).map(SaveOptions::Jpeg)
             }
-            "exr" => save_with_format(path, &frames[0], ImageFormat::OpenExr),
+            "webp" => get_webp_options(dialog_proxy.clone()).map(SaveOptions::WebP),
             #[cfg(feature = "jxl")]
-            "jxl" => {
-                let (quality, lossy) = match get_jxl_quality(dialog_proxy.clone()) {
-                    Some(quality) => quality,
-                    None => {
-                        proxy.send_output(Output::Done);
-                        return;
-                    }
-                };
+            "jxl" => get_jxl_options(dialog_proxy.clone()).map(SaveOptions::Jxl),
+            #[cfg(feature = "avif")]
+            "avif" => get_avif_options(dialog_proxy.clone()).map(SaveOptions::Avif),
+            #[cfg(feature = "heif")]
+            "heic" | "heif" => get_heif_options(dialog_proxy.clone()).map(SaveOptions::Heif),
+            "gif" => get_gif_options(dialog_proxy.clone()).map(SaveOptions::Gif),
+            "tiff" | "tif" => Some(SaveOptions::Tiff(
+                PREFERENCES.lock().unwrap().tiff_options(),
+            )),
+            _ => Some(SaveOptions::Png(PREFERENCES.lock().unwrap().png_options())),
+        };
 
-                crate::image_io::save::jpeg_xl(path, &frames[0], quality, lossy)
-            }
-            _ => {
-                path.set_extension("png");
-                save_with_format(path, &frames[0], ImageFormat::Png)
-            }
+        let Some(options) = options else {
+            proxy.send_output(Output::Done);
+            return;
+        };
+
+        let res = if kind == SaveKind::Pages {
+            save_frames_as_pages(path, frames, &metadata, options)
+        } else {
+            save_frames(path, frames, &metadata, options).map(|_| ())
         };
 
-        proxy.send_output(Output::Saved);
+        if matches!(kind, SaveKind::Frame(_)) {
+            proxy.send_output(Output::Done);
+        } else {
+            proxy.send_output(Output::Saved);
+        }
         if let Err(error) = res {
             let _ = proxy.send_event(UserEvent::ErrorMessage(error.to_string()));
         }
     });
 }
 
-pub fn get_jpeg_quality(dialog_proxy: DialogProxy) -> Option<u8> {
+/// Larger svgs would need more than a gigabyte of memory.
+const MAX_SVG_SIZE: u32 = 16384;
+
+struct SvgSize {
+    width: String,
+    height: String,
+    maintain_aspect_ratio: bool,
+}
+
+pub fn get_svg_size(dialog_proxy: DialogProxy, (width, height): (u32, u32)) -> Option<(u32, u32)> {
+    let state = Arc::new(Mutex::new(SvgSize {
+        width: width.to_string(),
+        height: height.to_string(),
+        maintain_aspect_ratio: true,
+    }));
+    let ratio = width as f32 / height as f32;
+    dialog_proxy
+        .spawn_dialog("Export settings", move |ui, enter| {
+            let mut size = state.lock().unwrap();
+            let mut output = None;
+            egui::Grid::new("svg export settings grid").show(ui, |ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Width: ");
+                });
+                let w_focus = ui.text_edit_singleline(&mut size.width).has_focus();
+                ui.end_row();
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Height: ");
+                });
+                let h_focus = ui.text_edit_singleline(&mut size.height).has_focus();
+                ui.end_row();
+
+                size.width.retain(|c| c.is_ascii_digit());
+                size.height.retain(|c| c.is_ascii_digit());
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Maintain aspect ratio:");
+                });
+                ui.checkbox(&mut size.maintain_aspect_ratio, "");
+                ui.end_row();
+                ui.end_row();
+
+                let width = size.width.parse::<u32>();
+                let height = size.height.parse::<u32>();
+                if size.maintain_aspect_ratio
+                    && w_focus
+                    && let Ok(width) = width
+                {
+                    size.height = ((width as f32 / ratio).round() as u32).to_string();
+                }
+                if size.maintain_aspect_ratio
+                    && h_focus
+                    && let Ok(height) = height
+                {
+                    size.width = ((height as f32 * ratio).round() as u32).to_string();
+                }
+
+                let valid = match (size.width.parse::<u32>(), size.height.parse::<u32>()) {
+                    (Ok(width), Ok(height))
+                        if (1..=MAX_SVG_SIZE).contains(&width)
+                            && (1..=MAX_SVG_SIZE).contains(&height) =>
+                    {
+                        Some((width, height))
+                    }
+                    _ => None,
+                };
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui
+                            .add_enabled(valid.is_some(), egui::Button::new("Save"))
+                            .clicked()
+                        {
+                            output = Some(valid);
+                        }
+                    },
+                );
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Cancel").clicked() {
+                            output = Some(None);
+                        }
+                    },
+                );
+
+                if *enter && valid.is_some() {
+                    *enter = false;
+                    output = Some(valid);
+                }
+            });
+            output
+        })
+        .wait()
+        .flatten()
+}
+
+pub fn get_jpeg_options(dialog_proxy: DialogProxy) -> Option<JpegOptions> {
     dialog_proxy
         .spawn_dialog("Export settings", |ui, enter| {
             let mut preferences = PREFERENCES.lock().unwrap();
@@ -160,11 +324,38 @@ pub fn get_jpeg_quality(dialog_proxy: DialogProxy) -> Option<u8> {
                 ui.add(egui::Slider::new(&mut preferences.jpeg_quality, 1..=100));
                 ui.end_row();
 
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Chroma subsampling: ");
+                });
+                egui::ComboBox::new("jpeg subsampling combobox", "")
+                    .selected_text(preferences.jpeg_subsampling.as_ref())
+                    .show_ui(ui, |ui| {
+                        let subsamplings = [
+                            JpegSubsampling::Yuv444,
+                            JpegSubsampling::Yuv422,
+                            JpegSubsampling::Yuv420,
+                        ];
+                        for subsampling in subsamplings {
+                            ui.selectable_value(
+                                &mut preferences.jpeg_subsampling,
+                                subsampling,
+                                subsampling.as_ref(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Progressive: ");
+                });
+                ui.add(egui::Checkbox::new(&mut preferences.jpeg_progressive, ""));
+                ui.end_row();
+
                 ui.with_layout(
                     egui::Layout::top_down_justified(egui::Align::Center),
                     |ui| {
                         if ui.button("Save").clicked() {
-                            output = Some(Some(preferences.jpeg_quality))
+                            output = Some(Some(preferences.jpeg_options()))
                         }
                     },
                 );
@@ -180,7 +371,7 @@ pub fn get_jpeg_quality(dialog_proxy: DialogProxy) -> Option<u8> {
 
                 if *enter {
                     *enter = false;
-                    output = Some(Some(preferences.jpeg_quality))
+                    output = Some(Some(preferences.jpeg_options()))
                 }
             });
             output
@@ -189,7 +380,7 @@ pub fn get_jpeg_quality(dialog_proxy: DialogProxy) -> Option<u8> {
         .flatten()
 }
 
-pub fn get_webp_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
+pub fn get_webp_options(dialog_proxy: DialogProxy) -> Option<WebpOptions> {
     dialog_proxy
         .spawn_dialog("Export settings", |ui, enter| {
             let mut preferences = PREFERENCES.lock().unwrap();
@@ -210,11 +401,94 @@ pub fn get_webp_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
                 ));
                 ui.end_row();
 
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Webp Method: ");
+                });
+                ui.add(egui::Slider::new(&mut preferences.webp_method, 0..=6));
+                ui.end_row();
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Save").clicked() {
+                            output = Some(Some(preferences.webp_options()))
+                        }
+                    },
+                );
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Cancel").clicked() {
+                            output = Some(None);
+                        }
+                    },
+                );
+
+                if *enter {
+                    *enter = false;
+                    output = Some(Some(preferences.webp_options()))
+                }
+            });
+            output
+        })
+        .wait()
+        .flatten()
+}
+
+pub fn get_gif_options(dialog_proxy: DialogProxy) -> Option<GifOptions> {
+    dialog_proxy
+        .spawn_dialog("Export settings", |ui, enter| {
+            let mut preferences = PREFERENCES.lock().unwrap();
+            let mut output = None;
+            egui::Grid::new("gif export settings grid").show(ui, |ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Quantizer speed: ");
+                });
+                ui.add(egui::Slider::new(&mut preferences.gif_speed, 1..=30));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Dithering: ");
+                });
+                ui.add(egui::Checkbox::new(&mut preferences.gif_dithering, ""));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Global palette: ");
+                });
+                ui.add(egui::Checkbox::new(&mut preferences.gif_global_palette, ""));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Transparency threshold: ");
+                });
+                ui.add(egui::Slider::new(
+                    &mut preferences.gif_transparency_threshold,
+                    0..=255,
+                ));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Repeat: ");
+                });
+                gif_repeat_ui(ui, &mut preferences.gif_repeat);
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Drop identical frames: ");
+                });
+                ui.add(egui::Checkbox::new(
+                    &mut preferences.gif_drop_duplicates,
+                    "",
+                ));
+                ui.end_row();
+
                 ui.with_layout(
                     egui::Layout::top_down_justified(egui::Align::Center),
                     |ui| {
                         if ui.button("Save").clicked() {
-                            output = Some(Some((preferences.webp_quality, preferences.webp_lossy)))
+                            output = Some(Some(preferences.gif_options()))
                         }
                     },
                 );
@@ -230,7 +504,7 @@ pub fn get_webp_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
 
                 if *enter {
                     *enter = false;
-                    output = Some(Some((preferences.webp_quality, preferences.webp_lossy)))
+                    output = Some(Some(preferences.gif_options()))
                 }
             });
             output
@@ -240,7 +514,7 @@ pub fn get_webp_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
 }
 
 #[cfg(feature = "jxl")]
-pub fn get_jxl_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
+pub fn get_jxl_options(dialog_proxy: DialogProxy) -> Option<simp::io::JxlOptions> {
     dialog_proxy
         .spawn_dialog("Export settings", |ui, enter| {
             let mut preferences = PREFERENCES.lock().unwrap();
@@ -253,16 +527,139 @@ pub fn get_jxl_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
                 ui.end_row();
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
-                    ui.label("JPEG XL Quality: ");
+                    ui.label("JPEG XL Distance: ");
                 });
                 ui.add(egui::Slider::new(&mut preferences.jxl_quality, 0.0..=15.0));
                 ui.end_row();
 
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("JPEG XL Effort: ");
+                });
+                ui.add(egui::Slider::new(&mut preferences.jxl_effort, 1..=10));
+                ui.end_row();
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Save").clicked() {
+                            output = Some(Some(preferences.jxl_options()))
+                        }
+                    },
+                );
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Cancel").clicked() {
+                            output = Some(None);
+                        }
+                    },
+                );
+
+                if *enter {
+                    *enter = false;
+                    output = Some(Some(preferences.jxl_options()))
+                }
+            });
+            output
+        })
+        .wait()
+        .flatten()
+}
+
+#[cfg(feature = "avif")]
+pub fn get_avif_options(dialog_proxy: DialogProxy) -> Option<simp::io::AvifOptions> {
+    use simp::io::AvifBitDepth;
+
+    dialog_proxy
+        .spawn_dialog("Export settings", |ui, enter| {
+            let mut preferences = PREFERENCES.lock().unwrap();
+            let mut output = None;
+            egui::Grid::new("avif export settings grid").show(ui, |ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("AVIF Quality: ");
+                });
+                ui.add(egui::Slider::new(&mut preferences.avif_quality, 1..=100));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("AVIF Speed: ");
+                });
+                ui.add(egui::Slider::new(&mut preferences.avif_speed, 1..=10));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("Bit depth: ");
+                });
+                egui::ComboBox::new("avif export bit depth combobox", "")
+                    .selected_text(preferences.avif_bit_depth.as_ref())
+                    .show_ui(ui, |ui| {
+                        for bit_depth in [AvifBitDepth::Eight, AvifBitDepth::Ten] {
+                            ui.selectable_value(
+                                &mut preferences.avif_bit_depth,
+                                bit_depth,
+                                bit_depth.as_ref(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Save").clicked() {
+                            output = Some(Some(preferences.avif_options()))
+                        }
+                    },
+                );
+
+                ui.with_layout(
+                    egui::Layout::top_down_justified(egui::Align::Center),
+                    |ui| {
+                        if ui.button("Cancel").clicked() {
+                            output = Some(None);
+                        }
+                    },
+                );
+
+                if *enter {
+                    *enter = false;
+                    output = Some(Some(preferences.avif_options()))
+                }
+            });
+            output
+        })
+        .wait()
+        .flatten()
+}
+
+#[cfg(feature = "heif")]
+pub fn get_heif_options(dialog_proxy: DialogProxy) -> Option<simp::io::HeifOptions> {
+    dialog_proxy
+        .spawn_dialog("Export settings", |ui, enter| {
+            let mut preferences = PREFERENCES.lock().unwrap();
+            let mut output = None;
+            egui::Grid::new("heif export settings grid").show(ui, |ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("HEIC lossy compression: ");
+                });
+                ui.add(egui::Checkbox::new(&mut preferences.heif_lossy, ""));
+                ui.end_row();
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
+                    ui.label("HEIC Quality: ");
+                });
+                ui.add_enabled(
+                    preferences.heif_lossy,
+                    egui::Slider::new(&mut preferences.heif_quality, 0..=100),
+                );
+                ui.end_row();
+
                 ui.with_layout(
                     egui::Layout::top_down_justified(egui::Align::Center),
                     |ui| {
                         if ui.button("Save").clicked() {
-                            output = Some(Some((preferences.jxl_quality, preferences.jxl_lossy)))
+                            output = Some(Some(preferences.heif_options()))
                         }
                     },
                 );
@@ -278,7 +675,7 @@ pub fn get_jxl_quality(dialog_proxy: DialogProxy) -> Option<(f32, bool)> {
 
                 if *enter {
                     *enter = false;
-                    output = Some(Some((preferences.jxl_quality, preferences.jxl_lossy)))
+                    output = Some(Some(preferences.heif_options()))
                 }
             });
             output
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use egui::{
+    Align2, Color32, ColorImage, FontId, Painter, Rect, TextureHandle, TextureOptions, pos2,
+};
+use image::RgbaImage;
+use simp::io::thumbnail::{self, ThumbnailSize};
+use winit::event_loop::EventLoopProxy;
+
+use super::{
+    cache::Cache,
+    load_image::load_uncached,
+    op_queue::{LoadingInfo, wait_for_load},
+};
+use crate::util::UserEvent;
+
+type Job = (PathBuf, ThumbnailSize);
+
+#[derive(Default)]
+struct Queue {
+    /// The newest job is at the front, those are the ones on screen right now.
+    jobs: VecDeque<(Job, bool)>,
+    /// Jobs that are queued or being worked on.
+    pending: HashSet<Job>,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    condvar: Condvar,
+    cache: Arc<Cache>,
+    proxy: EventLoopProxy<UserEvent>,
+    loading_info: Arc<Mutex<LoadingInfo>>,
+}
+
+/// Generates thumbnails in the shared on disk cache on a few background threads and sends
+/// each one back as a [`UserEvent::Thumbnail`].
+pub struct Thumbnailer {
+    shared: Arc<Shared>,
+}
+
+impl Thumbnailer {
+    pub fn new(
+        cache: Arc<Cache>,
+        proxy: EventLoopProxy<UserEvent>,
+        loading_info: Arc<Mutex<LoadingInfo>>,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            condvar: Condvar::new(),
+            cache,
+            proxy,
+            loading_info,
+        });
+
+        // Leave most of the cores for decoding the images that are being looked at.
+        let workers = thread::available_parallelism()
+            .map(|n| n.get() / 2)
+            .unwrap_or(1)
+            .clamp(1, 4);
+        for _ in 0..workers {
+            let shared = shared.clone();
+            thread::spawn(move || work(&shared));
+        }
+
+        Self { shared }
+    }
+
+    /// Queues a thumbnail unless it is already queued. It is only written to the disk
+    /// cache if `save` is set.
+    pub fn request(&self, path: PathBuf, size: ThumbnailSize, save: bool) {
+        let job = (path, size);
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.pending.insert(job.clone()) {
+            queue.jobs.push_front((job, save));
+            self.shared.condvar.notify_one();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let ((path, size), save) = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                match queue.jobs.pop_front() {
+                    Some(job) => break job,
+                    None => queue = shared.condvar.wait(queue).unwrap(),
+                }
+            }
+        };
+
+        let thumbnail = generate(&path, size, save, shared);
+        shared
+            .queue
+            .lock()
+            .unwrap()
+            .pending
+            .remove(&(path.clone(), size));
+        let _ = shared
+            .proxy
+            .send_event(UserEvent::Thumbnail(path, size, thumbnail));
+    }
+}
+
+/// Returns `None` if the image could not be loaded.
+fn generate(path: &Path, size: ThumbnailSize, save: bool, shared: &Shared) -> Option<RgbaImage> {
+    // Thumbnails are shown but never get thumbnails of their own.
+    let is_thumbnail = thumbnail::is_thumbnail(path);
+    let save = save && !is_thumbnail;
+    if !is_thumbnail {
+        if let Some(thumbnail) = thumbnail::read(path, size) {
+            return Some(thumbnail);
+        }
+        if thumbnail::has_failed(path) {
+            return None;
+        }
+    }
+
+    // Images that were just opened or prefetched are already decoded. Others are decoded
+    // here without going through the cache, so they don't evict the prefetched images.
+    wait_for_load(path, &shared.loading_info);
+    let image_data = match shared.cache.get(&path.to_path_buf()) {
+        Some(image_data) => image_data,
+        None => match load_uncached(path) {
+            Ok(image_data) => Arc::new(image_data),
+            Err(_) => {
+                if save {
+                    let _ = thumbnail::write_failure(path);
+                }
+                return None;
+            }
+        },
+    };
+    let image = image_data.frames[0].buffer();
+    if save && let Ok(thumbnail) = thumbnail::write(path, size, image) {
+        return Some(thumbnail);
+    }
+    Some(thumbnail::scale(image, size))
+}
+
+/// Thumbnail textures of one size, requested from the [`Thumbnailer`] when they are missing.
+#[derive(Default)]
+pub struct Thumbnails {
+    pub size: ThumbnailSize,
+    /// `None` for images that could not be loaded.
+    textures: HashMap<PathBuf, Option<TextureHandle>>,
+    /// Thumbnails that are done but not uploaded yet.
+    received: Vec<(PathBuf, Option<RgbaImage>)>,
+    requested: HashSet<PathBuf>,
+}
+
+impl Thumbnails {
+    pub fn new(size: ThumbnailSize) -> Self {
+        Self {
+            size,
+            ..Self::default()
+        }
+    }
+
+    pub fn receive(&mut self, path: &Path, size: ThumbnailSize, thumbnail: &Option<RgbaImage>) {
+        if size == self.size && self.requested.contains(path) {
+            self.received.push((path.to_path_buf(), thumbnail.clone()));
+        }
+    }
+
+    /// Changes the size, the textures of the old size are dropped.
+    pub fn set_size(&mut self, size: ThumbnailSize) {
+        if size != self.size {
+            self.clear();
+            self.size = size;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.received.clear();
+        self.requested.clear();
+    }
+
+    /// Only keeps the textures of these paths.
+    pub fn retain(&mut self, paths: &[PathBuf]) {
+        self.textures.retain(|path, _| paths.contains(path));
+        self.requested.retain(|path| paths.contains(path));
+    }
+
+    pub fn upload(&mut self, ctx: &egui::Context) {
+        for (path, thumbnail) in self.received.drain(..) {
+            let texture = thumbnail.map(|thumbnail| {
+                let size = [thumbnail.width() as usize, thumbnail.height() as usize];
+                let image = ColorImage::from_rgba_unmultiplied(size, thumbnail.as_raw());
+                ctx.load_texture(path.to_string_lossy(), image, TextureOptions::LINEAR)
+            });
+            self.textures.insert(path, texture);
+        }
+    }
+
+    /// The texture if it is done, `Some(None)` if the image could not be loaded.
+    pub fn get(
+        &mut self,
+        path: &Path,
+        thumbnailer: &Thumbnailer,
+        save: bool,
+    ) -> Option<Option<&TextureHandle>> {
+        if !self.textures.contains_key(path) && self.requested.insert(path.to_path_buf()) {
+            thumbnailer.request(path.to_path_buf(), self.size, save);
+        }
+        self.textures.get(path).map(Option::as_ref)
+    }
+}
+
+/// Draws a thumbnail centered in the area without scaling it up, or a placeholder while it
+/// is being generated.
+pub fn paint_thumbnail(painter: &Painter, area: Rect, thumbnail: Option<Option<&TextureHandle>>) {
+    let style = painter.ctx().global_style();
+    let visuals = &style.visuals;
+    match thumbnail {
+        Some(Some(texture)) => {
+            let size = texture.size_vec2() / painter.ctx().pixels_per_point();
+            let scale = (area.width() / size.x).min(area.height() / size.y).min(1.0);
+            painter.image(
+                texture.id(),
+                Rect::from_center_size(area.center(), size * scale),
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+        Some(None) => {
+            painter.text(
+                area.center(),
+                Align2::CENTER_CENTER,
+                "⚠",
+                FontId::proportional(area.height().min(32.0) / 2.0),
+                visuals.weak_text_color(),
+            );
+        }
+        None => {
+            painter.rect_filled(
+                area.shrink(area.width().min(area.height()) / 4.0),
+                4.0,
+                visuals.faint_bg_color,
+            );
+        }
+    }
+}
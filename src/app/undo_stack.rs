@@ -1,13 +1,53 @@
+use std::sync::Arc;
+
+use simp::io::{ExrSelection, PsdVisibility, RawSettings, Svg};
+
 use crate::util::Image;
 
+/// The frames an edit replaced, with the svg they were rendered from so undoing the edit
+/// keeps the image sharp when zoomed in.
+pub struct Snapshot {
+    pub frames: Vec<Image>,
+    pub svg: Option<Arc<Svg>>,
+}
+
+impl From<Vec<Image>> for Snapshot {
+    fn from(frames: Vec<Image>) -> Self {
+        Self { frames, svg: None }
+    }
+}
+
 pub enum UndoFrame {
     Rotate(i32),
     FlipHorizontal,
     FlipVertical,
-    Crop { frames: Vec<Image>, rotation: i32 },
-    Resize(Vec<Image>),
-    Color(Vec<Image>),
-    ColorSpace(Vec<Image>),
+    Crop {
+        frames: Snapshot,
+        rotation: i32,
+    },
+    Resize(Snapshot),
+    Color(Snapshot),
+    ColorSpace(Snapshot),
+    ExrLayer {
+        frames: Snapshot,
+        selection: ExrSelection,
+    },
+    Page {
+        frames: Snapshot,
+        page: usize,
+    },
+    PsdVisibility {
+        frames: Snapshot,
+        visibility: PsdVisibility,
+    },
+    RawDevelop {
+        frames: Snapshot,
+        settings: RawSettings,
+    },
+    Frames {
+        frames: Snapshot,
+        index: usize,
+    },
 }
 
 impl UndoFrame {
@@ -20,6 +60,11 @@ impl UndoFrame {
             UndoFrame::Resize(..) => true,
             UndoFrame::Color(..) => true,
             UndoFrame::ColorSpace(..) => true,
+            UndoFrame::ExrLayer { .. } => false,
+            UndoFrame::Page { .. } => false,
+            UndoFrame::PsdVisibility { .. } => false,
+            UndoFrame::RawDevelop { .. } => false,
+            UndoFrame::Frames { .. } => true,
         }
     }
 }
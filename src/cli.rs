@@ -3,6 +3,7 @@ pub fn get_clap_command() -> clap::Command {
         .author(env!("CARGO_PKG_AUTHORS"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
         .version(env!("CARGO_PKG_VERSION"))
+        .args_conflicts_with_subcommands(true)
         .arg(
             clap::Arg::new("fullscreen")
                 .short('f')
@@ -36,4 +37,110 @@ pub fn get_clap_command() -> clap::Command {
                 .help("Defines window class/app_id on X11/Wayland"),
         )
         .arg(clap::Arg::new("file").help("Load this file").index(1))
+        .subcommand(
+            clap::Command::new("convert")
+                .about("Convert an image without opening a window")
+                .arg(
+                    clap::Arg::new("input")
+                        .help("Image to read")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    clap::Arg::new("output")
+                        .help("Image to write, the format is picked from the extension")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    clap::Arg::new("quality")
+                        .long("quality")
+                        .short('q')
+                        .help("Encoder quality for jpeg, webp and jpeg xl")
+                        .value_parser(clap::value_parser!(f32)),
+                )
+                .arg(
+                    clap::Arg::new("lossy")
+                        .long("lossy")
+                        .help("Use lossy compression for webp and jpeg xl")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("exposure")
+                        .long("exposure")
+                        .help("Exposure adjustment in stops, applied before tone mapping")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f32)),
+                )
+                .arg(
+                    clap::Arg::new("gamma")
+                        .long("gamma")
+                        .help("Gamma applied after tone mapping")
+                        .value_parser(clap::value_parser!(f32)),
+                )
+                .arg(
+                    clap::Arg::new("tonemap")
+                        .long("tonemap")
+                        .help("Tone mapping operator: clamp, reinhard, aces or agx"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("batch")
+                .about("Apply a list of operations to many images without opening a window")
+                .arg(
+                    clap::Arg::new("ops")
+                        .long("ops")
+                        .required(true)
+                        .help("Comma separated operations, for example \"resize:1920x1080:lanczos3,rotate:90,color:saturation=20,tonemap:exposure=1:operator=aces\""),
+                )
+                .arg(
+                    clap::Arg::new("out-dir")
+                        .long("out-dir")
+                        .short('o')
+                        .required(true)
+                        .help("Directory the results are written to"),
+                )
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .help("Extension of the output files, defaults to the extension of each input"),
+                )
+                .arg(
+                    clap::Arg::new("quality")
+                        .long("quality")
+                        .short('q')
+                        .help("Encoder quality for jpeg, webp and jpeg xl")
+                        .value_parser(clap::value_parser!(f32)),
+                )
+                .arg(
+                    clap::Arg::new("lossy")
+                        .long("lossy")
+                        .help("Use lossy compression for webp and jpeg xl")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("files")
+                        .help("Images to process")
+                        .required(true)
+                        .num_args(1..)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("info")
+                .about("Print what simp decodes from images without opening a window")
+                .arg(
+                    clap::Arg::new("json")
+                        .long("json")
+                        .help("Print the information as json")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("files")
+                        .help("Images to inspect")
+                        .required(true)
+                        .num_args(1..)
+                        .index(1),
+                ),
+        )
 }
@@ -0,0 +1,597 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use cgmath::Vector2;
+use clap::ArgMatches;
+use image::{ColorType, imageops::FilterType};
+use simp::io::{COLOR_SPACES, JpegOptions, SaveOptions, ToneMapOperator, WebpOptions, save_frames};
+
+use crate::{
+    Config,
+    app::{
+        color_type_to_str,
+        load_image::{LoadError, load_uncached, load_with_loader},
+        op_queue::Op,
+        preferences::PREFERENCES,
+        resize::DEFAULT_RESAMPLE,
+    },
+    rect::Rect,
+    util::ImageData,
+};
+
+/// Loads the user preferences so decoding behaves the same as in the viewer.
+pub fn init() {
+    #[cfg(windows)]
+    unsafe {
+        // Release builds use the windows subsystem so there is no console unless we attach to the parent one.
+        winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+    }
+
+    let mut config: Config = confy::load("simp", None).unwrap_or_default();
+    config.preferences.clamp();
+    *PREFERENCES.lock().unwrap() = config.preferences;
+}
+
+pub fn convert(matches: &ArgMatches) -> i32 {
+    let input: &String = matches.get_one("input").unwrap();
+    let output: &String = matches.get_one("output").unwrap();
+    let options = save_options(matches, Path::new(output));
+    let tone_map = match tone_map_from_args(matches) {
+        Ok(tone_map) => tone_map,
+        Err(error) => {
+            eprintln!("{error}");
+            return 2;
+        }
+    };
+
+    let mut image_data = match load_uncached(input) {
+        Ok(image_data) => image_data,
+        Err(error) => {
+            eprintln!("{input}: {error}");
+            return 1;
+        }
+    };
+    if let Some(frames) = tone_map.and_then(|op| op.apply(&image_data)) {
+        image_data.frames = frames;
+    }
+
+    match save_frames(
+        PathBuf::from(output),
+        image_data.frames,
+        &image_data.raw_metadata,
+        options,
+    ) {
+        Ok(_) => 0,
+        Err(error) => {
+            eprintln!("{output}: {error}");
+            1
+        }
+    }
+}
+
+pub fn batch(matches: &ArgMatches) -> i32 {
+    let ops: &String = matches.get_one("ops").unwrap();
+    let out_dir = PathBuf::from(matches.get_one::<String>("out-dir").unwrap());
+    let format: Option<&String> = matches.get_one("format");
+    let files: Vec<&String> = matches.get_many("files").unwrap().collect();
+
+    let ops = match parse_ops(ops) {
+        Ok(ops) => ops,
+        Err(error) => {
+            eprintln!("invalid operations: {error}");
+            return 2;
+        }
+    };
+
+    if let Err(error) = fs::create_dir_all(&out_dir) {
+        eprintln!("{}: {error}", out_dir.display());
+        return 1;
+    }
+
+    // Inputs with the same file name from different dirs would overwrite each other.
+    let mut written: HashMap<PathBuf, &String> = HashMap::new();
+    let mut jobs = Vec::new();
+    let mut collisions = 0;
+    for &input in &files {
+        let mut output = out_dir.join(Path::new(input).file_name().unwrap_or_default());
+        if let Some(format) = format {
+            output.set_extension(format);
+        }
+        match written.get(&output) {
+            Some(other) => {
+                eprintln!(
+                    "{input}: {} is already written by {other}",
+                    output.display()
+                );
+                collisions += 1;
+            }
+            None => {
+                written.insert(output.clone(), input);
+                jobs.push((input, output));
+            }
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(collisions);
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(jobs.len());
+
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((input, output)) = jobs.get(index) else {
+                        break;
+                    };
+
+                    let options = save_options(matches, output);
+                    if let Err(error) = process(input, output.clone(), &ops, options) {
+                        eprintln!("{input}: {error}");
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    if failed.into_inner() > 0 { 1 } else { 0 }
+}
+
+/// Maps `--quality` and `--lossy` onto the encoder picked from the extension of `output`.
+/// For JPEG XL the quality is the butteraugli distance, AVIF is always lossy.
+fn save_options(matches: &ArgMatches, output: &Path) -> SaveOptions {
+    let quality: Option<f32> = matches.get_one("quality").copied();
+    let lossy = matches.get_flag("lossy");
+    let ext = output
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "jpg" | "jpeg" | "jpe" | "jif" | "jfif" => {
+            let mut options = JpegOptions::default();
+            if let Some(quality) = quality {
+                options.quality = quality.clamp(1.0, 100.0) as u8;
+            }
+            SaveOptions::Jpeg(options)
+        }
+        "webp" => {
+            let mut options = WebpOptions {
+                lossy,
+                ..Default::default()
+            };
+            if let Some(quality) = quality {
+                options.quality = quality;
+            }
+            SaveOptions::WebP(options)
+        }
+        #[cfg(feature = "jxl")]
+        "jxl" => {
+            let mut options = simp::io::JxlOptions {
+                lossy,
+                ..Default::default()
+            };
+            if let Some(quality) = quality {
+                options.distance = quality;
+            }
+            SaveOptions::Jxl(options)
+        }
+        #[cfg(feature = "avif")]
+        "avif" => {
+            let mut options = simp::io::AvifOptions::default();
+            if let Some(quality) = quality {
+                options.quality = quality.clamp(1.0, 100.0) as u8;
+            }
+            SaveOptions::Avif(options)
+        }
+        #[cfg(feature = "heif")]
+        "heic" | "heif" => {
+            let mut options = simp::io::HeifOptions {
+                lossy,
+                ..Default::default()
+            };
+            if let Some(quality) = quality {
+                options.quality = quality.clamp(0.0, 100.0) as u8;
+            }
+            SaveOptions::Heif(options)
+        }
+        _ => SaveOptions::Default,
+    }
+}
+
+fn process(input: &str, output: PathBuf, ops: &[Op], options: SaveOptions) -> Result<(), String> {
+    let mut image_data = load_uncached(input).map_err(|e| e.to_string())?;
+    for op in ops {
+        op.validate(&image_data)?;
+        if let Some(frames) = op.apply(&image_data) {
+            image_data.frames = frames;
+        }
+    }
+    save_frames(output, image_data.frames, &image_data.raw_metadata, options)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Parses a pipeline such as `resize:1920x1080:lanczos3,rotate:90,color:saturation=20`.
+fn parse_ops(ops: &str) -> Result<Vec<Op>, String> {
+    ops.split(',')
+        .map(str::trim)
+        .filter(|op| !op.is_empty())
+        .map(parse_op)
+        .collect()
+}
+
+fn parse_op(op: &str) -> Result<Op, String> {
+    let mut parts = op.split(':');
+    let name = parts.next().unwrap_or_default().to_lowercase();
+    let args: Vec<&str> = parts.collect();
+
+    let op = match (name.as_str(), args.as_slice()) {
+        ("resize", [size]) => Op::Resize(parse_size(size)?, DEFAULT_RESAMPLE),
+        ("resize", [size, filter]) => Op::Resize(parse_size(size)?, parse_filter(filter)?),
+        ("rotate", [degrees]) => {
+            let degrees: i32 = degrees
+                .parse()
+                .map_err(|_| format!("invalid rotation `{degrees}`"))?;
+            if degrees % 90 != 0 {
+                return Err(format!("rotation must be a multiple of 90, got {degrees}"));
+            }
+            Op::Rotate(degrees / 90)
+        }
+        ("flip", ["horizontal"]) => Op::FlipHorizontal,
+        ("flip", ["vertical"]) => Op::FlipVertical,
+        ("crop", [x, y, size]) => {
+            let x: u32 = x.parse().map_err(|_| format!("invalid crop x `{x}`"))?;
+            let y: u32 = y.parse().map_err(|_| format!("invalid crop y `{y}`"))?;
+            let size = parse_size(size)?;
+            Op::Crop(Rect::new(
+                Vector2::new(x as f32, y as f32),
+                Vector2::new(size.x as f32, size.y as f32),
+            ))
+        }
+        ("color", args) if !args.is_empty() => parse_color(args)?,
+        ("colorspace", [name]) => Op::ColorSpace(parse_color_type(name)?),
+        ("tonemap", args) => parse_tone_map(args)?,
+        _ => return Err(format!("unknown operation `{op}`")),
+    };
+
+    Ok(op)
+}
+
+fn parse_size(size: &str) -> Result<Vector2<u32>, String> {
+    let invalid = || format!("invalid size `{size}`, expected WIDTHxHEIGHT");
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let width: u32 = width.parse().map_err(|_| invalid())?;
+    let height: u32 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok(Vector2::new(width, height))
+}
+
+fn parse_filter(filter: &str) -> Result<FilterType, String> {
+    match filter.to_lowercase().as_str() {
+        "nearest" => Ok(FilterType::Nearest),
+        "triangle" | "linear" => Ok(FilterType::Triangle),
+        "catmullrom" | "cubic" => Ok(FilterType::CatmullRom),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "lanczos3" | "lanczos" => Ok(FilterType::Lanczos3),
+        _ => Err(format!("unknown filter `{filter}`")),
+    }
+}
+
+fn parse_color(args: &[&str]) -> Result<Op, String> {
+    let mut hue = 0.0;
+    let mut saturation = 0.0;
+    let mut contrast = 0.0;
+    let mut brightness = 0.0;
+    let mut grayscale = false;
+    let mut invert = false;
+
+    for arg in args {
+        let (key, value) = arg.split_once('=').unwrap_or((arg, "true"));
+        let number = || {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("invalid value `{value}` for {key}"))
+        };
+        let flag = || {
+            value
+                .parse::<bool>()
+                .map_err(|_| format!("invalid value `{value}` for {key}"))
+        };
+        match key {
+            "hue" => hue = number()?.clamp(0.0, 180.0),
+            "saturation" => saturation = number()?.clamp(-100.0, 100.0),
+            "contrast" => contrast = number()?.clamp(-100.0, 100.0),
+            "brightness" => brightness = number()?.clamp(-100.0, 100.0),
+            "grayscale" => grayscale = flag()?,
+            "invert" => invert = flag()?,
+            _ => return Err(format!("unknown color adjustment `{key}`")),
+        }
+    }
+
+    Ok(Op::Color {
+        hue,
+        saturation,
+        contrast,
+        brightness,
+        grayscale,
+        invert,
+    })
+}
+
+/// Builds a tone map operation when any of `--exposure`, `--gamma` or `--tonemap` is given.
+fn tone_map_from_args(matches: &ArgMatches) -> Result<Option<Op>, String> {
+    let exposure: Option<f32> = matches.get_one("exposure").copied();
+    let gamma: Option<f32> = matches.get_one("gamma").copied();
+    let operator: Option<&String> = matches.get_one("tonemap");
+    if exposure.is_none() && gamma.is_none() && operator.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(Op::ToneMap {
+        exposure: exposure.unwrap_or(0.0).clamp(-10.0, 10.0),
+        gamma: gamma.unwrap_or(1.0).clamp(0.2, 5.0),
+        operator: operator
+            .map(|name| parse_tone_map_operator(name))
+            .transpose()?
+            .unwrap_or_default(),
+    }))
+}
+
+fn parse_tone_map(args: &[&str]) -> Result<Op, String> {
+    let mut exposure = 0.0;
+    let mut gamma = 1.0;
+    let mut operator = ToneMapOperator::Clamp;
+
+    for arg in args {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got `{arg}`"))?;
+        let number = || {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("invalid value `{value}` for {key}"))
+        };
+        match key {
+            "exposure" => exposure = number()?.clamp(-10.0, 10.0),
+            "gamma" => gamma = number()?.clamp(0.2, 5.0),
+            "operator" => operator = parse_tone_map_operator(value)?,
+            _ => return Err(format!("unknown tone map setting `{key}`")),
+        }
+    }
+
+    Ok(Op::ToneMap {
+        exposure,
+        gamma,
+        operator,
+    })
+}
+
+fn parse_tone_map_operator(name: &str) -> Result<ToneMapOperator, String> {
+    match name.to_lowercase().as_str() {
+        "clamp" => Ok(ToneMapOperator::Clamp),
+        "reinhard" => Ok(ToneMapOperator::Reinhard),
+        "aces" => Ok(ToneMapOperator::AcesFilmic),
+        "agx" => Ok(ToneMapOperator::Agx),
+        _ => Err(format!("unknown tone mapping operator `{name}`")),
+    }
+}
+
+fn parse_color_type(name: &str) -> Result<ColorType, String> {
+    COLOR_SPACES
+        .into_iter()
+        .find(|color_type| color_type_to_str(*color_type).eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown color space `{name}`"))
+}
+
+pub fn info(matches: &ArgMatches) -> i32 {
+    let json = matches.get_flag("json");
+    let files: Vec<&String> = matches.get_many("files").unwrap().collect();
+
+    let mut failed = false;
+    let mut entries = Vec::new();
+    for file in files {
+        let res = fs::read(file)
+            .map_err(LoadError::from)
+            .and_then(|bytes| load_with_loader(&bytes, Some(PathBuf::from(file))))
+            .and_then(|(image_data, loader)| {
+                if image_data.frames.is_empty() {
+                    Err(LoadError::Decoding(String::from("the image has no frames")))
+                } else {
+                    Ok((image_data, loader))
+                }
+            });
+        if res.is_err() {
+            failed = true;
+        }
+
+        if json {
+            entries.push(info_json(file, &res));
+        } else {
+            match res {
+                Ok((image_data, loader)) => print!("{}", info_text(file, &image_data, loader)),
+                Err(error) => eprintln!("{file}: {error}"),
+            }
+        }
+    }
+
+    if json {
+        println!("[{}]", entries.join(","));
+    }
+
+    if failed { 1 } else { 0 }
+}
+
+/// `image_data` has at least one frame.
+fn info_text(file: &str, image_data: &ImageData, loader: &str) -> String {
+    let buffer = image_data.frames[0].buffer();
+    let mut out = String::new();
+    let _ = writeln!(out, "{file}");
+    let _ = writeln!(out, "  loader: {loader}");
+    let _ = writeln!(out, "  dimensions: {}x{}", buffer.width(), buffer.height());
+    let _ = writeln!(out, "  color type: {}", color_type_to_str(buffer.color()));
+    let _ = writeln!(out, "  frames: {}", image_data.frames.len());
+    if image_data.frames.len() > 1 {
+        let delays: Vec<_> = image_data
+            .frames
+            .iter()
+            .map(|frame| format!("{}ms", frame.delay.as_millis()))
+            .collect();
+        let _ = writeln!(out, "  delays: {}", delays.join(" "));
+    }
+    if let Some(pages) = &image_data.pages {
+        let _ = writeln!(out, "  pages: {}", pages.len());
+        for (index, page) in pages.iter().enumerate() {
+            let buffer = page.buffer();
+            let _ = writeln!(
+                out,
+                "    {}: {}x{} {}",
+                index + 1,
+                buffer.width(),
+                buffer.height(),
+                color_type_to_str(buffer.color())
+            );
+        }
+    }
+    if let Some(exr) = &image_data.exr {
+        let _ = writeln!(out, "  exr layers:");
+        for layer in &exr.layers {
+            let channels: Vec<_> = layer.channels.iter().map(|c| c.name.as_str()).collect();
+            let name = if layer.name.is_empty() {
+                "(default)"
+            } else {
+                &layer.name
+            };
+            let _ = writeln!(out, "    {name}: {}", channels.join(" "));
+        }
+    }
+    if let Some(psd) = &image_data.psd {
+        let _ = writeln!(out, "  psd layers:");
+        for layer in &psd.layers {
+            let hidden = if layer.visible { "" } else { " (hidden)" };
+            let _ = writeln!(out, "    {}{hidden}", layer.name);
+        }
+    }
+    if !image_data.metadata.is_empty() {
+        let _ = writeln!(out, "  metadata:");
+        for (key, value) in &image_data.metadata {
+            let _ = writeln!(out, "    {key}: {value}");
+        }
+    }
+    out
+}
+
+fn info_json(file: &str, res: &Result<(ImageData, &str), LoadError>) -> String {
+    let (image_data, loader) = match res {
+        Ok(ok) => ok,
+        Err(error) => {
+            return format!(
+                "{{\"path\":{},\"error\":{}}}",
+                json_string(file),
+                json_string(&error.to_string())
+            );
+        }
+    };
+
+    let buffer = image_data.frames[0].buffer();
+    let delays: Vec<_> = image_data
+        .frames
+        .iter()
+        .map(|frame| frame.delay.as_millis().to_string())
+        .collect();
+    let metadata: Vec<_> = image_data
+        .metadata
+        .iter()
+        .map(|(key, value)| format!("[{},{}]", json_string(key), json_string(value)))
+        .collect();
+    let exr_layers: Vec<_> = image_data
+        .exr
+        .iter()
+        .flat_map(|exr| &exr.layers)
+        .map(|layer| {
+            let channels: Vec<_> = layer
+                .channels
+                .iter()
+                .map(|channel| json_string(&channel.name))
+                .collect();
+            format!(
+                "{{\"name\":{},\"channels\":[{}]}}",
+                json_string(&layer.name),
+                channels.join(",")
+            )
+        })
+        .collect();
+    let psd_layers: Vec<_> = image_data
+        .psd
+        .iter()
+        .flat_map(|psd| &psd.layers)
+        .map(|layer| {
+            format!(
+                "{{\"name\":{},\"visible\":{}}}",
+                json_string(&layer.name),
+                layer.visible
+            )
+        })
+        .collect();
+    let pages: Vec<_> = image_data
+        .pages
+        .iter()
+        .flat_map(|pages| pages.iter())
+        .map(|page| {
+            let buffer = page.buffer();
+            format!(
+                "{{\"width\":{},\"height\":{},\"color_type\":{}}}",
+                buffer.width(),
+                buffer.height(),
+                json_string(color_type_to_str(buffer.color()))
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"path\":{},\"loader\":{},\"width\":{},\"height\":{},\"color_type\":{},\"frames\":{},\"delays_ms\":[{}],\"metadata\":[{}],\"exr_layers\":[{}],\"psd_layers\":[{}],\"pages\":[{}]}}",
+        json_string(file),
+        json_string(loader),
+        buffer.width(),
+        buffer.height(),
+        json_string(color_type_to_str(buffer.color())),
+        image_data.frames.len(),
+        delays.join(","),
+        metadata.join(","),
+        exr_layers.join(","),
+        psd_layers.join(","),
+        pages.join(","),
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,214 @@
+use std::io::Cursor;
+
+use exr::prelude::{ReadChannels, ReadLayers};
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+
+/// Every channel of an OpenEXR file grouped into layers, so AOVs such as depth or
+/// normals can be shown instead of only the default RGBA layer.
+#[derive(Debug)]
+pub struct ExrLayers {
+    pub layers: Vec<ExrLayer>,
+}
+
+#[derive(Debug)]
+pub struct ExrLayer {
+    /// Empty for the default layer.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub channels: Vec<ExrChannel>,
+}
+
+#[derive(Debug)]
+pub struct ExrChannel {
+    /// The name without the layer prefix, e.g. `R` or `Z`.
+    pub name: String,
+    pub samples: Vec<f32>,
+}
+
+/// Which layer is displayed and optionally a single channel of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExrSelection {
+    pub layer: usize,
+    pub channel: Option<usize>,
+}
+
+impl ExrLayers {
+    pub fn read(bytes: &[u8]) -> Option<Self> {
+        let image = exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .ok()?;
+
+        let mut layers: Vec<ExrLayer> = Vec::new();
+        for part in &image.layer_data {
+            let part_name = part
+                .attributes
+                .layer_name
+                .as_ref()
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+
+            for channel in &part.channel_data.list {
+                // Single part files name their channels `layer.channel`.
+                let full_name = channel.name.to_string();
+                let (prefix, name) = full_name.rsplit_once('.').unwrap_or(("", &full_name));
+                let layer_name = match (part_name.is_empty(), prefix.is_empty()) {
+                    (true, _) => prefix.to_string(),
+                    (false, true) => part_name.clone(),
+                    (false, false) => format!("{part_name}.{prefix}"),
+                };
+
+                let samples: Vec<f32> = channel
+                    .sample_data
+                    .values_as_f32()
+                    .map(|sample| if sample.is_nan() { 0.0 } else { sample })
+                    .collect();
+                let size = (part.size.width(), part.size.height());
+                let sampling = (channel.sampling.x(), channel.sampling.y());
+                // Channels with samples missing are left out.
+                let Some(samples) = upsample(samples, sampling, size) else {
+                    continue;
+                };
+                let channel = ExrChannel {
+                    name: name.to_string(),
+                    samples,
+                };
+
+                match layers.iter_mut().find(|layer| layer.name == layer_name) {
+                    Some(layer) => layer.channels.push(channel),
+                    None => layers.push(ExrLayer {
+                        name: layer_name,
+                        width: part.size.width() as u32,
+                        height: part.size.height() as u32,
+                        channels: vec![channel],
+                    }),
+                }
+            }
+        }
+
+        (!layers.is_empty()).then_some(Self { layers })
+    }
+
+    /// Bytes taken by the samples of every layer.
+    pub fn memory_size(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|layer| &layer.channels)
+            .map(|channel| channel.samples.len() * size_of::<f32>())
+            .sum()
+    }
+
+    /// The layer shown when the file is opened, the first one with color channels.
+    pub fn default_selection(&self) -> ExrSelection {
+        let layer = self
+            .layers
+            .iter()
+            .position(|layer| layer.color_channels().is_some())
+            .unwrap_or_default();
+        ExrSelection {
+            layer,
+            channel: None,
+        }
+    }
+
+    /// Whether the selection is shown as a single gray channel.
+    pub fn is_grayscale(&self, selection: ExrSelection) -> bool {
+        selection.channel.is_some()
+            || self
+                .layers
+                .get(selection.layer)
+                .is_some_and(|layer| layer.color_channels().is_none() && layer.channels.len() < 3)
+    }
+
+    pub fn image(&self, selection: ExrSelection) -> Option<DynamicImage> {
+        let layer = self.layers.get(selection.layer)?;
+        match selection.channel {
+            Some(channel) => layer.grayscale(layer.channels.get(channel)?),
+            None => layer.composite(),
+        }
+    }
+}
+
+impl ExrLayer {
+    fn channel(&self, name: &str) -> Option<&ExrChannel> {
+        self.channels
+            .iter()
+            .find(|channel| channel.name.eq_ignore_ascii_case(name))
+    }
+
+    fn color_channels(&self) -> Option<[&ExrChannel; 3]> {
+        Some([self.channel("R")?, self.channel("G")?, self.channel("B")?])
+    }
+
+    /// RGB(A) channels as a float image, any other layer with at least three channels
+    /// (such as normals) uses its first three, single channels become grayscale.
+    fn composite(&self) -> Option<DynamicImage> {
+        let rgb = match self.color_channels() {
+            Some(rgb) => rgb,
+            None if self.channels.len() >= 3 => {
+                [&self.channels[0], &self.channels[1], &self.channels[2]]
+            }
+            None => return self.grayscale(self.channels.first()?),
+        };
+        let alpha = self.channel("A");
+
+        let len = (self.width * self.height) as usize;
+        if rgb.iter().any(|channel| channel.samples.len() != len) {
+            return None;
+        }
+        let alpha = alpha.filter(|alpha| alpha.samples.len() == len);
+        let mut data = Vec::with_capacity(len * 4);
+        for i in 0..len {
+            data.push(rgb[0].samples[i]);
+            data.push(rgb[1].samples[i]);
+            data.push(rgb[2].samples[i]);
+            data.push(alpha.map(|alpha| alpha.samples[i]).unwrap_or(1.0));
+        }
+        ImageBuffer::<Rgba<f32>, _>::from_raw(self.width, self.height, data)
+            .map(DynamicImage::ImageRgba32F)
+    }
+
+    /// A single channel as a gray float image, the values are kept as they are so depth
+    /// and other data can be saved. The view stretches them to their range.
+    fn grayscale(&self, channel: &ExrChannel) -> Option<DynamicImage> {
+        let data = channel
+            .samples
+            .iter()
+            .flat_map(|&sample| [sample; 3])
+            .collect();
+        ImageBuffer::<Rgb<f32>, _>::from_raw(self.width, self.height, data)
+            .map(DynamicImage::ImageRgb32F)
+    }
+}
+
+/// Repeats the samples of a subsampled channel so it has one for every pixel, `None` if
+/// there are fewer samples than the sampling rate needs.
+fn upsample(
+    samples: Vec<f32>,
+    (x_sampling, y_sampling): (usize, usize),
+    (width, height): (usize, usize),
+) -> Option<Vec<f32>> {
+    if (x_sampling, y_sampling) == (1, 1) {
+        return (samples.len() == width * height).then_some(samples);
+    }
+    if x_sampling == 0 || y_sampling == 0 {
+        return None;
+    }
+    let stride = width / x_sampling;
+    if samples.len() < stride * (height / y_sampling) {
+        return None;
+    }
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            samples
+                .get((y / y_sampling) * stride + x / x_sampling)
+                .copied()
+        })
+        .collect()
+}
@@ -3,8 +3,6 @@ use image::{
 };
 use num_traits::{NumCast, ToPrimitive};
 
-use crate::{max, min};
-
 pub trait ToGrayScale {
     type SubPixel;
     fn to_gray_scale(&self) -> LumaA<Self::SubPixel>;
@@ -96,8 +94,8 @@ pub fn rgb2hsl<Sub: Primitive + ToPrimitive>(rgb: Rgb<Sub>) -> Hsl {
     let g: f64 = rgb.0[1].to_f64().unwrap() / max;
     let b: f64 = rgb.0[2].to_f64().unwrap() / max;
 
-    let max = max!(max!(r, g), b);
-    let min = min!(min!(r, g), b);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
 
     // Luminosity is the average of the max and min rgb color intensities.
     let l = (max + min) / 2_f64;
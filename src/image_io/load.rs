@@ -1,14 +1,252 @@
-use std::{io::Cursor, time::Duration};
+use std::{
+    cell::Cell,
+    error, fmt, fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use image::{
-    AnimationDecoder, DynamicImage, Frame, ImageBuffer, ImageFormat, ImageReader, Rgb, Rgba,
+    AnimationDecoder, DynamicImage, Frame, ImageBuffer, ImageFormat, ImageReader, Rgba,
     codecs::{gif::GifDecoder, openexr::OpenExrDecoder, png::PngDecoder, webp::WebPDecoder},
+    metadata::Orientation,
 };
-use imagepipe::{ImageSource, Pipeline};
 use psd::Psd;
-use resvg::usvg::{Options, Tree};
+use resvg::tiny_skia::IntSize;
+use rexif::ExifTag;
+
+use super::{
+    Image, ImageData, RawMetadata,
+    exr::ExrLayers,
+    extensions::*,
+    pages::load_pages,
+    photoshop::PsdLayers,
+    raw::{RawFile, embedded_preview, has_sidecar},
+    svg::Svg,
+};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Decoding(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => e.fmt(f),
+            LoadError::Decoding(ref source) => {
+                write!(f, "error decoding image: {:?}", source)
+            }
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            LoadError::Io(ref e) => Some(e),
+            LoadError::Decoding(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(err: std::io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+/// Settings for the loaders that need them.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    /// Svgs are rasterized so that the smaller axis is at least this many pixels.
+    pub min_svg_size: u32,
+    /// Camera raw files are decoded from their embedded JPEG preview when they have one.
+    pub raw_preview: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            min_svg_size: 1000,
+            raw_preview: false,
+        }
+    }
+}
+
+pub fn load_uncached(path: impl AsRef<Path>, options: LoadOptions) -> Result<ImageData, LoadError> {
+    let bytes = fs::read(path.as_ref())?;
+    load_from_bytes(&bytes, Some(path.as_ref().to_path_buf()), options)
+}
 
-use crate::{app::preferences::PREFERENCES, util::Image};
+pub fn load_from_bytes(
+    bytes: &[u8],
+    path_buf: Option<PathBuf>,
+    options: LoadOptions,
+) -> Result<ImageData, LoadError> {
+    load_with_loader(bytes, path_buf, options).map(|(image_data, _)| image_data)
+}
+
+type Loader<'a> = &'a dyn Fn(&[u8]) -> Option<Vec<Image>>;
+
+/// Same as [`load_from_bytes`] but also returns the name of the loader that decoded the image.
+pub fn load_with_loader(
+    bytes: &[u8],
+    path_buf: Option<PathBuf>,
+    options: LoadOptions,
+) -> Result<(ImageData, &'static str), LoadError> {
+    let source = path_buf
+        .as_ref()
+        .map(|path| path.to_string_lossy().into())
+        .unwrap_or_else(|| String::from("from stdin"));
+    let extension = path_buf
+        .as_ref()
+        .and_then(|path| path.extension())
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+
+    let icc = Cell::new(None);
+    let exr = Cell::new(None);
+    // Set by loaders that return every page of a container instead of animation frames.
+    let paged = Cell::new(false);
+    let raster_loader = |bytes: &[u8]| {
+        if let Some((images, layers)) = load_exr(bytes) {
+            exr.set(Some(Arc::new(layers)));
+            return Some(images);
+        }
+        if let Some(pages) = load_pages(bytes) {
+            paged.set(true);
+            return Some(pages);
+        }
+        load_raster(bytes)
+    };
+    let raw = Cell::new(None);
+    let preview = Cell::new(false);
+    let raw_loader = |bytes: &[u8]| {
+        // The camera preview would hide the settings stored for the file.
+        if options.raw_preview
+            && !path_buf.as_deref().is_some_and(has_sidecar)
+            && let Some(image) = embedded_preview(bytes)
+        {
+            preview.set(true);
+            return Some(vec![Image::new(image)]);
+        }
+        load_raw(bytes, path_buf.as_deref()).map(|(images, file)| {
+            raw.set(Some(file));
+            images
+        })
+    };
+    let psd = Cell::new(None);
+    let psd_loader = |bytes: &[u8]| {
+        load_psd(bytes).map(|(images, layers)| {
+            psd.set(layers.map(Arc::new));
+            images
+        })
+    };
+    let svg = Cell::new(None);
+    let svg_loader = |bytes: &[u8]| {
+        load_svg(bytes, options.min_svg_size).map(|(images, document)| {
+            svg.set(Some(Arc::new(document)));
+            images
+        })
+    };
+    let jxl_loader = |bytes: &[u8]| {
+        load_jxl(bytes).map(|(images, profile)| {
+            icc.set(profile);
+            images
+        })
+    };
+    let heif_loader = |bytes: &[u8]| {
+        load_heif(bytes).map(|(images, profile)| {
+            icc.set(profile);
+            paged.set(images.len() > 1);
+            images
+        })
+    };
+    let mut loaders: [(&'static str, Loader); 7] = [
+        ("raw", &raw_loader),
+        ("svg", &svg_loader),
+        ("psd", &psd_loader),
+        ("raster", &raster_loader),
+        ("tga", &load_un_detectable_raster),
+        ("jxl", &jxl_loader),
+        ("heif", &heif_loader),
+    ];
+
+    if HEIF.contains(&extension.as_str()) {
+        loaders.swap(0, 6);
+    } else if JXL.contains(&extension.as_str()) {
+        loaders.swap(0, 5);
+    } else if RASTER.contains(&extension.as_str()) {
+        loaders.swap(0, 3);
+    } else if VECTOR.contains(&extension.as_str()) {
+        loaders.swap(0, 1);
+    } else if PHOTOSHOP.contains(&extension.as_str()) {
+        loaders.swap(0, 2);
+    } else if UNDETECTABLE_RASTER.contains(&extension.as_str()) {
+        loaders.swap(0, 4);
+    }
+
+    let mut orientation = None;
+    let mut metadata = Vec::new();
+    if let Ok(exif) = rexif::parse_buffer_quiet(bytes).0 {
+        for entry in exif.entries {
+            if ExifTag::UnknownToMe != entry.tag {
+                let text = entry.value_more_readable;
+                metadata.push((entry.tag.to_string(), text.to_string()));
+            }
+            if ExifTag::Orientation == entry.tag {
+                let raw = match entry.value {
+                    rexif::TagValue::U16(vec) => vec.last().copied(),
+                    _ => None,
+                };
+                if let Some(raw) = raw {
+                    orientation = Orientation::from_exif(raw.min(255) as u8);
+                }
+            }
+        }
+    }
+
+    for (name, loader) in loaders {
+        if let Some(mut image) = loader(bytes) {
+            if let Some(orientation) = orientation {
+                for image in image.iter_mut() {
+                    image.buffer_mut().apply_orientation(orientation);
+                }
+            }
+            let mut raw_metadata = RawMetadata::read(bytes);
+            if raw_metadata.icc.is_none() {
+                raw_metadata.icc = icc.take();
+            }
+            let (frames, pages) = if paged.get() {
+                (vec![image[0].clone()], Some(Arc::from(image)))
+            } else {
+                (image, None)
+            };
+            let image_data = ImageData {
+                frames,
+                metadata,
+                raw_metadata,
+                exr: exr.take(),
+                pages,
+                psd: psd.take(),
+                raw: raw.take().map(|mut raw: RawFile| {
+                    raw.orientation = orientation;
+                    Arc::new(raw)
+                }),
+                preview: preview.get(),
+                downscaled: false,
+                svg: svg.take(),
+            };
+            return Ok((image_data, name));
+        }
+    }
+
+    Err(LoadError::Decoding(source))
+}
 
 pub fn decode_images<T, E>(frames: T) -> Vec<Image>
 where
@@ -120,6 +358,16 @@ pub fn load_raster(bytes: &[u8]) -> Option<Vec<Image>> {
     }
 }
 
+/// Reads every layer of an OpenEXR file and shows the default one.
+pub fn load_exr(bytes: &[u8]) -> Option<(Vec<Image>, ExrLayers)> {
+    if image::guess_format(bytes).ok()? != ImageFormat::OpenExr {
+        return None;
+    }
+    let layers = ExrLayers::read(bytes)?;
+    let image = layers.image(layers.default_selection())?;
+    Some((vec![Image::new(image)], layers))
+}
+
 pub fn load_un_detectable_raster(bytes: &[u8]) -> Option<Vec<Image>> {
     match ImageReader::with_format(Cursor::new(&bytes), ImageFormat::Tga).decode() {
         Ok(image) => Some(vec![Image::new(image)]),
@@ -127,13 +375,13 @@ pub fn load_un_detectable_raster(bytes: &[u8]) -> Option<Vec<Image>> {
     }
 }
 
-pub fn load_svg(bytes: &[u8]) -> Option<Vec<Image>> {
-    let options = Options::default();
-
-    let tree = Tree::from_data(bytes, &options).ok()?;
-    let size = tree.size().to_int_size();
+/// Also returns the document so it can be rasterized again when zooming in or exporting.
+pub fn load_svg(bytes: &[u8], min_size: u32) -> Option<(Vec<Image>, Svg)> {
+    let svg = Svg::new(bytes)?;
+    let (width, height) = svg.size();
+    let size = IntSize::from_wh((width.ceil() as u32).max(1), (height.ceil() as u32).max(1))?;
 
-    let min_size = PREFERENCES.lock().unwrap().min_svg_size.max(100);
+    let min_size = min_size.max(100);
     let smaller_axis = size.width().min(size.height());
     let size = if smaller_axis < min_size {
         let scale_factor = min_size as f32 / smaller_axis as f32;
@@ -142,70 +390,75 @@ pub fn load_svg(bytes: &[u8]) -> Option<Vec<Image>> {
         size
     };
 
-    let transform = resvg::tiny_skia::Transform::from_scale(
-        size.width() as f32 / tree.size().width(),
-        size.height() as f32 / tree.size().height(),
-    );
-
-    let mut pix_map = resvg::tiny_skia::Pixmap::new(size.width(), size.height()).unwrap();
-    resvg::render(&tree, transform, &mut pix_map.as_mut());
-
-    let width = pix_map.width();
-    let height = pix_map.height();
-    let data = pix_map.take();
-    Some(vec![Image::from(ImageBuffer::<Rgba<u8>, _>::from_raw(
-        width, height, data,
-    )?)])
+    let image = svg.render(size.width(), size.height())?;
+    Some((vec![Image::new(image)], svg))
 }
 
-pub fn load_psd(bytes: &[u8]) -> Option<Vec<Image>> {
-    let psd = match Psd::from_bytes(bytes) {
-        Ok(psd) => psd,
-        Err(_) => return None,
-    };
-
-    let raw = psd.rgba();
-
-    Some(vec![Image::from(
-        ImageBuffer::<Rgba<u8>, _>::from_raw(psd.width(), psd.height(), raw).unwrap(),
-    )])
+/// Shows the merged image stored in the file and also returns the layers if there are any.
+pub fn load_psd(bytes: &[u8]) -> Option<(Vec<Image>, Option<PsdLayers>)> {
+    let psd = Psd::from_bytes(bytes).ok()?;
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(psd.width(), psd.height(), psd.rgba())?;
+    Some((vec![Image::from(image)], PsdLayers::new(psd)))
 }
 
-pub fn load_raw(bytes: &[u8]) -> Option<Vec<Image>> {
-    let Ok(raw) = rawloader::decode(&mut Cursor::new(bytes)) else {
-        return None;
-    };
-
-    let source = ImageSource::Raw(raw);
-    let Ok(mut pipeline) = Pipeline::new_from_source(source) else {
-        return None;
-    };
-
-    pipeline.run(None);
-    let Ok(image) = pipeline.output_16bit(None) else {
-        return None;
-    };
-
-    let image = ImageBuffer::<Rgb<u16>, Vec<u16>>::from_raw(
-        image.width as u32,
-        image.height as u32,
-        image.data,
-    )?;
-
-    let image = image::DynamicImage::ImageRgb16(image);
-    Some(vec![Image::new(image)])
+/// Also returns the decoded sensor data so the image can be developed again with other settings.
+pub fn load_raw(bytes: &[u8], path: Option<&Path>) -> Option<(Vec<Image>, RawFile)> {
+    let raw = RawFile::decode(bytes, path)?;
+    let image = raw.develop(&raw.settings)?;
+    Some((vec![Image::new(image)], raw))
 }
 
-pub fn load_jxl(bytes: &[u8]) -> Option<Vec<Image>> {
+/// Also returns the embedded icc profile since the image crate can't read it for this format.
+pub fn load_jxl(bytes: &[u8]) -> Option<(Vec<Image>, Option<Vec<u8>>)> {
     #[cfg(feature = "jxl")]
     {
-        use jpegxl_rs::image::ToDynamic;
+        use jpegxl_rs::decode::Pixels;
         let decoder = jpegxl_rs::decoder_builder()
             .unpremul_alpha(true)
+            .icc_profile(true)
             .build()
             .ok()?;
-        let image = decoder.decode_to_image(bytes).ok()??;
-        Some(vec![Image::new(image)])
+        let (metadata, pixels) = decoder.decode(bytes).ok()?;
+        let (width, height) = (metadata.width, metadata.height);
+        let channels = metadata.num_color_channels + metadata.has_alpha_channel as u32;
+        let pixels = match pixels {
+            Pixels::Float16(data) => Pixels::Float(data.into_iter().map(f32::from).collect()),
+            pixels => pixels,
+        };
+        let image = match (pixels, channels) {
+            (Pixels::Uint8(data), 1) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+            }
+            (Pixels::Uint8(data), 2) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+            }
+            (Pixels::Uint8(data), 3) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+            }
+            (Pixels::Uint8(data), 4) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+            }
+            (Pixels::Uint16(data), 1) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+            }
+            (Pixels::Uint16(data), 2) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+            }
+            (Pixels::Uint16(data), 3) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+            }
+            (Pixels::Uint16(data), 4) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+            }
+            (Pixels::Float(data), 3) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+            }
+            (Pixels::Float(data), 4) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+            }
+            _ => None,
+        }?;
+        Some((vec![Image::new(image)], metadata.icc_profile))
     }
 
     #[cfg(not(feature = "jxl"))]
@@ -215,30 +468,55 @@ pub fn load_jxl(bytes: &[u8]) -> Option<Vec<Image>> {
     }
 }
 
-pub fn load_heif(bytes: &[u8]) -> Option<Vec<Image>> {
+/// Decodes every top level image as a page and also returns the embedded icc profile
+/// of the primary image since the image crate can't read it for this format.
+pub fn load_heif(bytes: &[u8]) -> Option<(Vec<Image>, Option<Vec<u8>>)> {
     #[cfg(feature = "heif")]
     {
         use image::RgbaImage;
-        use libheif_rs::{ColorSpace, DecodingOptions, HeifContext, LibHeif, RgbChroma};
+        use libheif_rs::{ColorSpace, DecodingOptions, HeifContext, ItemId, LibHeif, RgbChroma};
         let lib_heif = LibHeif::new();
         let ctx = HeifContext::read_from_bytes(bytes).ok()?;
-        let handle = ctx.primary_image_handle().ok()?;
-        let image = lib_heif
-            .decode(
+        let primary = ctx.primary_image_handle().ok()?;
+
+        let mut ids: Vec<ItemId> = vec![0; ctx.number_of_top_level_images()];
+        let count = ctx.top_level_image_ids(&mut ids);
+        ids.truncate(count);
+        // The primary image is shown first no matter where it is stored.
+        ids.sort_by_key(|id| *id != primary.item_id());
+
+        let mut images = Vec::new();
+        for id in ids {
+            let Ok(handle) = ctx.image_handle(id) else {
+                continue;
+            };
+            let Ok(image) = lib_heif.decode(
                 &handle,
                 ColorSpace::Rgb(RgbChroma::Rgba),
                 DecodingOptions::new().map(|mut o| {
                     o.set_convert_hdr_to_8bit(true);
                     o
                 }),
-            )
-            .ok()?;
-        let planes = image.planes();
-        let interleaved_plane = planes.interleaved?;
-        let raw = interleaved_plane.data.to_vec();
-        let rgba_image =
-            RgbaImage::from_raw(interleaved_plane.width, interleaved_plane.height, raw)?;
-        Some(vec![Image::new(DynamicImage::ImageRgba8(rgba_image))])
+            ) else {
+                continue;
+            };
+            let planes = image.planes();
+            let Some(interleaved_plane) = planes.interleaved else {
+                continue;
+            };
+            let raw = interleaved_plane.data.to_vec();
+            if let Some(rgba_image) =
+                RgbaImage::from_raw(interleaved_plane.width, interleaved_plane.height, raw)
+            {
+                images.push(Image::new(DynamicImage::ImageRgba8(rgba_image)));
+            }
+        }
+        if images.is_empty() {
+            return None;
+        }
+
+        let icc = primary.color_profile_raw().map(|profile| profile.data);
+        Some((images, icc))
     }
 
     #[cfg(not(feature = "heif"))]
@@ -0,0 +1,456 @@
+use std::io::Cursor;
+
+use image::{ImageDecoder, ImageReader};
+
+const ORIENTATION: u16 = 0x0112;
+const EXIF_IFD: u16 = 0x8769;
+const INTEROP_IFD: u16 = 0xa005;
+
+/// Tags from the first ifd that describe the photo rather than the pixel layout.
+const DESCRIPTIVE_TAGS: [u16; 7] = [
+    0x010e, // ImageDescription
+    0x010f, // Make
+    0x0110, // Model
+    0x0131, // Software
+    0x0132, // DateTime
+    0x013b, // Artist
+    0x8298, // Copyright
+];
+
+/// The metadata blocks of the source file exactly as they were embedded,
+/// so they can be written back when saving.
+#[derive(Debug, Clone, Default)]
+pub struct RawMetadata {
+    /// Tiff structured exif without the `Exif\0\0` prefix. Orientation is always 1
+    /// because the orientation is applied to the pixels when loading.
+    pub exif: Option<Vec<u8>>,
+    pub icc: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+    /// How many times an animation is played, 0 is forever. `None` when the file does not say.
+    pub loop_count: Option<u32>,
+}
+
+impl RawMetadata {
+    /// Reads whatever blocks the decoder for the detected format exposes.
+    pub fn read(bytes: &[u8]) -> Self {
+        let Ok(reader) = ImageReader::new(Cursor::new(bytes)).with_guessed_format() else {
+            return Self::default();
+        };
+        let Ok(mut decoder) = reader.into_decoder() else {
+            return Self::default();
+        };
+
+        let mut exif = decoder.exif_metadata().ok().flatten().map(|mut exif| {
+            if exif.starts_with(b"Exif\0\0") {
+                exif.drain(..6);
+            }
+            exif
+        });
+        if let Some(exif) = exif.as_mut() {
+            reset_orientation(exif);
+        }
+
+        Self {
+            exif,
+            icc: decoder.icc_profile().ok().flatten(),
+            xmp: decoder.xmp_metadata().ok().flatten(),
+            loop_count: read_loop_count(bytes),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none() && self.xmp.is_none()
+    }
+}
+
+/// Reads the loop count of an animated png, gif or webp.
+fn read_loop_count(bytes: &[u8]) -> Option<u32> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut offset = 8;
+        while let Some(header) = bytes.get(offset..offset + 8) {
+            let len = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+            match &header[4..] {
+                // num_frames followed by num_plays
+                b"acTL" => {
+                    let plays = bytes.get(offset + 12..offset + 16)?;
+                    return Some(u32::from_be_bytes(plays.try_into().ok()?));
+                }
+                b"IDAT" | b"IEND" => return None,
+                _ => offset = offset.checked_add(len + 12)?,
+            }
+        }
+        None
+    } else if bytes.starts_with(b"GIF8") {
+        // The netscape extension stores the repeat count, which excludes the first play.
+        // Without it the animation is played once.
+        let block = b"\x21\xff\x0bNETSCAPE2.0\x03\x01";
+        let Some(start) = bytes
+            .windows(block.len())
+            .position(|window| window == block)
+        else {
+            return Some(1);
+        };
+        let start = start + block.len();
+        let repeat = u16::from_le_bytes(bytes.get(start..start + 2)?.try_into().ok()?);
+        Some(if repeat == 0 { 0 } else { repeat as u32 + 1 })
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        let mut offset = 12;
+        while let Some(header) = bytes.get(offset..offset + 8) {
+            let len = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+            if &header[..4] == b"ANIM" {
+                // background color followed by the loop count
+                let count = bytes.get(offset + 12..offset + 14)?;
+                return Some(u16::from_le_bytes(count.try_into().ok()?) as u32);
+            }
+            // chunks are padded to an even size
+            offset = offset.checked_add(8 + len + (len & 1))?;
+        }
+        None
+    } else {
+        None
+    }
+}
+
+/// A single ifd entry with its value bytes in the byte order of the exif block.
+pub struct ExifEntry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub data: Vec<u8>,
+}
+
+/// The entries of an exif block that can be copied into another tiff file.
+pub struct ExifEntries {
+    pub big_endian: bool,
+    /// Descriptive tags of the first ifd such as Make, Model and Copyright.
+    pub image: Vec<ExifEntry>,
+    /// The exif ifd without nested ifd pointers.
+    pub exif: Vec<ExifEntry>,
+}
+
+impl ExifEntries {
+    pub fn parse(exif: &[u8]) -> Option<Self> {
+        let tiff = Tiff::new(exif)?;
+        let ifd0 = tiff.entries(tiff.ifd0()?)?;
+
+        let image = ifd0
+            .iter()
+            .filter(|entry| DESCRIPTIVE_TAGS.contains(&entry.tag))
+            .filter_map(|entry| tiff.entry(entry))
+            .collect();
+
+        let exif = ifd0
+            .iter()
+            .find(|entry| entry.tag == EXIF_IFD)
+            .and_then(|entry| tiff.entries(tiff.u32(entry.offset + 8)? as usize))
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.tag != INTEROP_IFD)
+                    .filter_map(|entry| tiff.entry(entry))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            big_endian: tiff.big_endian,
+            image,
+            exif,
+        })
+    }
+}
+
+/// Sets the orientation in the first ifd to 1 (no transform) in place.
+pub fn reset_orientation(exif: &mut [u8]) {
+    let Some(tiff) = Tiff::new(exif) else {
+        return;
+    };
+    let Some(entries) = tiff.ifd0().and_then(|ifd0| tiff.entries(ifd0)) else {
+        return;
+    };
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.tag == ORIENTATION && entry.field_type == 3 && entry.count == 1)
+    else {
+        return;
+    };
+
+    let value = if tiff.big_endian {
+        1u16.to_be_bytes()
+    } else {
+        1u16.to_le_bytes()
+    };
+    let offset = entry.offset + 8;
+    exif[offset..offset + 2].copy_from_slice(&value);
+}
+
+struct RawEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    /// Position of the 12 byte entry in the block.
+    offset: usize,
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..4)? {
+            [b'I', b'I', 42, 0] => false,
+            [b'M', b'M', 0, 42] => true,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn ifd0(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    fn entries(&self, ifd: usize) -> Option<Vec<RawEntry>> {
+        let count = self.u16(ifd)? as usize;
+        (0..count)
+            .map(|i| {
+                let offset = ifd + 2 + i * 12;
+                Some(RawEntry {
+                    tag: self.u16(offset)?,
+                    field_type: self.u16(offset + 2)?,
+                    count: self.u32(offset + 4)?,
+                    offset,
+                })
+            })
+            .collect()
+    }
+
+    fn entry(&self, entry: &RawEntry) -> Option<ExifEntry> {
+        let size = match entry.field_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let len = size * entry.count as usize;
+        let start = if len <= 4 {
+            entry.offset + 8
+        } else {
+            self.u32(entry.offset + 8)? as usize
+        };
+
+        Some(ExifEntry {
+            tag: entry.tag,
+            field_type: entry.field_type,
+            data: self.data.get(start..start.checked_add(len)?)?.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+    use crate::image_io::{
+        Image,
+        save::{self, SaveOptions},
+    };
+
+    const MAKE: u16 = 0x010f;
+    const EXPOSURE_TIME: u16 = 0x829a;
+
+    /// An exif block with Make, Orientation 6 and an exif ifd holding ExposureTime 1/125
+    /// and an interop pointer.
+    fn exif_block(big_endian: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        let u16 = |data: &mut Vec<u8>, value: u16| {
+            data.extend(if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            })
+        };
+        let u32 = |data: &mut Vec<u8>, value: u32| {
+            data.extend(if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            })
+        };
+
+        data.extend(if big_endian { b"MM\0*" } else { b"II*\0" });
+        u32(&mut data, 8);
+
+        // ifd0 at 8, 3 entries end at 50
+        u16(&mut data, 3);
+        u16(&mut data, MAKE);
+        u16(&mut data, 2);
+        u32(&mut data, 4);
+        data.extend(b"Cam\0");
+        u16(&mut data, ORIENTATION);
+        u16(&mut data, 3);
+        u32(&mut data, 1);
+        u16(&mut data, 6);
+        u16(&mut data, 0);
+        u16(&mut data, EXIF_IFD);
+        u16(&mut data, 4);
+        u32(&mut data, 1);
+        u32(&mut data, 50);
+        u32(&mut data, 0);
+
+        // exif ifd at 50, 2 entries end at 80
+        u16(&mut data, 2);
+        u16(&mut data, EXPOSURE_TIME);
+        u16(&mut data, 5);
+        u32(&mut data, 1);
+        u32(&mut data, 80);
+        u16(&mut data, INTEROP_IFD);
+        u16(&mut data, 4);
+        u32(&mut data, 1);
+        u32(&mut data, 0);
+        u32(&mut data, 0);
+
+        u32(&mut data, 1);
+        u32(&mut data, 125);
+        data
+    }
+
+    fn value(entries: &[ExifEntry], tag: u16) -> Option<&ExifEntry> {
+        entries.iter().find(|entry| entry.tag == tag)
+    }
+
+    fn rational(entry: &ExifEntry, big_endian: bool) -> (u32, u32) {
+        let read = |bytes: &[u8]| {
+            let bytes = bytes.try_into().unwrap();
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+        (read(&entry.data[..4]), read(&entry.data[4..]))
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("simp-metadata-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn parses_both_byte_orders() {
+        for big_endian in [false, true] {
+            let entries = ExifEntries::parse(&exif_block(big_endian)).unwrap();
+            assert_eq!(entries.big_endian, big_endian);
+            assert_eq!(entries.image.len(), 1);
+            assert_eq!(value(&entries.image, MAKE).unwrap().data, b"Cam\0");
+            // Nested ifd pointers are not copied.
+            assert_eq!(entries.exif.len(), 1);
+            let exposure = value(&entries.exif, EXPOSURE_TIME).unwrap();
+            assert_eq!(rational(exposure, big_endian), (1, 125));
+        }
+    }
+
+    #[test]
+    fn resets_orientation() {
+        for big_endian in [false, true] {
+            let mut exif = exif_block(big_endian);
+            reset_orientation(&mut exif);
+            let tiff = Tiff::new(&exif).unwrap();
+            let entries = tiff.entries(tiff.ifd0().unwrap()).unwrap();
+            let orientation = entries
+                .iter()
+                .find(|entry| entry.tag == ORIENTATION)
+                .unwrap();
+            assert_eq!(tiff.u16(orientation.offset + 8), Some(1));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_blocks() {
+        let exif = exif_block(false);
+        assert!(ExifEntries::parse(&exif[..4]).is_none());
+        for len in [8, 20, 50, 60, 84] {
+            // Entries pointing past the end are skipped instead of panicking.
+            if let Some(entries) = ExifEntries::parse(&exif[..len]) {
+                assert!(value(&entries.exif, EXPOSURE_TIME).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_through_jpeg_and_png() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(8, 8));
+        for (name, big_endian) in [("exif.jpg", false), ("exif.png", true)] {
+            let metadata = RawMetadata {
+                exif: Some(exif_block(big_endian)),
+                icc: None,
+                xmp: Some(b"<x:xmpmeta/>".to_vec()),
+                loop_count: None,
+            };
+            let path = temp_path(name);
+            save::save(
+                path.clone(),
+                vec![Image::new(image.clone())],
+                &metadata,
+                SaveOptions::Default,
+            )
+            .unwrap();
+            let bytes = fs::read(&path).unwrap();
+            let _ = fs::remove_file(&path);
+
+            let read = RawMetadata::read(&bytes);
+            let mut expected = exif_block(big_endian);
+            reset_orientation(&mut expected);
+            assert_eq!(read.exif, Some(expected), "{name}");
+            assert_eq!(read.xmp, metadata.xmp, "{name}");
+        }
+    }
+
+    #[test]
+    fn round_trips_through_tiff() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(8, 8));
+        for big_endian in [false, true] {
+            let metadata = RawMetadata {
+                exif: Some(exif_block(big_endian)),
+                ..Default::default()
+            };
+            let path = temp_path("exif.tiff");
+            save::save(
+                path.clone(),
+                vec![Image::new(image.clone())],
+                &metadata,
+                SaveOptions::Default,
+            )
+            .unwrap();
+            let bytes = fs::read(&path).unwrap();
+            let _ = fs::remove_file(&path);
+
+            // The entries are written into the ifd of the image itself.
+            let entries = ExifEntries::parse(&bytes).unwrap();
+            assert_eq!(value(&entries.image, MAKE).unwrap().data, b"Cam\0");
+            let exposure = value(&entries.exif, EXPOSURE_TIME).unwrap();
+            assert_eq!(rational(exposure, entries.big_endian), (1, 125));
+        }
+    }
+}
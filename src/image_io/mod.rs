@@ -1,2 +1,161 @@
+use std::{sync::Arc, time::Duration};
+
+use image::{Delay, DynamicImage, Frame, ImageBuffer, Rgba};
+
+pub use self::metadata::RawMetadata;
+use self::{exr::ExrLayers, photoshop::PsdLayers, raw::RawFile, svg::Svg};
+
+pub mod exr;
+pub mod extensions;
+mod imageops;
 pub mod load;
+pub mod metadata;
+pub mod pages;
+pub mod photoshop;
+pub mod raw;
 pub mod save;
+pub mod svg;
+pub mod thumbnail;
+pub mod tonemap;
+pub mod transform;
+
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub image: DynamicImage,
+    pub delay: Duration,
+}
+
+impl Image {
+    pub fn new(image: DynamicImage) -> Self {
+        Image {
+            image,
+            delay: Duration::default(),
+        }
+    }
+
+    pub fn with_delay(image: DynamicImage, delay: Duration) -> Self {
+        Image { image, delay }
+    }
+
+    pub fn buffer(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut DynamicImage {
+        &mut self.image
+    }
+}
+
+impl From<ImageBuffer<Rgba<u8>, Vec<u8>>> for Image {
+    fn from(buffer: ImageBuffer<Rgba<u8>, Vec<u8>>) -> Self {
+        Image {
+            image: DynamicImage::ImageRgba8(buffer),
+            delay: Duration::default(),
+        }
+    }
+}
+
+impl From<Frame> for Image {
+    fn from(frame: Frame) -> Self {
+        let (num, deno) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_millis((num / deno) as u64);
+        let buffer = frame.into_buffer();
+        Image {
+            image: DynamicImage::ImageRgba8(buffer),
+            delay,
+        }
+    }
+}
+
+impl From<Image> for Frame {
+    fn from(image: Image) -> Frame {
+        let duration = image.delay;
+        let frame = image.image.to_rgba8();
+        Frame::from_parts(frame, 0, 0, Delay::from_saturating_duration(duration))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageData {
+    pub frames: Vec<Image>,
+    pub metadata: Vec<(String, String)>,
+    pub raw_metadata: RawMetadata,
+    /// All layers of an OpenEXR file, `frames` holds the one being displayed.
+    pub exr: Option<Arc<ExrLayers>>,
+    /// Every page of a multi-page TIFF, ICO, HEIF or DDS file, `frames` holds the one
+    /// being displayed. Pages are separate images, not animation frames.
+    pub pages: Option<Arc<[Image]>>,
+    /// The layers of a Photoshop file, `frames` holds their composite.
+    pub psd: Option<Arc<PsdLayers>>,
+    /// The sensor data of a camera raw file, `frames` holds it developed.
+    pub raw: Option<Arc<RawFile>>,
+    /// Only the embedded preview of a camera raw file was decoded.
+    pub preview: bool,
+    /// A copy scaled down to the screen, kept by the cache when the full image did not fit.
+    pub downscaled: bool,
+    /// The document of an svg, `frames` holds it rasterized.
+    pub svg: Option<Arc<Svg>>,
+}
+
+impl ImageData {
+    pub fn new(frames: Vec<Image>, metadata: Vec<(String, String)>) -> Self {
+        Self {
+            frames,
+            metadata,
+            raw_metadata: RawMetadata::default(),
+            exr: None,
+            pages: None,
+            psd: None,
+            raw: None,
+            preview: false,
+            downscaled: false,
+            svg: None,
+        }
+    }
+
+    /// Whether the full image still has to be loaded.
+    pub fn is_preview(&self) -> bool {
+        self.preview || self.downscaled
+    }
+
+    /// Bytes taken by the decoded image. Every frame and page also costs a little besides
+    /// its pixels.
+    pub fn memory_size(&self) -> usize {
+        let images = |images: &[Image]| -> usize {
+            images
+                .iter()
+                .map(|image| image.buffer().as_bytes().len() + size_of::<Image>())
+                .sum()
+        };
+        let metadata: usize = self
+            .metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        size_of::<Self>()
+            + images(&self.frames)
+            + self.pages.as_deref().map_or(0, images)
+            + self.exr.as_ref().map_or(0, |exr| exr.memory_size())
+            + self.raw.as_ref().map_or(0, |raw| raw.memory_size())
+            + self.psd.as_ref().map_or(0, |psd| psd.memory_size())
+            + self.svg.as_ref().map_or(0, |svg| svg.memory_size())
+            + metadata
+    }
+}
+
+impl From<Vec<Image>> for ImageData {
+    fn from(frames: Vec<Image>) -> Self {
+        Self {
+            frames,
+            metadata: Vec::new(),
+            raw_metadata: RawMetadata::default(),
+            exr: None,
+            pages: None,
+            psd: None,
+            raw: None,
+            preview: false,
+            downscaled: false,
+            svg: None,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::{cmp::Reverse, io::Cursor};
+
+use image::{DynamicImage, ImageBuffer, ImageFormat};
+use tiff::{
+    ColorType,
+    decoder::{Decoder, DecodingResult},
+};
+
+use super::Image;
+
+/// Decodes containers that hold several independent images rather than an animation.
+/// Returns `None` unless there is more than one page so single page files keep going
+/// through the regular decoders.
+pub fn load_pages(bytes: &[u8]) -> Option<Vec<Image>> {
+    let pages = match image::guess_format(bytes).ok()? {
+        ImageFormat::Tiff => tiff_pages(bytes)?,
+        ImageFormat::Ico => ico_pages(bytes)?,
+        ImageFormat::Dds => dds_pages(bytes)?,
+        _ => return None,
+    };
+    (pages.len() > 1).then_some(pages)
+}
+
+/// Every directory of a multi-page TIFF. Pages with a layout the image crate has no
+/// buffer type for (CMYK, palette, signed samples) are skipped.
+fn tiff_pages(bytes: &[u8]) -> Option<Vec<Image>> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).ok()?;
+    if !decoder.more_images() {
+        return None;
+    }
+
+    let mut pages = Vec::new();
+    loop {
+        if let Some(image) = tiff_page(&mut decoder) {
+            pages.push(Image::new(image));
+        }
+        if !decoder.more_images() || decoder.next_image().is_err() {
+            break;
+        }
+    }
+    Some(pages)
+}
+
+fn tiff_page(decoder: &mut Decoder<Cursor<&[u8]>>) -> Option<DynamicImage> {
+    let (width, height) = decoder.dimensions().ok()?;
+    let color_type = decoder.colortype().ok()?;
+    match (decoder.read_image().ok()?, color_type) {
+        (DecodingResult::U8(data), ColorType::Gray(8)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (DecodingResult::U8(data), ColorType::GrayA(8)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (DecodingResult::U8(data), ColorType::RGB(8)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (DecodingResult::U8(data), ColorType::RGBA(8)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (DecodingResult::U16(data), ColorType::Gray(16)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (DecodingResult::U16(data), ColorType::GrayA(16)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (DecodingResult::U16(data), ColorType::RGB(16)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (DecodingResult::U16(data), ColorType::RGBA(16)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        (DecodingResult::F32(data), ColorType::RGB(32)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+        }
+        (DecodingResult::F32(data), ColorType::RGBA(32)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+        }
+        _ => None,
+    }
+}
+
+/// Every size stored in an ICO or CUR file, decoded by handing the image crate a copy
+/// of the file that only lists that entry.
+fn ico_pages(bytes: &[u8]) -> Option<Vec<Image>> {
+    const HEADER: usize = 6;
+    const ENTRY: usize = 16;
+
+    let count = u16::from_le_bytes(bytes.get(4..6)?.try_into().ok()?) as usize;
+    if count < 2 {
+        return None;
+    }
+
+    let mut pages = Vec::new();
+    for index in 0..count {
+        let entry = bytes.get(HEADER + index * ENTRY..HEADER + (index + 1) * ENTRY)?;
+        let size = u32::from_le_bytes(entry[8..12].try_into().ok()?) as usize;
+        let offset = u32::from_le_bytes(entry[12..16].try_into().ok()?) as usize;
+        let Some(data) = bytes.get(offset..offset.saturating_add(size)) else {
+            continue;
+        };
+
+        let mut single = Vec::with_capacity(HEADER + ENTRY + size);
+        single.extend_from_slice(&bytes[..4]);
+        single.extend_from_slice(&1u16.to_le_bytes());
+        single.extend_from_slice(&entry[..12]);
+        single.extend_from_slice(&((HEADER + ENTRY) as u32).to_le_bytes());
+        single.extend_from_slice(data);
+
+        if let Ok(image) = image::load_from_memory_with_format(&single, ImageFormat::Ico) {
+            pages.push(Image::new(image));
+        }
+    }
+    // Largest first so the icon opens at the same size as before.
+    pages.sort_by_key(|page| Reverse(page.buffer().width() * page.buffer().height()));
+    Some(pages)
+}
+
+/// Every mip level of every array slice or cube face in a DDS file. Only the block
+/// compressed formats the image crate can decode are supported. The header is untrusted,
+/// sizes that overflow make it `None`.
+fn dds_pages(bytes: &[u8]) -> Option<Vec<Image>> {
+    const HEADER: usize = 128;
+    const DX10_HEADER: usize = 20;
+    const MIPMAP_COUNT_FLAG: u32 = 0x20000;
+    const CUBEMAP_CAPS: u32 = 0x200;
+    const CUBEMAP_MISC: u32 = 0x4;
+
+    let read = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    let flags = read(8)?;
+    let height = read(12)?;
+    let width = read(16)?;
+    // There are never more levels than bits in the width.
+    let mip_count = if flags & MIPMAP_COUNT_FLAG != 0 {
+        read(28)?.clamp(1, 32)
+    } else {
+        1
+    };
+    let four_cc = bytes.get(84..88)?;
+    let caps2 = read(112)?;
+
+    let dx10 = four_cc == b"DX10";
+    let (header_size, block_size, slices) = if dx10 {
+        // BC1 is 70 to 72, BC2 and BC3 are 73 to 78.
+        let block_size = match read(HEADER)? {
+            70..=72 => 8,
+            73..=78 => 16,
+            _ => return None,
+        };
+        let faces = if read(HEADER + 8)? & CUBEMAP_MISC != 0 {
+            6
+        } else {
+            1
+        };
+        (
+            HEADER + DX10_HEADER,
+            block_size,
+            read(HEADER + 12)?.max(1).checked_mul(faces)?,
+        )
+    } else {
+        let block_size = match four_cc {
+            b"DXT1" => 8,
+            b"DXT2" | b"DXT3" | b"DXT4" | b"DXT5" => 16,
+            _ => return None,
+        };
+        let faces = if caps2 & CUBEMAP_CAPS != 0 {
+            // Partial cube maps are rare enough to not be worth counting the face flags.
+            6
+        } else {
+            1
+        };
+        (HEADER, block_size, faces)
+    };
+
+    if mip_count.checked_mul(slices)? < 2 {
+        return None;
+    }
+
+    let mut pages = Vec::new();
+    let mut offset = header_size;
+    for _ in 0..slices {
+        for level in 0..mip_count {
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            // The decoder wants whole blocks so small mips are decoded padded and cropped.
+            let padded_width = level_width.div_ceil(4).checked_mul(4)?;
+            let padded_height = level_height.div_ceil(4).checked_mul(4)?;
+            let size = ((padded_width / 4) as usize)
+                .checked_mul((padded_height / 4) as usize)?
+                .checked_mul(block_size)?;
+            let end = offset.checked_add(size)?;
+            let data = bytes.get(offset..end)?;
+            offset = end;
+
+            let mut single = bytes[..header_size].to_vec();
+            single[8..12].copy_from_slice(&(flags & !MIPMAP_COUNT_FLAG).to_le_bytes());
+            single[12..16].copy_from_slice(&padded_height.to_le_bytes());
+            single[16..20].copy_from_slice(&padded_width.to_le_bytes());
+            single[28..32].copy_from_slice(&1u32.to_le_bytes());
+            single[112..116].copy_from_slice(&0u32.to_le_bytes());
+            if dx10 {
+                single[HEADER + 8..HEADER + 12].copy_from_slice(&0u32.to_le_bytes());
+                single[HEADER + 12..HEADER + 16].copy_from_slice(&1u32.to_le_bytes());
+            }
+            single.extend_from_slice(data);
+
+            if let Ok(image) = image::load_from_memory_with_format(&single, ImageFormat::Dds) {
+                pages.push(Image::new(image.crop_imm(0, 0, level_width, level_height)));
+            }
+        }
+    }
+    Some(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, RgbaImage, codecs::png::PngEncoder};
+    use tiff::encoder::{TiffEncoder, colortype};
+
+    use super::*;
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::new(size, size))
+            .write_with_encoder(PngEncoder::new(&mut bytes))
+            .unwrap();
+        bytes
+    }
+
+    /// An icon with a png entry for every size, in the order given.
+    fn ico(sizes: &[u32]) -> Vec<u8> {
+        let images: Vec<Vec<u8>> = sizes.iter().map(|&size| png(size)).collect();
+        let mut bytes = vec![0, 0, 1, 0];
+        bytes.extend((sizes.len() as u16).to_le_bytes());
+        let mut offset = 6 + 16 * sizes.len();
+        for (&size, image) in sizes.iter().zip(&images) {
+            bytes.extend([size as u8, size as u8, 0, 0]);
+            bytes.extend(1u16.to_le_bytes());
+            bytes.extend(32u16.to_le_bytes());
+            bytes.extend((image.len() as u32).to_le_bytes());
+            bytes.extend((offset as u32).to_le_bytes());
+            offset += image.len();
+        }
+        for image in images {
+            bytes.extend(image);
+        }
+        bytes
+    }
+
+    /// A DXT1 header with `mip_count` levels, the block data is left to the caller.
+    fn dds_header(width: u32, height: u32, mip_count: u32) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        let mut write = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        write(4, 124);
+        write(8, 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000);
+        write(12, height);
+        write(16, width);
+        write(28, mip_count);
+        write(76, 32);
+        write(80, 0x4);
+        write(108, 0x1000 | 0x400000 | 0x8);
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes
+    }
+
+    fn sizes(pages: &[Image]) -> Vec<(u32, u32)> {
+        pages
+            .iter()
+            .map(|page| (page.buffer().width(), page.buffer().height()))
+            .collect()
+    }
+
+    #[test]
+    fn tiff_pages_keep_their_order_and_size() {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        encoder
+            .write_image::<colortype::RGB8>(4, 2, &[10; 4 * 2 * 3])
+            .unwrap();
+        encoder
+            .write_image::<colortype::Gray16>(3, 5, &[1000; 3 * 5])
+            .unwrap();
+        let bytes = bytes.into_inner();
+
+        let pages = load_pages(&bytes).unwrap();
+        assert_eq!(sizes(&pages), [(4, 2), (3, 5)]);
+        assert!(matches!(pages[0].buffer(), DynamicImage::ImageRgb8(_)));
+        assert!(matches!(pages[1].buffer(), DynamicImage::ImageLuma16(_)));
+    }
+
+    #[test]
+    fn single_page_tiff_is_not_paged() {
+        let mut bytes = Cursor::new(Vec::new());
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<colortype::Gray8>(2, 2, &[0; 4])
+            .unwrap();
+        assert!(load_pages(&bytes.into_inner()).is_none());
+    }
+
+    #[test]
+    fn ico_pages_are_largest_first() {
+        let pages = load_pages(&ico(&[16, 48, 32])).unwrap();
+        assert_eq!(sizes(&pages), [(48, 48), (32, 32), (16, 16)]);
+    }
+
+    #[test]
+    fn ico_entries_outside_the_file_are_skipped() {
+        let mut bytes = ico(&[16, 32, 24]);
+        // Point the second entry past the end.
+        bytes[6 + 16 + 12..6 + 16 + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let pages = load_pages(&bytes).unwrap();
+        assert_eq!(sizes(&pages), [(24, 24), (16, 16)]);
+    }
+
+    #[test]
+    fn dds_mip_levels_are_pages() {
+        // 8x8 is four blocks, 4x4 one and 2x2 is padded to one.
+        let mut bytes = dds_header(8, 8, 3);
+        bytes.extend([0; (4 + 1 + 1) * 8]);
+        let pages = load_pages(&bytes).unwrap();
+        assert_eq!(sizes(&pages), [(8, 8), (4, 4), (2, 2)]);
+    }
+
+    #[test]
+    fn dds_with_missing_data_is_rejected() {
+        let mut bytes = dds_header(8, 8, 2);
+        bytes.extend([0; 4 * 8]);
+        assert!(load_pages(&bytes).is_none());
+    }
+
+    #[test]
+    fn dds_header_overflows_are_rejected() {
+        let bytes = dds_header(u32::MAX, u32::MAX, u32::MAX);
+        assert!(load_pages(&bytes).is_none());
+
+        // A DX10 array with more slices than fit in a u32 once cube faces are counted.
+        let mut bytes = dds_header(4, 4, 1);
+        bytes[84..88].copy_from_slice(b"DX10");
+        bytes.extend(71u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(0x4u32.to_le_bytes());
+        bytes.extend(u32::MAX.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        assert!(load_pages(&bytes).is_none());
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use psd::Psd;
+
+/// The layer structure of a Photoshop file so layers can be hidden, shown and
+/// exported one by one instead of only showing the merged image stored in the file.
+#[derive(Debug)]
+pub struct PsdLayers {
+    psd: Psd,
+    /// Top most layer first.
+    pub layers: Vec<PsdLayer>,
+    pub groups: BTreeMap<u32, PsdGroup>,
+}
+
+#[derive(Debug)]
+pub struct PsdLayer {
+    pub name: String,
+    /// Id of the group the layer is in.
+    pub group: Option<u32>,
+    pub visible: bool,
+    pub opacity: u8,
+    pub blend_mode: BlendMode,
+}
+
+#[derive(Debug)]
+pub struct PsdGroup {
+    pub name: String,
+    pub parent: Option<u32>,
+    pub visible: bool,
+}
+
+/// Which layers and groups are shown, indexed like [`PsdLayers::layers`] and by group id.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PsdVisibility {
+    pub layers: Vec<bool>,
+    pub groups: BTreeMap<u32, bool>,
+}
+
+/// The separable blend modes, anything else is drawn as normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Darken,
+    Multiply,
+    ColorBurn,
+    LinearBurn,
+    Lighten,
+    Screen,
+    ColorDodge,
+    LinearDodge,
+    Overlay,
+    SoftLight,
+    HardLight,
+    Difference,
+    Exclusion,
+    Subtract,
+    Divide,
+}
+
+impl BlendMode {
+    /// The psd crate does not export its enum so it is matched by discriminant.
+    fn from_psd(mode: u8) -> Self {
+        match mode {
+            3 => Self::Darken,
+            4 => Self::Multiply,
+            5 => Self::ColorBurn,
+            6 => Self::LinearBurn,
+            8 => Self::Lighten,
+            9 => Self::Screen,
+            10 => Self::ColorDodge,
+            11 => Self::LinearDodge,
+            13 => Self::Overlay,
+            14 => Self::SoftLight,
+            15 => Self::HardLight,
+            20 => Self::Difference,
+            21 => Self::Exclusion,
+            22 => Self::Subtract,
+            23 => Self::Divide,
+            _ => Self::Normal,
+        }
+    }
+
+    // https://www.w3.org/TR/compositing-1/#blending
+    fn blend(self, backdrop: f32, source: f32) -> f32 {
+        let (b, s) = (backdrop, source);
+        match self {
+            Self::Normal => s,
+            Self::Darken => b.min(s),
+            Self::Multiply => b * s,
+            Self::ColorBurn => {
+                if b >= 1.0 {
+                    1.0
+                } else if s <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / s).min(1.0)
+                }
+            }
+            Self::LinearBurn => (b + s - 1.0).max(0.0),
+            Self::Lighten => b.max(s),
+            Self::Screen => b + s - b * s,
+            Self::ColorDodge => {
+                if b <= 0.0 {
+                    0.0
+                } else if s >= 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - s)).min(1.0)
+                }
+            }
+            Self::LinearDodge => (b + s).min(1.0),
+            Self::Overlay => Self::HardLight.blend(s, b),
+            Self::SoftLight => {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 {
+                        ((16.0 * b - 12.0) * b + 4.0) * b
+                    } else {
+                        b.sqrt()
+                    };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            }
+            Self::HardLight => {
+                if s <= 0.5 {
+                    Self::Multiply.blend(b, 2.0 * s)
+                } else {
+                    Self::Screen.blend(b, 2.0 * s - 1.0)
+                }
+            }
+            Self::Difference => (b - s).abs(),
+            Self::Exclusion => b + s - 2.0 * b * s,
+            Self::Subtract => (b - s).max(0.0),
+            Self::Divide => {
+                if b <= 0.0 {
+                    0.0
+                } else if s <= 0.0 {
+                    1.0
+                } else {
+                    (b / s).min(1.0)
+                }
+            }
+        }
+    }
+}
+
+impl PsdLayers {
+    /// Returns `None` for files without a layer section, they only have the merged image.
+    pub fn new(psd: Psd) -> Option<Self> {
+        if psd.layers().is_empty() {
+            return None;
+        }
+
+        // The psd crate reads the "hidden" flag as visible, so both are inverted here.
+        let layers = psd
+            .layers()
+            .iter()
+            .map(|layer| PsdLayer {
+                name: layer.name().to_string(),
+                group: layer.parent_id(),
+                visible: !layer.visible(),
+                opacity: layer.opacity(),
+                blend_mode: BlendMode::from_psd(layer.blend_mode() as u8),
+            })
+            .collect();
+        let groups = psd
+            .groups()
+            .iter()
+            .map(|(id, group)| {
+                let group = PsdGroup {
+                    name: group.name().to_string(),
+                    parent: group.parent_id(),
+                    visible: !group.visible(),
+                };
+                (*id, group)
+            })
+            .collect();
+
+        Some(Self {
+            psd,
+            layers,
+            groups,
+        })
+    }
+
+    /// Bytes taken by the layers at most, the crate may keep their channels compressed.
+    pub fn memory_size(&self) -> usize {
+        let merged = self.psd.width() as usize * self.psd.height() as usize * 4;
+        let layers: usize = self
+            .psd
+            .layers()
+            .iter()
+            .map(|layer| layer.width() as usize * layer.height() as usize * 4)
+            .sum();
+        merged + layers
+    }
+
+    pub fn default_visibility(&self) -> PsdVisibility {
+        PsdVisibility {
+            layers: self.layers.iter().map(|layer| layer.visible).collect(),
+            groups: self
+                .groups
+                .iter()
+                .map(|(id, group)| (*id, group.visible))
+                .collect(),
+        }
+    }
+
+    /// The groups a layer is nested in, innermost first.
+    pub fn ancestors(&self, group: Option<u32>) -> Vec<u32> {
+        let mut ancestors = Vec::new();
+        let mut current = group;
+        while let Some(id) = current {
+            // Guards against broken files where a group ends up being its own parent.
+            if ancestors.contains(&id) {
+                break;
+            }
+            ancestors.push(id);
+            current = self.groups.get(&id).and_then(|group| group.parent);
+        }
+        ancestors
+    }
+
+    fn is_shown(&self, index: usize, visibility: &PsdVisibility) -> bool {
+        visibility.layers.get(index).copied().unwrap_or(false)
+            && self
+                .ancestors(self.layers[index].group)
+                .iter()
+                .all(|id| visibility.groups.get(id).copied().unwrap_or(true))
+    }
+
+    /// Blends the shown layers bottom up. Layer effects, masks and adjustment layers are
+    /// not supported so this can differ from the merged image stored in the file.
+    pub fn composite(&self, visibility: &PsdVisibility) -> Option<DynamicImage> {
+        let (width, height) = (self.psd.width(), self.psd.height());
+        let mut canvas = vec![0.0f32; (width * height * 4) as usize];
+
+        for (index, layer) in self.layers.iter().enumerate().rev() {
+            if !self.is_shown(index, visibility) || layer.opacity == 0 {
+                continue;
+            }
+            let rgba = self.psd.layer_by_idx(index).rgba();
+            let opacity = layer.opacity as f32 / 255.0;
+
+            for (backdrop, source) in canvas.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
+                let source_alpha = source[3] as f32 / 255.0 * opacity;
+                if source_alpha <= 0.0 {
+                    continue;
+                }
+                let backdrop_alpha = backdrop[3];
+                let alpha = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+                for channel in 0..3 {
+                    let s = source[channel] as f32 / 255.0;
+                    let b = backdrop[channel];
+                    let blended =
+                        (1.0 - backdrop_alpha) * s + backdrop_alpha * layer.blend_mode.blend(b, s);
+                    backdrop[channel] = (source_alpha * blended
+                        + backdrop_alpha * b * (1.0 - source_alpha))
+                        / alpha;
+                }
+                backdrop[3] = alpha;
+            }
+        }
+
+        let data = canvas
+            .into_iter()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect();
+        ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+    }
+
+    /// A single layer cropped to its bounds, ignoring its visibility and opacity.
+    pub fn layer_image(&self, index: usize) -> Option<DynamicImage> {
+        if index >= self.layers.len() {
+            return None;
+        }
+        let layer = self.psd.layer_by_idx(index);
+        let (width, height) = (self.psd.width(), self.psd.height());
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, layer.rgba())
+            .map(DynamicImage::ImageRgba8)?;
+
+        let left = layer.layer_left().clamp(0, width as i32) as u32;
+        let top = layer.layer_top().clamp(0, height as i32) as u32;
+        let right = (layer.layer_left() + layer.width() as i32).clamp(0, width as i32) as u32;
+        let bottom = (layer.layer_top() + layer.height() as i32).clamp(0, height as i32) as u32;
+        if right <= left || bottom <= top {
+            return Some(image);
+        }
+        Some(image.crop_imm(left, top, right - left, bottom - top))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A layer covering the whole 1x1 canvas.
+    struct Layer {
+        rgba: [u8; 4],
+        key: &'static [u8; 4],
+        opacity: u8,
+        hidden: bool,
+        clipped: bool,
+    }
+
+    impl Layer {
+        fn new(rgba: [u8; 4], key: &'static [u8; 4]) -> Self {
+            Self {
+                rgba,
+                key,
+                opacity: 255,
+                hidden: false,
+                clipped: false,
+            }
+        }
+    }
+
+    /// A 1x1 RGB file with `layers` given top most first like [`PsdLayers::layers`].
+    fn psd(layers: &[Layer]) -> PsdLayers {
+        let mut info = Vec::new();
+        info.extend((layers.len() as i16).to_be_bytes());
+        // Photoshop stores the bottom layer first.
+        for layer in layers.iter().rev() {
+            for bound in [0i32, 0, 1, 1] {
+                info.extend(bound.to_be_bytes());
+            }
+            info.extend(4u16.to_be_bytes());
+            for id in [-1i16, 0, 1, 2] {
+                info.extend(id.to_be_bytes());
+                info.extend(3u32.to_be_bytes());
+            }
+            info.extend(b"8BIM");
+            info.extend(layer.key);
+            info.push(layer.opacity);
+            info.push(layer.clipped as u8);
+            // The spec calls this bit visible, Photoshop sets it for hidden layers.
+            info.push(if layer.hidden { 0x02 } else { 0 });
+            info.push(0);
+            info.extend(12u32.to_be_bytes());
+            info.extend(0u32.to_be_bytes());
+            info.extend(0u32.to_be_bytes());
+            info.extend([3, b'a', b'b', b'c']);
+        }
+        for layer in layers.iter().rev() {
+            for channel in [3, 0, 1, 2] {
+                info.extend(0u16.to_be_bytes());
+                info.push(layer.rgba[channel]);
+            }
+        }
+        if info.len() % 2 != 0 {
+            info.push(0);
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend(b"8BPS");
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend([0; 6]);
+        bytes.extend(3u16.to_be_bytes());
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(8u16.to_be_bytes());
+        bytes.extend(3u16.to_be_bytes());
+        // Color mode data and image resources.
+        bytes.extend(0u32.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+        // Layer and mask information with an empty global mask.
+        bytes.extend((4 + info.len() as u32 + 4).to_be_bytes());
+        bytes.extend((info.len() as u32).to_be_bytes());
+        bytes.extend(info);
+        bytes.extend(0u32.to_be_bytes());
+        // Merged image.
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend([0, 0, 0]);
+
+        PsdLayers::new(Psd::from_bytes(&bytes).unwrap()).unwrap()
+    }
+
+    fn composite(psd: &PsdLayers) -> [u8; 4] {
+        let image = psd.composite(&psd.default_visibility()).unwrap();
+        image.to_rgba8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn hidden_flag_is_read_inverted() {
+        let mut hidden = Layer::new([255, 0, 0, 255], b"norm");
+        hidden.hidden = true;
+        let psd = psd(&[hidden, Layer::new([0, 0, 255, 255], b"norm")]);
+
+        assert!(!psd.layers[0].visible);
+        assert!(psd.layers[1].visible);
+        assert_eq!(composite(&psd), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn shown_layers_can_be_changed() {
+        let psd = psd(&[
+            Layer::new([255, 0, 0, 255], b"norm"),
+            Layer::new([0, 0, 255, 255], b"norm"),
+        ]);
+        let mut visibility = psd.default_visibility();
+        visibility.layers[0] = false;
+        let image = psd.composite(&visibility).unwrap();
+        assert_eq!(image.to_rgba8().get_pixel(0, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn blend_modes() {
+        let backdrop = [200, 100, 50, 255];
+        let cases: [(&[u8; 4], [u8; 4]); 6] = [
+            (b"norm", [100, 150, 250, 255]),
+            (b"mul ", [78, 59, 49, 255]),
+            (b"scrn", [222, 191, 251, 255]),
+            (b"diff", [100, 50, 200, 255]),
+            (b"dark", [100, 100, 50, 255]),
+            (b"lite", [200, 150, 250, 255]),
+        ];
+        for (key, expected) in cases {
+            let psd = psd(&[
+                Layer::new([100, 150, 250, 255], key),
+                Layer::new(backdrop, b"norm"),
+            ]);
+            let key = String::from_utf8_lossy(key);
+            assert_eq!(composite(&psd), expected, "{key}");
+        }
+    }
+
+    #[test]
+    fn unsupported_blend_modes_are_normal() {
+        let psd = psd(&[
+            Layer::new([100, 150, 250, 255], b"hue "),
+            Layer::new([200, 100, 50, 255], b"norm"),
+        ]);
+        assert_eq!(psd.layers[0].blend_mode, BlendMode::Normal);
+        assert_eq!(composite(&psd), [100, 150, 250, 255]);
+    }
+
+    #[test]
+    fn opacity_mixes_with_the_backdrop() {
+        let mut top = Layer::new([255, 255, 255, 255], b"norm");
+        top.opacity = 51;
+        let psd = psd(&[top, Layer::new([0, 0, 0, 255], b"norm")]);
+        assert_eq!(composite(&psd), [51, 51, 51, 255]);
+    }
+
+    #[test]
+    fn layers_over_transparency_keep_their_color() {
+        let psd = psd(&[
+            Layer::new([100, 150, 250, 255], b"mul "),
+            Layer::new([0, 0, 0, 0], b"norm"),
+        ]);
+        assert_eq!(composite(&psd), [100, 150, 250, 255]);
+    }
+
+    #[test]
+    fn clipping_masks_are_ignored() {
+        // Clipped to a fully transparent base the layer would not show in Photoshop.
+        let mut clipped = Layer::new([255, 0, 0, 255], b"norm");
+        clipped.clipped = true;
+        let psd = psd(&[clipped, Layer::new([0, 0, 0, 0], b"norm")]);
+        assert_eq!(composite(&psd), [255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,463 @@
+use std::{
+    cmp::Reverse,
+    fmt,
+    io::Cursor,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb, metadata::Orientation};
+use imagepipe::{
+    ImageSource, Pipeline, PipelineOps, SplineFunc,
+    color_conversions::{output8bit, output16bit},
+};
+use rawloader::{RawImage, RawImageData};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WhiteBalance {
+    /// The coefficients the camera stored in the file.
+    #[default]
+    AsShot,
+    /// Gray world estimate from the sensor data.
+    Auto,
+    /// `temperature` and `tint` from the settings.
+    Custom,
+}
+
+impl WhiteBalance {
+    pub const ALL: [WhiteBalance; 3] = [Self::AsShot, Self::Auto, Self::Custom];
+}
+
+impl AsRef<str> for WhiteBalance {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::AsShot => "As shot",
+            Self::Auto => "Auto",
+            Self::Custom => "Custom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RawSettings {
+    pub white_balance: WhiteBalance,
+    /// Kelvin, only used with [`WhiteBalance::Custom`].
+    pub temperature: f32,
+    /// Green to magenta ratio, only used with [`WhiteBalance::Custom`].
+    pub tint: f32,
+    /// Exposure compensation in stops.
+    pub exposure: f32,
+    /// 0 clips highlights, 1 rolls off the brightest half of the range.
+    pub highlight_recovery: f32,
+    /// 8 or 16.
+    pub bit_depth: u8,
+}
+
+impl RawSettings {
+    pub const TEMPERATURE: std::ops::RangeInclusive<f32> = 2000.0..=12000.0;
+    pub const TINT: std::ops::RangeInclusive<f32> = 0.5..=1.5;
+    pub const EXPOSURE: std::ops::RangeInclusive<f32> = -5.0..=5.0;
+
+    fn clamp(&mut self) {
+        self.temperature = self
+            .temperature
+            .clamp(*Self::TEMPERATURE.start(), *Self::TEMPERATURE.end());
+        self.tint = self.tint.clamp(*Self::TINT.start(), *Self::TINT.end());
+        self.exposure = self
+            .exposure
+            .clamp(*Self::EXPOSURE.start(), *Self::EXPOSURE.end());
+        self.highlight_recovery = self.highlight_recovery.clamp(0.0, 1.0);
+        if self.bit_depth != 8 {
+            self.bit_depth = 16;
+        }
+    }
+}
+
+impl Default for RawSettings {
+    fn default() -> Self {
+        Self {
+            white_balance: WhiteBalance::AsShot,
+            temperature: 5000.0,
+            tint: 1.0,
+            exposure: 0.0,
+            highlight_recovery: 0.0,
+            bit_depth: 16,
+        }
+    }
+}
+
+/// The decoded sensor data of a camera raw file so it can be developed again with
+/// other settings without decoding the file again.
+pub struct RawFile {
+    /// Owns the sensor data, it is developed in place since copying it costs as much
+    /// memory as the file takes.
+    pipeline: Mutex<Pipeline>,
+    /// The operations as set up for the file, every develop starts from them.
+    ops: PipelineOps,
+    make: String,
+    model: String,
+    memory_size: usize,
+    /// The settings `ImageData::frames` was developed with when the file was loaded.
+    pub settings: RawSettings,
+    /// White balance stored in the file as temperature and tint.
+    pub as_shot: (f32, f32),
+    /// Exif orientation applied after developing.
+    pub orientation: Option<Orientation>,
+    sidecar: Option<PathBuf>,
+}
+
+impl fmt::Debug for RawFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawFile")
+            .field("make", &self.make)
+            .field("model", &self.model)
+            .field("settings", &self.settings)
+            .field("sidecar", &self.sidecar)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RawFile {
+    /// Settings are read from the sidecar of `path` if there is one.
+    pub fn decode(bytes: &[u8], path: Option<&Path>) -> Option<Self> {
+        let raw = rawloader::decode(&mut Cursor::new(bytes)).ok()?;
+        let (make, model) = (raw.clean_make.clone(), raw.clean_model.clone());
+        let memory_size = match &raw.data {
+            RawImageData::Integer(data) => data.len() * size_of::<u16>(),
+            RawImageData::Float(data) => data.len() * size_of::<f32>(),
+        };
+        let pipeline = Pipeline::new_from_source(ImageSource::Raw(raw)).ok()?;
+        let (temperature, tint) = pipeline.ops.tolab.get_temp();
+        let as_shot = (
+            temperature.clamp(
+                *RawSettings::TEMPERATURE.start(),
+                *RawSettings::TEMPERATURE.end(),
+            ),
+            tint.clamp(*RawSettings::TINT.start(), *RawSettings::TINT.end()),
+        );
+
+        let sidecar = path.map(sidecar_path);
+        let settings = sidecar
+            .as_ref()
+            .filter(|sidecar| sidecar.is_file())
+            .and_then(|sidecar| confy::load_path::<RawSettings>(sidecar).ok())
+            .map(|mut settings| {
+                settings.clamp();
+                settings
+            })
+            .unwrap_or_else(|| RawSettings {
+                temperature: as_shot.0,
+                tint: as_shot.1,
+                ..Default::default()
+            });
+
+        Some(Self {
+            ops: pipeline.ops.clone(),
+            pipeline: Mutex::new(pipeline),
+            make,
+            model,
+            memory_size,
+            settings,
+            as_shot,
+            orientation: None,
+            sidecar,
+        })
+    }
+
+    /// Runs the processing pipeline on the sensor data. Develops of the same file wait
+    /// for each other.
+    pub fn develop(&self, settings: &RawSettings) -> Option<DynamicImage> {
+        let mut pipeline = self.pipeline.lock().unwrap();
+        pipeline.ops = self.ops.clone();
+        match settings.white_balance {
+            WhiteBalance::AsShot => (),
+            WhiteBalance::Auto => {
+                let ImageSource::Raw(raw) = &pipeline.globals.image else {
+                    return None;
+                };
+                if let Some(coeffs) = gray_world(raw) {
+                    pipeline.ops.tolab.wb_coeffs = coeffs;
+                }
+            }
+            WhiteBalance::Custom => pipeline
+                .ops
+                .tolab
+                .set_temp(settings.temperature, settings.tint),
+        }
+        pipeline.ops.basecurve.points = tone_curve(
+            &pipeline.ops.basecurve.points,
+            settings.exposure,
+            settings.highlight_recovery,
+        );
+
+        // `output_16bit` skips the gamma curve so the buffer is quantized here instead.
+        pipeline.globals.settings.linear = false;
+        let buffer = pipeline.run(None);
+        drop(pipeline);
+        let (width, height) = (buffer.width as u32, buffer.height as u32);
+        let mut image = if settings.bit_depth == 8 {
+            let data = buffer.data.iter().map(|value| output8bit(*value)).collect();
+            DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data)?)
+        } else {
+            let data = buffer
+                .data
+                .iter()
+                .map(|value| output16bit(*value))
+                .collect();
+            DynamicImage::ImageRgb16(ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, data)?)
+        };
+
+        if let Some(orientation) = self.orientation {
+            image.apply_orientation(orientation);
+        }
+        Some(image)
+    }
+
+    /// Remembers the settings next to the file. Default settings remove the sidecar
+    /// instead so files that were only looked at do not get one.
+    pub fn store_settings(&self, settings: &RawSettings) {
+        let Some(sidecar) = &self.sidecar else {
+            return;
+        };
+        let defaults = RawSettings {
+            temperature: settings.temperature,
+            tint: settings.tint,
+            ..Default::default()
+        };
+        if *settings == defaults {
+            let _ = std::fs::remove_file(sidecar);
+        } else {
+            let _ = confy::store_path(sidecar, settings);
+        }
+    }
+
+    /// Bytes taken by the sensor data.
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+}
+
+/// Multipliers that make the average of every color channel the same, ignoring
+/// clipped photosites.
+fn gray_world(raw: &RawImage) -> Option<[f32; 4]> {
+    let mut sums = [0.0f64; 4];
+    let mut counts = [0u64; 4];
+    let (width, cpp) = (raw.width.max(1), raw.cpp.max(1));
+    let color_at = |index: usize| {
+        if cpp == 1 {
+            raw.cfa.color_at(index / width, index % width)
+        } else {
+            index % cpp
+        }
+    };
+    let mut add = |color: usize, value: f32| {
+        if color < 4 && value > 0.0 && value < 0.95 {
+            sums[color] += value as f64;
+            counts[color] += 1;
+        }
+    };
+
+    match &raw.data {
+        RawImageData::Integer(data) => {
+            for (index, value) in data.iter().enumerate() {
+                let color = color_at(index).min(3);
+                let black = raw.blacklevels[color] as f32;
+                let white = raw.whitelevels[color] as f32;
+                add(color, (*value as f32 - black) / (white - black).max(1.0));
+            }
+        }
+        RawImageData::Float(data) => {
+            for (index, value) in data.iter().enumerate() {
+                add(color_at(index), *value);
+            }
+        }
+    }
+
+    let green = sums[1] / counts[1].max(1) as f64;
+    let mut coeffs = [0.0; 4];
+    for color in 0..4 {
+        if counts[color] == 0 {
+            continue;
+        }
+        let mean = sums[color] / counts[color] as f64;
+        if mean > 0.0 {
+            coeffs[color] = (green / mean) as f32;
+        }
+    }
+    (coeffs[0] > 0.0 && coeffs[1] > 0.0 && coeffs[2] > 0.0).then_some(coeffs)
+}
+
+/// Whether settings were stored for the file, its embedded preview does not show them.
+pub fn has_sidecar(path: &Path) -> bool {
+    sidecar_path(path).is_file()
+}
+
+/// `photo.nef` remembers its settings in `photo.nef.simp.toml`.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".simp.toml");
+    path.with_file_name(name)
+}
+
+/// Bakes exposure and highlight roll off into the points of the base curve, which
+/// works on the lightness channel.
+fn tone_curve(base: &[(f32, f32)], exposure: f32, highlight_recovery: f32) -> Vec<(f32, f32)> {
+    const SAMPLES: usize = 32;
+
+    if exposure.abs() < 0.001 && highlight_recovery <= 0.0 {
+        return base.to_vec();
+    }
+
+    let base = SplineFunc::new(base);
+    let gain = exposure.exp2();
+    let knee = 1.0 - 0.5 * highlight_recovery.clamp(0.0, 1.0);
+    (0..=SAMPLES)
+        .map(|i| {
+            let x = i as f32 / SAMPLES as f32;
+            let value = x * gain;
+            let value = if value <= knee || knee >= 1.0 {
+                value.min(1.0)
+            } else {
+                let range = 1.0 - knee;
+                knee + range * (1.0 - (-(value - knee) / range).exp())
+            };
+            (x, base.interpolate(value))
+        })
+        .collect()
+}
+
+/// The largest embedded JPEG preview of a camera raw file. Looks through every IFD of
+/// TIFF based formats (CR2, NEF, ARW, DNG, ORF, RW2, PEF...) and the header of RAF files.
+pub fn embedded_preview(bytes: &[u8]) -> Option<DynamicImage> {
+    // A dummy decode only reads the headers. It keeps JPEG compressed TIFFs and other
+    // files that are not camera raw out.
+    rawloader::decode_dummy(&mut Cursor::new(bytes)).ok()?;
+
+    let mut candidates = if bytes.starts_with(b"FUJIFILMCCD-RAW") {
+        raf_preview(bytes).into_iter().collect()
+    } else {
+        tiff_previews(bytes)
+    };
+    candidates.sort_by_key(|range| Reverse(range.len()));
+    candidates.dedup();
+
+    // The raw data of a DNG can itself be a lossless JPEG which the decoder rejects.
+    candidates.into_iter().find_map(|range| {
+        let data = bytes.get(range)?;
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        image::load_from_memory_with_format(data, ImageFormat::Jpeg).ok()
+    })
+}
+
+fn raf_preview(bytes: &[u8]) -> Option<Range<usize>> {
+    let read = |offset: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?) as usize)
+    };
+    let offset = read(84)?;
+    Some(offset..offset.saturating_add(read(88)?))
+}
+
+fn tiff_previews(bytes: &[u8]) -> Vec<Range<usize>> {
+    const SUB_IFDS: u16 = 0x14A;
+    const EXIF_IFD: u16 = 0x8769;
+    const STRIP_OFFSETS: u16 = 0x111;
+    const STRIP_BYTE_COUNTS: u16 = 0x117;
+    const JPEG_OFFSET: u16 = 0x201;
+    const JPEG_LENGTH: u16 = 0x202;
+    // Enough for every IFD of real files while stopping loops in broken ones.
+    const MAX_IFDS: usize = 64;
+
+    let little_endian = match bytes.get(..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Vec::new(),
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let data = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(data)
+        } else {
+            u16::from_be_bytes(data)
+        })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let data = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(data)
+        } else {
+            u32::from_be_bytes(data)
+        })
+    };
+    // The first value of a SHORT or LONG entry, or every LONG value for sub IFDs.
+    let values = |entry: usize| -> Vec<usize> {
+        let (Some(kind), Some(count)) = (u16_at(entry + 2), u32_at(entry + 4)) else {
+            return Vec::new();
+        };
+        match kind {
+            3 => u16_at(entry + 8)
+                .map(|v| vec![v as usize])
+                .unwrap_or_default(),
+            4 | 13 => {
+                let count = count as usize;
+                let start = if count > 1 {
+                    u32_at(entry + 8).map(|v| v as usize)
+                } else {
+                    Some(entry + 8)
+                };
+                start
+                    .map(|start| {
+                        (0..count.min(MAX_IFDS))
+                            .filter_map(|i| u32_at(start + i * 4).map(|v| v as usize))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    };
+
+    let mut candidates = Vec::new();
+    let mut queue: Vec<usize> = u32_at(4).map(|v| vec![v as usize]).unwrap_or_default();
+    let mut visited = Vec::new();
+    while let Some(ifd) = queue.pop() {
+        if ifd == 0 || visited.contains(&ifd) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(ifd);
+        let Some(count) = u16_at(ifd) else {
+            continue;
+        };
+
+        let (mut strip, mut strip_length, mut jpeg, mut jpeg_length) = (None, None, None, None);
+        for index in 0..count as usize {
+            let entry = ifd + 2 + index * 12;
+            let Some(tag) = u16_at(entry) else {
+                break;
+            };
+            let first = || values(entry).first().copied();
+            match tag {
+                SUB_IFDS | EXIF_IFD => queue.extend(values(entry)),
+                STRIP_OFFSETS => strip = first(),
+                STRIP_BYTE_COUNTS => strip_length = first(),
+                JPEG_OFFSET => jpeg = first(),
+                JPEG_LENGTH => jpeg_length = first(),
+                _ => (),
+            }
+        }
+        if let (Some(offset), Some(length)) = (jpeg, jpeg_length) {
+            candidates.push(offset..offset.saturating_add(length));
+        }
+        if let (Some(offset), Some(length)) = (strip, strip_length) {
+            candidates.push(offset..offset.saturating_add(length));
+        }
+        if let Some(next) = u32_at(ifd + 2 + count as usize * 12) {
+            queue.push(next as usize);
+        }
+    }
+    candidates
+}
@@ -1,29 +1,421 @@
 use std::{
+    borrow::Cow,
     error, fmt,
     fs::{self, File, OpenOptions},
     io::{BufWriter, Write},
+    mem,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use image::{
-    Frame, GenericImageView, ImageError, ImageFormat,
-    codecs::{gif::GifEncoder, jpeg::JpegEncoder},
+    ColorType, DynamicImage, GenericImageView, ImageEncoder, ImageError, ImageFormat, RgbaImage,
+    codecs::png::PngEncoder,
 };
+use serde::{Deserialize, Serialize};
 use webp_animation::prelude::*;
 
-use crate::util::Image;
+use super::{
+    Image, RawMetadata,
+    metadata::{ExifEntries, ExifEntry},
+};
+
+/// Signature of an xmp packet in a jpeg APP1 segment.
+const XMP_JPEG_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Keyword of the png iTXt chunk holding xmp.
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
 
 type SaveResult<T> = Result<T, SaveError>;
 
+/// Encoder settings for the format picked from the file extension.
+/// `Default` saves any format with its default settings. Options for another format than
+/// the one being saved are an error, formats without settings of their own ignore them.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SaveOptions {
+    #[default]
+    Default,
+    Jpeg(JpegOptions),
+    Png(PngOptions),
+    WebP(WebpOptions),
+    #[cfg(feature = "jxl")]
+    Jxl(JxlOptions),
+    Tiff(TiffOptions),
+    Gif(GifOptions),
+    #[cfg(feature = "avif")]
+    Avif(AvifOptions),
+    #[cfg(feature = "heif")]
+    Heif(HeifOptions),
+}
+
+impl SaveOptions {
+    fn name(&self) -> &'static str {
+        match self {
+            SaveOptions::Default => "default",
+            SaveOptions::Jpeg(_) => "jpeg",
+            SaveOptions::Png(_) => "png",
+            SaveOptions::WebP(_) => "webp",
+            #[cfg(feature = "jxl")]
+            SaveOptions::Jxl(_) => "jxl",
+            SaveOptions::Tiff(_) => "tiff",
+            SaveOptions::Gif(_) => "gif",
+            #[cfg(feature = "avif")]
+            SaveOptions::Avif(_) => "avif",
+            #[cfg(feature = "heif")]
+            SaveOptions::Heif(_) => "heif",
+        }
+    }
+
+    /// Options for another format would silently be replaced by the defaults.
+    fn mismatch(&self, format: &str) -> SaveError {
+        SaveError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} options cannot be used for {format}", self.name()),
+        ))
+    }
+
+    pub fn jpeg(&self) -> SaveResult<JpegOptions> {
+        match self {
+            SaveOptions::Jpeg(options) => Ok(*options),
+            SaveOptions::Default => Ok(JpegOptions::default()),
+            _ => Err(self.mismatch("jpeg")),
+        }
+    }
+
+    pub fn png(&self) -> SaveResult<PngOptions> {
+        match self {
+            SaveOptions::Png(options) => Ok(*options),
+            SaveOptions::Default => Ok(PngOptions::default()),
+            _ => Err(self.mismatch("png")),
+        }
+    }
+
+    pub fn webp(&self) -> SaveResult<WebpOptions> {
+        match self {
+            SaveOptions::WebP(options) => Ok(*options),
+            SaveOptions::Default => Ok(WebpOptions::default()),
+            _ => Err(self.mismatch("webp")),
+        }
+    }
+
+    #[cfg(feature = "jxl")]
+    pub fn jxl(&self) -> SaveResult<JxlOptions> {
+        match self {
+            SaveOptions::Jxl(options) => Ok(*options),
+            SaveOptions::Default => Ok(JxlOptions::default()),
+            _ => Err(self.mismatch("jxl")),
+        }
+    }
+
+    pub fn tiff(&self) -> SaveResult<TiffOptions> {
+        match self {
+            SaveOptions::Tiff(options) => Ok(*options),
+            SaveOptions::Default => Ok(TiffOptions::default()),
+            _ => Err(self.mismatch("tiff")),
+        }
+    }
+
+    pub fn gif(&self) -> SaveResult<GifOptions> {
+        match self {
+            SaveOptions::Gif(options) => Ok(*options),
+            SaveOptions::Default => Ok(GifOptions::default()),
+            _ => Err(self.mismatch("gif")),
+        }
+    }
+
+    #[cfg(feature = "avif")]
+    pub fn avif(&self) -> SaveResult<AvifOptions> {
+        match self {
+            SaveOptions::Avif(options) => Ok(*options),
+            SaveOptions::Default => Ok(AvifOptions::default()),
+            _ => Err(self.mismatch("avif")),
+        }
+    }
+
+    #[cfg(feature = "heif")]
+    pub fn heif(&self) -> SaveResult<HeifOptions> {
+        match self {
+            SaveOptions::Heif(options) => Ok(*options),
+            SaveOptions::Default => Ok(HeifOptions::default()),
+            _ => Err(self.mismatch("heif")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum JpegSubsampling {
+    #[default]
+    Yuv444,
+    Yuv422,
+    Yuv420,
+}
+
+impl AsRef<str> for JpegSubsampling {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Yuv444 => "4:4:4",
+            Self::Yuv422 => "4:2:2",
+            Self::Yuv420 => "4:2:0",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JpegOptions {
+    /// 1 to 100
+    pub quality: u8,
+    pub subsampling: JpegSubsampling,
+    pub progressive: bool,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80,
+            subsampling: JpegSubsampling::default(),
+            progressive: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PngCompression {
+    Uncompressed,
+    #[default]
+    Fast,
+    Default,
+    Best,
+}
+
+impl AsRef<str> for PngCompression {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Uncompressed => "Uncompressed",
+            Self::Fast => "Fast",
+            Self::Default => "Default",
+            Self::Best => "Best",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PngFilter {
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+impl AsRef<str> for PngFilter {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::NoFilter => "None",
+            Self::Sub => "Sub",
+            Self::Up => "Up",
+            Self::Avg => "Average",
+            Self::Paeth => "Paeth",
+            Self::Adaptive => "Adaptive",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PngOptions {
+    pub compression: PngCompression,
+    pub filter: PngFilter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WebpOptions {
+    pub lossy: bool,
+    /// 0 to 100, for lossless this is the compression effort
+    pub quality: f32,
+    /// 0 (fast) to 6 (slower but smaller)
+    pub method: u8,
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            lossy: false,
+            quality: 80.0,
+            method: 4,
+        }
+    }
+}
+
+#[cfg(feature = "jxl")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JxlOptions {
+    pub lossy: bool,
+    /// Butteraugli distance 0 to 15, lower is better. Only used when lossy.
+    pub distance: f32,
+    /// 1 (fast) to 10 (slow)
+    pub effort: u8,
+}
+
+#[cfg(feature = "jxl")]
+impl Default for JxlOptions {
+    fn default() -> Self {
+        Self {
+            lossy: true,
+            distance: 1.0,
+            effort: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AvifBitDepth {
+    Eight,
+    #[default]
+    Ten,
+}
+
+impl AsRef<str> for AvifBitDepth {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Eight => "8 bit",
+            Self::Ten => "10 bit",
+        }
+    }
+}
+
+#[cfg(feature = "avif")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AvifOptions {
+    /// 1 to 100
+    pub quality: u8,
+    /// 1 (slow but smaller) to 10 (fast)
+    pub speed: u8,
+    /// Ten bit files are smaller and band less even when made from 8 bit images.
+    pub bit_depth: AvifBitDepth,
+}
+
+#[cfg(feature = "avif")]
+impl Default for AvifOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80,
+            speed: 6,
+            bit_depth: AvifBitDepth::default(),
+        }
+    }
+}
+
+#[cfg(feature = "heif")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeifOptions {
+    pub lossy: bool,
+    /// 0 to 100, only used when lossy
+    pub quality: u8,
+}
+
+#[cfg(feature = "heif")]
+impl Default for HeifOptions {
+    fn default() -> Self {
+        Self {
+            lossy: true,
+            quality: 80,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TiffCompression {
+    #[default]
+    Uncompressed,
+    Lzw,
+    Deflate,
+    Packbits,
+}
+
+impl AsRef<str> for TiffCompression {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Uncompressed => "Uncompressed",
+            Self::Lzw => "LZW",
+            Self::Deflate => "Deflate",
+            Self::Packbits => "PackBits",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TiffOptions {
+    pub compression: TiffCompression,
+}
+
+/// How many times a gif asks to be played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GifRepeat {
+    /// The loop count of the source file.
+    #[default]
+    Source,
+    Infinite,
+    /// Repeats this many times after the first play.
+    Finite(u16),
+}
+
+impl AsRef<str> for GifRepeat {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Source => "Same as source",
+            Self::Infinite => "Forever",
+            Self::Finite(_) => "Count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GifOptions {
+    /// NeuQuant sampling factor 1 (best) to 30 (fast).
+    pub speed: u8,
+    /// Floyd-Steinberg error diffusion.
+    pub dithering: bool,
+    /// One palette for every frame instead of one per frame.
+    pub global_palette: bool,
+    /// Pixels with a lower alpha are transparent, the rest opaque.
+    pub transparency_threshold: u8,
+    pub repeat: GifRepeat,
+    /// Drops frames that are identical to the one before and adds their delay to it.
+    pub drop_duplicates: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            speed: 10,
+            dithering: true,
+            global_palette: false,
+            transparency_threshold: 128,
+            repeat: GifRepeat::Source,
+            drop_duplicates: true,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SaveError {
     Image(ImageError),
     Io(std::io::Error),
     #[allow(unused)]
     WebpAnimation(webp_animation::Error),
-    LibWebp(libwebp::error::WebPSimpleError),
+    Webp(u32),
+    /// A `WebPMuxError` from adding the metadata chunks.
+    WebpMux(i32),
+    Jpeg(jpeg_encoder::EncodingError),
+    Png(png::EncodingError),
+    Tiff(tiff::TiffError),
+    Gif(gif::EncodingError),
     #[cfg(feature = "jxl")]
     JxlEncode(jpegxl_rs::EncodeError),
+    #[cfg(feature = "avif")]
+    Avif(ravif::Error),
+    #[cfg(feature = "heif")]
+    Heif(libheif_rs::HeifError),
 }
 
 impl fmt::Display for SaveError {
@@ -33,9 +425,28 @@ impl fmt::Display for SaveError {
             SaveError::Image(ref e) => e.fmt(f),
             SaveError::Io(ref e) => e.fmt(f),
             SaveError::WebpAnimation(_) => write!(f, "error encoding webp"),
-            SaveError::LibWebp(ref e) => e.fmt(f),
+            SaveError::Webp(code) => write!(f, "error encoding webp: code {code}"),
+            SaveError::WebpMux(code) => {
+                let reason = match code {
+                    0 => "not found",
+                    -1 => "invalid argument",
+                    -2 => "bad data",
+                    -3 => "out of memory",
+                    -4 => "not enough data",
+                    _ => "unknown error",
+                };
+                write!(f, "error adding metadata to webp: {reason}")
+            }
+            SaveError::Jpeg(ref e) => e.fmt(f),
+            SaveError::Png(ref e) => e.fmt(f),
+            SaveError::Tiff(ref e) => e.fmt(f),
+            SaveError::Gif(ref e) => e.fmt(f),
             #[cfg(feature = "jxl")]
             SaveError::JxlEncode(ref e) => e.fmt(f),
+            #[cfg(feature = "avif")]
+            SaveError::Avif(ref e) => write!(f, "error encoding avif: {e}"),
+            #[cfg(feature = "heif")]
+            SaveError::Heif(ref e) => write!(f, "error encoding heif: {e}"),
         }
     }
 }
@@ -47,9 +458,18 @@ impl error::Error for SaveError {
             SaveError::Image(ref e) => Some(e),
             SaveError::Io(ref e) => Some(e),
             SaveError::WebpAnimation(_) => None,
-            SaveError::LibWebp(ref e) => Some(e),
+            SaveError::Webp(_) => None,
+            SaveError::WebpMux(_) => None,
+            SaveError::Jpeg(ref e) => Some(e),
+            SaveError::Png(ref e) => Some(e),
+            SaveError::Tiff(ref e) => Some(e),
+            SaveError::Gif(ref e) => Some(e),
             #[cfg(feature = "jxl")]
             SaveError::JxlEncode(ref e) => Some(e),
+            #[cfg(feature = "avif")]
+            SaveError::Avif(ref e) => Some(e),
+            #[cfg(feature = "heif")]
+            SaveError::Heif(ref e) => Some(e),
         }
     }
 }
@@ -75,10 +495,31 @@ impl From<webp_animation::Error> for SaveError {
     }
 }
 
-impl From<libwebp::error::WebPSimpleError> for SaveError {
+impl From<jpeg_encoder::EncodingError> for SaveError {
+    #[inline]
+    fn from(err: jpeg_encoder::EncodingError) -> SaveError {
+        SaveError::Jpeg(err)
+    }
+}
+
+impl From<png::EncodingError> for SaveError {
+    #[inline]
+    fn from(err: png::EncodingError) -> SaveError {
+        SaveError::Png(err)
+    }
+}
+
+impl From<tiff::TiffError> for SaveError {
+    #[inline]
+    fn from(err: tiff::TiffError) -> SaveError {
+        SaveError::Tiff(err)
+    }
+}
+
+impl From<gif::EncodingError> for SaveError {
     #[inline]
-    fn from(err: libwebp::error::WebPSimpleError) -> SaveError {
-        SaveError::LibWebp(err)
+    fn from(err: gif::EncodingError) -> SaveError {
+        SaveError::Gif(err)
     }
 }
 
@@ -90,6 +531,22 @@ impl From<jpegxl_rs::EncodeError> for SaveError {
     }
 }
 
+#[cfg(feature = "avif")]
+impl From<ravif::Error> for SaveError {
+    #[inline]
+    fn from(err: ravif::Error) -> SaveError {
+        SaveError::Avif(err)
+    }
+}
+
+#[cfg(feature = "heif")]
+impl From<libheif_rs::HeifError> for SaveError {
+    #[inline]
+    fn from(err: libheif_rs::HeifError) -> SaveError {
+        SaveError::Heif(err)
+    }
+}
+
 fn open_file(path: impl AsRef<Path>) -> Result<File, std::io::Error> {
     OpenOptions::new()
         .write(true)
@@ -134,43 +591,552 @@ pub fn save_with_format(
     Ok(())
 }
 
-pub fn jpeg(path: impl AsRef<Path>, image: &Image, quality: u8) -> SaveResult<()> {
+/// The OpenEXR encoder only takes float images.
+pub fn exr(path: impl AsRef<Path>, image: &Image) -> SaveResult<()> {
+    let buffer = match image.buffer() {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => image.buffer().clone(),
+        buffer if buffer.color().has_alpha() => DynamicImage::ImageRgba32F(buffer.to_rgba32f()),
+        buffer => DynamicImage::ImageRgb32F(buffer.to_rgb32f()),
+    };
+    save_with_format(
+        path,
+        &Image::with_delay(buffer, image.delay),
+        ImageFormat::OpenExr,
+    )
+}
+
+/// Picks an encoder from the extension of `path` and writes the frames to it.
+/// Unknown extensions are saved as png. Returns the path that was actually written.
+/// `metadata` is embedded by the jpeg, png, webp, tiff and jpeg xl encoders.
+pub fn save(
+    mut path: PathBuf,
+    frames: Vec<Image>,
+    metadata: &RawMetadata,
+    options: SaveOptions,
+) -> SaveResult<PathBuf> {
+    let ext = match path.extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => String::from("png"),
+    };
+    path.set_extension(&ext);
+    no_frames_check(&frames)?;
+
+    match ext.as_str() {
+        "hdr" => save_with_format(&path, &frames[0], ImageFormat::Hdr)?,
+        "png" | "apng" => {
+            if frames.len() > 1 {
+                apng(&path, &frames, metadata, options.png()?)?
+            } else {
+                png(&path, &frames[0], metadata, options.png()?)?
+            }
+        }
+        "qoi" => save_with_format(&path, &frames[0], ImageFormat::Qoi)?,
+        "jpg" | "jpeg" | "jpe" | "jif" | "jfif" => {
+            jpeg(&path, &frames[0], metadata, options.jpeg()?)?
+        }
+        "ico" => save_with_format(&path, &frames[0], ImageFormat::Ico)?,
+        "tga" => save_with_format(&path, &frames[0], ImageFormat::Tga)?,
+        "ff" | "farbfeld" => save_with_format(&path, &frames[0], ImageFormat::Farbfeld)?,
+        "tiff" | "tif" => tiff(&path, &frames[..1], metadata, options.tiff()?)?,
+        "gif" => gif(&path, frames, metadata, options.gif()?)?,
+        "webp" => {
+            if frames.len() > 1 {
+                webp_animation(&path, frames, metadata, options.webp()?)?
+            } else {
+                webp(&path, &frames[0], metadata, options.webp()?)?
+            }
+        }
+        "exr" => exr(&path, &frames[0])?,
+        #[cfg(feature = "jxl")]
+        "jxl" => {
+            if frames.len() > 1 {
+                jpeg_xl_animation(&path, &frames, metadata, options.jxl()?)?
+            } else {
+                jpeg_xl(&path, &frames[0], metadata, options.jxl()?)?
+            }
+        }
+        #[cfg(feature = "avif")]
+        "avif" => avif(&path, &frames[0], metadata, options.avif()?)?,
+        #[cfg(feature = "heif")]
+        "heic" | "heif" => heif(&path, &frames[0], metadata, options.heif()?)?,
+        _ => {
+            path.set_extension("png");
+            png(&path, &frames[0], metadata, options.png()?)?
+        }
+    }
+
+    Ok(path)
+}
+
+/// Saves every page of a multi-page image. TIFF keeps them in one file, every other
+/// format gets one numbered file per page next to `path`.
+pub fn save_pages(
+    mut path: PathBuf,
+    pages: Vec<Image>,
+    metadata: &RawMetadata,
+    options: SaveOptions,
+) -> SaveResult<()> {
+    let ext = match path.extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => String::from("png"),
+    };
+    path.set_extension(&ext);
+    no_frames_check(&pages)?;
+
+    if ext == "tiff" || ext == "tif" {
+        return tiff(&path, &pages, metadata, options.tiff()?);
+    }
+
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let width = pages.len().to_string().len();
+    for (index, page) in pages.into_iter().enumerate() {
+        let page_path = path.with_file_name(format!("{stem}-{:0width$}.{ext}", index + 1));
+        save(page_path, vec![page], metadata, options)?;
+    }
+    Ok(())
+}
+
+/// The encoders need at least one frame.
+fn no_frames_check(frames: &[Image]) -> SaveResult<()> {
+    if frames.is_empty() {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "there are no frames to save",
+        ))?
+    }
+    Ok(())
+}
+
+pub fn jpeg(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: JpegOptions,
+) -> SaveResult<()> {
+    use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+
+    let buffer = image.buffer();
+    let (Ok(width), Ok(height)) = (
+        u16::try_from(buffer.width()),
+        u16::try_from(buffer.height()),
+    ) else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "jpeg images can be at most 65535 pixels wide and high",
+        ))?
+    };
+
     let temp_path = get_temp_path(path.as_ref());
     let file = open_file(&temp_path)?;
 
-    let mut encoder = JpegEncoder::new_with_quality(BufWriter::new(file), quality);
-    let buffer = image.buffer();
+    let mut encoder = Encoder::new(BufWriter::new(file), options.quality.clamp(1, 100));
+    encoder.set_sampling_factor(match options.subsampling {
+        JpegSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+        JpegSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+        JpegSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+    });
+    encoder.set_progressive(options.progressive);
+
+    // Blocks that do not fit in a segment are dropped rather than failing the save.
+    if let Some(icc) = &metadata.icc {
+        let _ = encoder.add_icc_profile(icc);
+    }
+    if let Some(exif) = &metadata.exif {
+        let _ = encoder.add_exif_metadata(exif);
+    }
+    if let Some(xmp) = &metadata.xmp {
+        let _ = encoder.add_app_segment(1, [XMP_JPEG_HEADER, xmp].concat());
+    }
+
+    let res = if buffer.color().has_color() {
+        encoder.encode(&buffer.to_rgb8(), width, height, ColorType::Rgb)
+    } else {
+        encoder.encode(&buffer.to_luma8(), width, height, ColorType::Luma)
+    };
+
+    if let Err(err) = res {
+        let _ = fs::remove_file(&temp_path);
+        Err(err)?;
+    }
+
+    Ok(fs::rename(temp_path, path)?)
+}
+
+pub fn png(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: PngOptions,
+) -> SaveResult<()> {
+    use image::codecs::png::{CompressionType, FilterType};
+
+    let compression = match options.compression {
+        PngCompression::Uncompressed => CompressionType::Uncompressed,
+        PngCompression::Fast => CompressionType::Fast,
+        PngCompression::Default => CompressionType::Default,
+        PngCompression::Best => CompressionType::Best,
+    };
+    let filter = match options.filter {
+        PngFilter::NoFilter => FilterType::NoFilter,
+        PngFilter::Sub => FilterType::Sub,
+        PngFilter::Up => FilterType::Up,
+        PngFilter::Avg => FilterType::Avg,
+        PngFilter::Paeth => FilterType::Paeth,
+        PngFilter::Adaptive => FilterType::Adaptive,
+    };
+    let mut data = Vec::new();
+    let mut encoder = PngEncoder::new_with_quality(&mut data, compression, filter);
+    if let Some(icc) = &metadata.icc {
+        let _ = encoder.set_icc_profile(icc.clone());
+    }
+    if let Some(exif) = &metadata.exif {
+        let _ = encoder.set_exif_metadata(exif.clone());
+    }
 
-    encoder.encode(
+    // png has no float formats
+    let buffer = match image.buffer() {
+        DynamicImage::ImageRgb32F(_) => &DynamicImage::ImageRgb16(image.buffer().to_rgb16()),
+        DynamicImage::ImageRgba32F(_) => &DynamicImage::ImageRgba16(image.buffer().to_rgba16()),
+        buffer => buffer,
+    };
+
+    encoder.write_image(
         buffer.as_bytes(),
         buffer.width(),
         buffer.height(),
         buffer.color().into(),
     )?;
 
+    if let Some(xmp) = &metadata.xmp {
+        // The encoder has no xmp support so an iTXt chunk is inserted after IHDR.
+        let mut chunk = XMP_PNG_KEYWORD.to_vec();
+        // null separator, uncompressed, no language tag or translated keyword
+        chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+        chunk.extend_from_slice(xmp);
+        png_insert_chunk(&mut data, b"iTXt", &chunk);
+    }
+
+    let temp_path = get_temp_path(path.as_ref());
+    let mut file = open_file(&temp_path)?;
+    if let Err(err) = file.write_all(&data) {
+        let _ = fs::remove_file(&temp_path);
+        Err(err)?;
+    }
+
+    Ok(fs::rename(temp_path, path)?)
+}
+
+/// Writes the frames as an animated png, every frame covers the whole canvas.
+pub fn apng(
+    path: impl AsRef<Path>,
+    images: &[Image],
+    metadata: &RawMetadata,
+    options: PngOptions,
+) -> SaveResult<()> {
+    use png::{BitDepth, ColorType, Compression, Encoder, Filter, Info};
+
+    let (width, height) = images[0].buffer().dimensions();
+    if images
+        .iter()
+        .any(|image| image.buffer().dimensions() != (width, height))
+    {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "frames of an animated png must all have the same size",
+        ))?;
+    }
+
+    // 16-bit and float frames keep 16 bits, png has no float formats
+    let sixteen_bit = images.iter().any(|image| {
+        let color = image.buffer().color();
+        color.bytes_per_pixel() / color.channel_count() > 1
+    });
+
+    let mut info = Info::with_size(width, height);
+    info.icc_profile = metadata.icc.as_deref().map(Cow::Borrowed);
+    info.exif_metadata = metadata.exif.as_deref().map(Cow::Borrowed);
+
+    let mut data = Vec::new();
+    let mut encoder = Encoder::with_info(&mut data, info)?;
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(if sixteen_bit {
+        BitDepth::Sixteen
+    } else {
+        BitDepth::Eight
+    });
+    encoder.set_compression(match options.compression {
+        PngCompression::Uncompressed => Compression::NoCompression,
+        PngCompression::Fast => Compression::Fast,
+        PngCompression::Default => Compression::Balanced,
+        PngCompression::Best => Compression::High,
+    });
+    encoder.set_filter(match options.filter {
+        PngFilter::NoFilter => Filter::NoFilter,
+        PngFilter::Sub => Filter::Sub,
+        PngFilter::Up => Filter::Up,
+        PngFilter::Avg => Filter::Avg,
+        PngFilter::Paeth => Filter::Paeth,
+        PngFilter::Adaptive => Filter::Adaptive,
+    });
+    encoder.set_animated(images.len() as u32, metadata.loop_count.unwrap_or(0))?;
+
+    let mut writer = encoder.write_header()?;
+    for image in images {
+        let delay = image.delay.as_millis().min(u16::MAX as u128) as u16;
+        writer.set_frame_delay(delay, 1000)?;
+        if sixteen_bit {
+            let bytes: Vec<u8> = image
+                .buffer()
+                .to_rgba16()
+                .into_raw()
+                .into_iter()
+                .flat_map(u16::to_be_bytes)
+                .collect();
+            writer.write_image_data(&bytes)?;
+        } else {
+            writer.write_image_data(image.buffer().to_rgba8().as_raw())?;
+        }
+    }
+    writer.finish()?;
+
+    if let Some(xmp) = &metadata.xmp {
+        let mut chunk = XMP_PNG_KEYWORD.to_vec();
+        chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+        chunk.extend_from_slice(xmp);
+        png_insert_chunk(&mut data, b"iTXt", &chunk);
+    }
+
+    let temp_path = get_temp_path(path.as_ref());
+    let mut file = open_file(&temp_path)?;
+    if let Err(err) = file.write_all(&data) {
+        let _ = fs::remove_file(&temp_path);
+        Err(err)?;
+    }
+
     Ok(fs::rename(temp_path, path)?)
 }
 
+fn png_insert_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+
+    // Decoders only read text chunks that come before the image data
+    // so it goes right after the signature and IHDR.
+    let start = 8 + 25;
+    png.splice(start..start, chunk);
+}
+
+/// Writes every image as a page of the same file, `metadata` is attached to the first one.
+pub fn tiff(
+    path: impl AsRef<Path>,
+    pages: &[Image],
+    metadata: &RawMetadata,
+    options: TiffOptions,
+) -> SaveResult<()> {
+    use tiff::encoder::{Compression, DeflateLevel};
+
+    let temp_path = get_temp_path(path.as_ref());
+    let file = open_file(&temp_path)?;
+
+    let compression = match options.compression {
+        TiffCompression::Uncompressed => Compression::Uncompressed,
+        TiffCompression::Lzw => Compression::Lzw,
+        TiffCompression::Deflate => Compression::Deflate(DeflateLevel::Balanced),
+        TiffCompression::Packbits => Compression::Packbits,
+    };
+
+    let res = tiff_write_pages(BufWriter::new(file), compression, pages, metadata);
+    if let Err(err) = res {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    Ok(fs::rename(temp_path, path)?)
+}
+
+fn tiff_write_pages(
+    writer: BufWriter<File>,
+    compression: tiff::encoder::Compression,
+    pages: &[Image],
+    metadata: &RawMetadata,
+) -> SaveResult<()> {
+    use tiff::encoder::{TiffEncoder, colortype};
+
+    let mut encoder = TiffEncoder::new(writer)?.with_compression(compression);
+    let no_metadata = RawMetadata::default();
+    for (index, page) in pages.iter().enumerate() {
+        let metadata = if index == 0 { metadata } else { &no_metadata };
+        let encoder = &mut encoder;
+        let buffer = page.buffer();
+        match buffer.color() {
+            ColorType::L8 => {
+                tiff_write::<colortype::Gray8>(encoder, buffer, &buffer.to_luma8(), metadata)
+            }
+            ColorType::L16 => {
+                tiff_write::<colortype::Gray16>(encoder, buffer, &buffer.to_luma16(), metadata)
+            }
+            ColorType::Rgb8 => {
+                tiff_write::<colortype::RGB8>(encoder, buffer, &buffer.to_rgb8(), metadata)
+            }
+            ColorType::Rgb16 => {
+                tiff_write::<colortype::RGB16>(encoder, buffer, &buffer.to_rgb16(), metadata)
+            }
+            ColorType::Rgb32F => {
+                tiff_write::<colortype::RGB32Float>(encoder, buffer, &buffer.to_rgb32f(), metadata)
+            }
+            ColorType::La16 | ColorType::Rgba16 => {
+                tiff_write::<colortype::RGBA16>(encoder, buffer, &buffer.to_rgba16(), metadata)
+            }
+            ColorType::Rgba32F => tiff_write::<colortype::RGBA32Float>(
+                encoder,
+                buffer,
+                &buffer.to_rgba32f(),
+                metadata,
+            ),
+            _ => tiff_write::<colortype::RGBA8>(encoder, buffer, &buffer.to_rgba8(), metadata),
+        }?;
+    }
+    Ok(())
+}
+
+fn tiff_write<C: tiff::encoder::colortype::ColorType>(
+    encoder: &mut tiff::encoder::TiffEncoder<BufWriter<File>>,
+    buffer: &DynamicImage,
+    data: &[C::Inner],
+    metadata: &RawMetadata,
+) -> SaveResult<()>
+where
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    use tiff::tags::Tag;
+
+    let exif = metadata.exif.as_deref().and_then(ExifEntries::parse);
+
+    // The exif ifd has to be written before the image so the image can point to it.
+    let exif_offset = match &exif {
+        Some(exif) if !exif.exif.is_empty() => {
+            let mut directory = encoder.extra_directory()?;
+            tiff_write_entries(&mut directory, &exif.exif, exif.big_endian)?;
+            Some(directory.finish_with_offsets()?.offset)
+        }
+        _ => None,
+    };
+
+    let (width, height) = buffer.dimensions();
+    let mut image = encoder.new_image::<C>(width, height)?;
+    let directory = image.encoder();
+    if let Some(exif) = &exif {
+        tiff_write_entries(directory, &exif.image, exif.big_endian)?;
+    }
+    if let Some(offset) = exif_offset {
+        directory.write_tag(Tag::ExifDirectory, offset)?;
+    }
+    let blocks: Vec<_> = [(34675, 7, &metadata.icc), (700, 1, &metadata.xmp)]
+        .into_iter()
+        .filter_map(|(tag, field_type, data)| {
+            Some(ExifEntry {
+                tag,
+                field_type,
+                data: data.clone()?,
+            })
+        })
+        .collect();
+    // icc is UNDEFINED and xmp is BYTE so the byte order does not matter
+    tiff_write_entries(directory, &blocks, false)?;
+    image.write_data(data)?;
+
+    Ok(())
+}
+
+/// Copies exif entries into a tiff directory, converting them to the byte order of the file.
+fn tiff_write_entries<W: Write + std::io::Seek>(
+    directory: &mut tiff::encoder::DirectoryEncoder<W, tiff::encoder::TiffKindStandard>,
+    entries: &[ExifEntry],
+    big_endian: bool,
+) -> SaveResult<()> {
+    use tiff::{
+        Directory,
+        tags::{ByteOrder, Tag, Type},
+    };
+
+    let from = if big_endian {
+        ByteOrder::BigEndian
+    } else {
+        ByteOrder::LittleEndian
+    };
+
+    let mut written = Directory::empty();
+    for entry in entries {
+        let Some(field_type) = Type::from_u16(entry.field_type) else {
+            continue;
+        };
+        let mut data = entry.data.clone();
+        from.convert(field_type, &mut data, ByteOrder::native());
+        let value = directory.write_entry_bytes(field_type, &data)?;
+        written.extend([(Tag::from_u16_exhaustive(entry.tag), value)]);
+    }
+    directory.extend_from(&written);
+
+    Ok(())
+}
+
 #[cfg(feature = "jxl")]
-pub fn jpeg_xl(path: impl AsRef<Path>, image: &Image, quality: f32, lossy: bool) -> SaveResult<()> {
+pub fn jpeg_xl(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: JxlOptions,
+) -> SaveResult<()> {
     use std::io;
 
-    use image::DynamicImage;
-    use jpegxl_rs::encode::{self, EncoderFrame, EncoderResult, EncoderSpeed};
+    use jpegxl_rs::encode::{self, EncoderFrame, EncoderResult, EncoderSpeed, Metadata};
 
-    use crate::util::HasAlpha;
+    // jpegxl-rs can only describe the color space with an enum, the frame writer embeds the
+    // icc profile.
+    if metadata.icc.is_some() {
+        return jpeg_xl_animation(path, std::slice::from_ref(image), metadata, options);
+    }
 
     let temp_path = get_temp_path(path.as_ref());
     let mut file = open_file(&temp_path)?;
 
+    let speed = match options.effort {
+        0 | 1 => EncoderSpeed::Lightning,
+        2 => EncoderSpeed::Thunder,
+        3 => EncoderSpeed::Falcon,
+        4 => EncoderSpeed::Cheetah,
+        5 => EncoderSpeed::Hare,
+        6 => EncoderSpeed::Wombat,
+        7 => EncoderSpeed::Squirrel,
+        8 => EncoderSpeed::Kitten,
+        9 => EncoderSpeed::Tortoise,
+        _ => EncoderSpeed::Glacier,
+    };
+
     let mut encoder = encode::encoder_builder()
-        .quality(quality)
-        .lossless(!lossy)
-        .speed(EncoderSpeed::Kitten)
+        .quality(options.distance.clamp(0.0, 15.0))
+        .lossless(!options.lossy)
+        .speed(speed)
         .use_container(true)
-        .has_alpha(image.buffer().has_alpha())
+        .has_alpha(image.buffer().color().has_alpha())
         .build()?;
 
+    if let Some(exif) = &metadata.exif {
+        // The exif box starts with the offset to the tiff header.
+        let exif = [&[0, 0, 0, 0], exif.as_slice()].concat();
+        encoder.add_metadata(&Metadata::Exif(&exif), true)?;
+    }
+    if let Some(xmp) = &metadata.xmp {
+        encoder.add_metadata(&Metadata::Xmp(xmp), true)?;
+    }
+
     let data = match image.buffer() {
         DynamicImage::ImageRgb8(image_buffer) => {
             let frame = EncoderFrame::new(image_buffer.as_raw()).num_channels(3);
@@ -219,39 +1185,627 @@ pub fn jpeg_xl(path: impl AsRef<Path>, image: &Image, quality: f32, lossy: bool)
     Ok(fs::rename(temp_path, path)?)
 }
 
-pub fn gif(path: impl AsRef<Path>, images: Vec<Image>) -> SaveResult<()> {
+/// 10 bit files are encoded from 16 bit data. ravif can only describe the colors as sRGB
+/// so images with an icc profile are converted to it.
+#[cfg(feature = "avif")]
+pub fn avif(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: AvifOptions,
+) -> SaveResult<()> {
+    use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+    use ravif::{BitDepth, Encoder, Img, MatrixCoefficients, PixelRange, RGB8, RGBA8};
+
+    let bit_depth = match options.bit_depth {
+        AvifBitDepth::Eight => BitDepth::Eight,
+        AvifBitDepth::Ten => BitDepth::Ten,
+    };
+    let mut encoder = Encoder::new()
+        .with_quality(options.quality.clamp(1, 100) as f32)
+        .with_speed(options.speed.clamp(1, 10))
+        .with_bit_depth(bit_depth);
+    if let Some(exif) = &metadata.exif {
+        encoder = encoder.with_exif(exif.as_slice());
+    }
+
+    let profile = metadata
+        .icc
+        .as_deref()
+        .and_then(|icc| ColorProfile::new_from_slice(icc).ok())
+        .filter(|profile| profile.color_space == DataColorSpace::Rgb);
+    let srgb = ColorProfile::new_srgb();
+
+    let buffer = image.buffer();
+    let (width, height) = (buffer.width() as usize, buffer.height() as usize);
+    let encoded = match options.bit_depth {
+        AvifBitDepth::Eight => {
+            let mut rgba = buffer.to_rgba8().into_raw();
+            if let Some(profile) = &profile
+                && let Ok(transform) = profile.create_transform_8bit(
+                    Layout::Rgba,
+                    &srgb,
+                    Layout::Rgba,
+                    TransformOptions::default(),
+                )
+            {
+                let source = rgba.clone();
+                let _ = transform.transform(&source, &mut rgba);
+            }
+            if buffer.color().has_alpha() {
+                let pixels: Vec<RGBA8> = rgba
+                    .chunks_exact(4)
+                    .map(|pixel| RGBA8::new(pixel[0], pixel[1], pixel[2], pixel[3]))
+                    .collect();
+                encoder.encode_rgba(Img::new(pixels.as_slice(), width, height))?
+            } else {
+                let pixels: Vec<RGB8> = rgba
+                    .chunks_exact(4)
+                    .map(|pixel| RGB8::new(pixel[0], pixel[1], pixel[2]))
+                    .collect();
+                encoder.encode_rgb(Img::new(pixels.as_slice(), width, height))?
+            }
+        }
+        AvifBitDepth::Ten => {
+            let mut rgba = buffer.to_rgba16().into_raw();
+            if let Some(profile) = &profile
+                && let Ok(transform) = profile.create_transform_16bit(
+                    Layout::Rgba,
+                    &srgb,
+                    Layout::Rgba,
+                    TransformOptions::default(),
+                )
+            {
+                let source = rgba.clone();
+                let _ = transform.transform(&source, &mut rgba);
+            }
+            let planes = rgba
+                .chunks_exact(4)
+                .map(|pixel| ycbcr_10_bit(pixel[0], pixel[1], pixel[2]));
+            let alpha = rgba
+                .chunks_exact(4)
+                .any(|pixel| pixel[3] != u16::MAX)
+                .then(|| rgba.chunks_exact(4).map(|pixel| pixel[3] >> 6));
+            encoder.encode_raw_planes_10_bit(
+                width,
+                height,
+                planes,
+                alpha,
+                PixelRange::Full,
+                MatrixCoefficients::BT601,
+            )?
+        }
+    };
+
+    let temp_path = get_temp_path(path.as_ref());
+    let mut file = open_file(&temp_path)?;
+    file.write_all(&encoded.avif_file)?;
+    Ok(fs::rename(temp_path, path)?)
+}
+
+/// Full range BT.601 like ravif uses for 8 bit input.
+#[cfg(feature = "avif")]
+fn ycbcr_10_bit(r: u16, g: u16, b: u16) -> [u16; 3] {
+    const MAX: f32 = 1023.0;
+    let [r, g, b] = [r, g, b].map(|value| value as f32 / u16::MAX as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = (b - y) / 1.772 + 0.5;
+    let cr = (r - y) / 1.402 + 0.5;
+    [y, cb, cr].map(|value| (value * MAX).round().clamp(0.0, MAX) as u16)
+}
+
+/// Encodes HEVC with the encoder plugin libheif was built with.
+#[cfg(feature = "heif")]
+pub fn heif(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: HeifOptions,
+) -> SaveResult<()> {
+    use std::io;
+
+    use libheif_rs::{
+        Channel, ColorProfileRaw, ColorSpace, CompressionFormat, EncoderQuality, HeifContext,
+        Image as HeifImage, LibHeif, RgbChroma, color_profile_types,
+    };
+
+    let buffer = image.buffer();
+    let (width, height) = buffer.dimensions();
+    let (chroma, data, channels) = if buffer.color().has_alpha() {
+        (RgbChroma::Rgba, buffer.to_rgba8().into_raw(), 4)
+    } else {
+        (RgbChroma::Rgb, buffer.to_rgb8().into_raw(), 3)
+    };
+
+    let mut heif_image = HeifImage::new(width, height, ColorSpace::Rgb(chroma))?;
+    heif_image.create_plane(Channel::Interleaved, width, height, 8)?;
+    {
+        let planes = heif_image.planes_mut();
+        let Some(plane) = planes.interleaved else {
+            return Err(io::Error::other("libheif did not create an interleaved plane").into());
+        };
+        // Rows in the plane can be padded so they are copied one at a time.
+        let row = width as usize * channels;
+        for (y, source) in data.chunks_exact(row).enumerate() {
+            plane.data[y * plane.stride..][..row].copy_from_slice(source);
+        }
+    }
+    if let Some(icc) = &metadata.icc {
+        let profile = ColorProfileRaw::new(color_profile_types::PROF, icc.clone());
+        heif_image.set_color_profile_raw(&profile)?;
+    }
+
+    let lib_heif = LibHeif::new();
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Hevc)?;
+    encoder.set_quality(if options.lossy {
+        EncoderQuality::Lossy(options.quality.min(100))
+    } else {
+        EncoderQuality::LossLess
+    })?;
+
+    let mut context = HeifContext::new()?;
+    let handle = context.encode_image(&heif_image, &mut encoder, None)?;
+    // libheif adds the offset to the tiff header itself.
+    if let Some(exif) = &metadata.exif {
+        context.add_exif_metadata(&handle, exif)?;
+    }
+    if let Some(xmp) = &metadata.xmp {
+        context.add_xmp_metadata(&handle, xmp)?;
+    }
+    let data = context.write_to_bytes()?;
+
+    let temp_path = get_temp_path(path.as_ref());
+    let mut file = open_file(&temp_path)?;
+    file.write_all(&data)?;
+    Ok(fs::rename(temp_path, path)?)
+}
+
+/// Writes the frames as an animated JPEG XL. jpegxl-rs can only encode still images
+/// so this drives libjxl directly, every frame covers the whole canvas. A single frame
+/// is written as a still image, which is how images with an icc profile are saved.
+#[cfg(feature = "jxl")]
+pub fn jpeg_xl_animation(
+    path: impl AsRef<Path>,
+    images: &[Image],
+    metadata: &RawMetadata,
+    options: JxlOptions,
+) -> SaveResult<()> {
+    use std::{ffi::c_void, mem::MaybeUninit, ptr};
+
+    use jpegxl_rs::EncodeError;
+    use jpegxl_sys::{
+        common::types::{JxlBool, JxlBoxType, JxlDataType, JxlEndianness, JxlPixelFormat},
+        encoder::encode::*,
+        threads::thread_parallel_runner::*,
+    };
+
+    struct Handles {
+        encoder: *mut JxlEncoder,
+        runner: *mut c_void,
+    }
+
+    impl Drop for Handles {
+        fn drop(&mut self) {
+            // SAFETY: both were created by libjxl and are not used after this, the destroy
+            // functions do nothing for null pointers.
+            unsafe {
+                JxlEncoderDestroy(self.encoder);
+                JxlThreadParallelRunnerDestroy(self.runner);
+            }
+        }
+    }
+
+    fn check(status: JxlEncoderStatus) -> SaveResult<()> {
+        match status {
+            JxlEncoderStatus::Success => Ok(()),
+            _ => Err(EncodeError::GenericError.into()),
+        }
+    }
+
+    let (width, height) = images[0].buffer().dimensions();
+    if images
+        .iter()
+        .any(|image| image.buffer().dimensions() != (width, height))
+    {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "frames of an animated jpeg xl must all have the same size",
+        ))?;
+    }
+
+    let has_alpha = images
+        .iter()
+        .any(|image| image.buffer().color().has_alpha());
+    // 16-bit and float frames are encoded with 16 bits
+    let sixteen_bit = images.iter().any(|image| {
+        let color = image.buffer().color();
+        color.bytes_per_pixel() / color.channel_count() > 1
+    });
+    let num_channels = if has_alpha { 4 } else { 3 };
+    let bits = if sixteen_bit { 16 } else { 8 };
+
+    // SAFETY: the create functions have no preconditions. Null results are checked below
+    // and `Handles` destroys whatever was created once it is dropped, after the last use.
+    let handles = unsafe {
+        Handles {
+            encoder: JxlEncoderCreate(ptr::null()),
+            runner: JxlThreadParallelRunnerCreate(
+                ptr::null(),
+                JxlThreadParallelRunnerDefaultNumWorkerThreads(),
+            ),
+        }
+    };
+    let encoder = handles.encoder;
+    if encoder.is_null() || handles.runner.is_null() {
+        Err(EncodeError::CannotCreateEncoder)?;
+    }
+    // SAFETY: the encoder and runner are live and the runner outlives the encoder's use of
+    // it since both are destroyed together.
+    unsafe {
+        check(JxlEncoderSetParallelRunner(
+            encoder,
+            JxlThreadParallelRunner,
+            handles.runner,
+        ))?;
+        check(JxlEncoderUseContainer(encoder, true))?;
+    }
+
+    let mut basic_info = MaybeUninit::uninit();
+    // SAFETY: `JxlEncoderInitBasicInfo` initializes every field of the struct it is given.
+    let mut basic_info = unsafe {
+        JxlEncoderInitBasicInfo(basic_info.as_mut_ptr());
+        basic_info.assume_init()
+    };
+    basic_info.xsize = width;
+    basic_info.ysize = height;
+    basic_info.bits_per_sample = bits;
+    basic_info.uses_original_profile = (!options.lossy).into();
+    if has_alpha {
+        basic_info.num_extra_channels = 1;
+        basic_info.alpha_bits = bits;
+    }
+    basic_info.have_animation = (images.len() > 1).into();
+    // delays are stored in milliseconds
+    basic_info.animation.tps_numerator = 1000;
+    basic_info.animation.tps_denominator = 1;
+    basic_info.animation.num_loops = metadata.loop_count.unwrap_or(0);
+    // SAFETY: the encoder is live and libjxl copies the basic info.
+    unsafe { check(JxlEncoderSetBasicInfo(encoder, &basic_info))? };
+
+    // The frames are always written as rgb so only rgb profiles can describe them.
+    match &metadata.icc {
+        // SAFETY: the pointer and length describe `icc`, which libjxl copies.
+        Some(icc) if icc.get(16..20) == Some(&b"RGB "[..]) => unsafe {
+            check(JxlEncoderSetICCProfile(encoder, icc.as_ptr(), icc.len()))?;
+        },
+        _ => {
+            let mut color_encoding = MaybeUninit::uninit();
+            // SAFETY: `JxlColorEncodingSetToSRGB` initializes the whole struct before it
+            // is read by libjxl, which copies it.
+            unsafe {
+                JxlColorEncodingSetToSRGB(color_encoding.as_mut_ptr(), false);
+                check(JxlEncoderSetColorEncoding(encoder, color_encoding.as_ptr()))?;
+            }
+        }
+    }
+
+    let boxes = [
+        // The exif box starts with the offset to the tiff header.
+        (
+            b"Exif",
+            metadata
+                .exif
+                .as_ref()
+                .map(|exif| [&[0, 0, 0, 0], exif.as_slice()].concat()),
+        ),
+        (b"xml ", metadata.xmp.clone()),
+    ];
+    if boxes.iter().any(|(_, data)| data.is_some()) {
+        // SAFETY: boxes are enabled before the first one is added and before any frame,
+        // the pointer and length of each box describe a Vec that libjxl copies.
+        unsafe {
+            check(JxlEncoderUseBoxes(encoder))?;
+            for (kind, data) in boxes {
+                if let Some(data) = data {
+                    check(JxlEncoderAddBox(
+                        encoder,
+                        &JxlBoxType(kind.map(|byte| byte as _)),
+                        data.as_ptr(),
+                        data.len(),
+                        JxlBool::True,
+                    ))?;
+                }
+            }
+            JxlEncoderCloseBoxes(encoder);
+        }
+    }
+
+    // SAFETY: the encoder is live, the settings it returns are owned by the encoder and
+    // only used while it is.
+    let settings = unsafe { JxlEncoderFrameSettingsCreate(encoder, ptr::null()) };
+    if settings.is_null() {
+        Err(EncodeError::GenericError)?;
+    }
+    // SAFETY: the settings were checked to be non-null above and belong to the live encoder.
+    unsafe {
+        if options.lossy {
+            check(JxlEncoderSetFrameDistance(
+                settings,
+                options.distance.clamp(0.0, 15.0),
+            ))?;
+        } else {
+            check(JxlEncoderSetFrameLossless(settings, true))?;
+        }
+        check(JxlEncoderFrameSettingsSetOption(
+            settings,
+            JxlEncoderFrameSettingId::Effort,
+            options.effort.clamp(1, 10) as i64,
+        ))?;
+    }
+
+    let pixel_format = JxlPixelFormat {
+        num_channels,
+        data_type: if sixteen_bit {
+            JxlDataType::Uint16
+        } else {
+            JxlDataType::Uint8
+        },
+        endianness: JxlEndianness::Native,
+        align: 0,
+    };
+    for image in images {
+        let mut header = MaybeUninit::uninit();
+        // SAFETY: `JxlEncoderInitFrameHeader` initializes every field of the struct.
+        let mut header = unsafe {
+            JxlEncoderInitFrameHeader(header.as_mut_ptr());
+            header.assume_init()
+        };
+        if images.len() > 1 {
+            header.duration = image.delay.as_millis().min(u32::MAX as u128) as u32;
+        }
+
+        let buffer = image.buffer();
+        let pixels: Vec<u8> = match (sixteen_bit, has_alpha) {
+            (false, false) => buffer.to_rgb8().into_raw(),
+            (false, true) => buffer.to_rgba8().into_raw(),
+            (true, false) => bytemuck::cast_slice(&buffer.to_rgb16()).to_vec(),
+            (true, true) => bytemuck::cast_slice(&buffer.to_rgba16()).to_vec(),
+        };
+        // SAFETY: the header and pixels are copied by libjxl before the calls return. The
+        // pixels match `pixel_format`, every frame has the size of the basic info which was
+        // checked above.
+        unsafe {
+            check(JxlEncoderSetFrameHeader(settings, &header))?;
+            check(JxlEncoderAddImageFrame(
+                settings,
+                &pixel_format,
+                pixels.as_ptr() as *const c_void,
+                pixels.len(),
+            ))?;
+        }
+    }
+    // SAFETY: the encoder is live and every frame was added.
+    unsafe { JxlEncoderCloseInput(encoder) };
+
+    let mut data: Vec<u8> = Vec::with_capacity(64 * 1024);
+    loop {
+        let written = data.len();
+        let mut avail_out = data.capacity() - written;
+        // SAFETY: `next_out` points at the spare capacity of `data` which is `avail_out`
+        // bytes long. libjxl writes at most that many bytes and moves both forward, so the
+        // new length only covers initialized bytes.
+        let status = unsafe {
+            let mut next_out = data.as_mut_ptr().add(written);
+            let status = JxlEncoderProcessOutput(encoder, &mut next_out, &mut avail_out);
+            data.set_len(data.capacity() - avail_out);
+            status
+        };
+        match status {
+            JxlEncoderStatus::NeedMoreOutput => data.reserve(data.capacity()),
+            status => {
+                check(status)?;
+                break;
+            }
+        }
+    }
+    drop(handles);
+
+    let temp_path = get_temp_path(path.as_ref());
+    let mut file = open_file(&temp_path)?;
+    if let Err(err) = file.write_all(&data) {
+        let _ = fs::remove_file(&temp_path);
+        Err(err)?;
+    }
+
+    Ok(fs::rename(temp_path, path)?)
+}
+
+/// Most pixels a gif palette is built from.
+const GIF_PALETTE_SAMPLES: usize = 1 << 20;
+
+pub fn gif(
+    path: impl AsRef<Path>,
+    images: Vec<Image>,
+    metadata: &RawMetadata,
+    options: GifOptions,
+) -> SaveResult<()> {
+    use color_quant::NeuQuant;
+    use gif::{DisposalMethod, Encoder, Repeat};
+
+    let mut frames: Vec<(RgbaImage, Duration)> = Vec::with_capacity(images.len());
+    for image in images {
+        let buffer = image.buffer().to_rgba8();
+        match frames.last_mut() {
+            Some((last, delay)) if options.drop_duplicates && *last == buffer => {
+                *delay += image.delay;
+            }
+            _ => frames.push((buffer, image.delay)),
+        }
+    }
+
+    let (width, height) = frames.iter().fold((0, 0), |(width, height), (frame, _)| {
+        (width.max(frame.width()), height.max(frame.height()))
+    });
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "gif images can be at most 65535 pixels wide and high",
+        ))?
+    };
+
+    let threshold = options.transparency_threshold;
+    let transparent = frames
+        .iter()
+        .any(|(frame, _)| frame.pixels().any(|pixel| pixel[3] < threshold));
+    // The last palette entry is kept for transparent pixels.
+    let colors = if transparent { 255 } else { 256 };
+    let speed = options.speed.clamp(1, 30) as i32;
+    // Frames share the samples evenly so long animations don't copy every pixel.
+    let quantize = |frames: &[&RgbaImage]| {
+        let per_frame = (GIF_PALETTE_SAMPLES / frames.len()).max(1);
+        let opaque: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| {
+                let pixels = frame.width() as usize * frame.height() as usize;
+                frame.pixels().step_by(pixels.div_ceil(per_frame).max(1))
+            })
+            .filter(|pixel| pixel[3] >= threshold)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect();
+        NeuQuant::new(speed, colors, &opaque)
+    };
+    let palette = |quantizer: &NeuQuant| {
+        let mut palette = quantizer.color_map_rgb();
+        if transparent {
+            palette.extend_from_slice(&[0, 0, 0]);
+        }
+        palette
+    };
+
+    let global = options
+        .global_palette
+        .then(|| quantize(&frames.iter().map(|(frame, _)| frame).collect::<Vec<_>>()));
+    let global_palette = global.as_ref().map(palette).unwrap_or_default();
+
     let temp_path = get_temp_path(path.as_ref());
     let file = open_file(&temp_path)?;
+    let mut encoder = Encoder::new(BufWriter::new(file), width, height, &global_palette)?;
+
+    let repeat = match options.repeat {
+        GifRepeat::Source => match metadata.loop_count {
+            Some(0) | None => Some(Repeat::Infinite),
+            Some(plays) => Some(Repeat::Finite(
+                plays.saturating_sub(1).min(u16::MAX as u32) as u16
+            )),
+        },
+        GifRepeat::Infinite => Some(Repeat::Infinite),
+        GifRepeat::Finite(count) => Some(Repeat::Finite(count)),
+    };
+    // A repeat count of 0 means forever so playing once is told by leaving the block out.
+    if let Some(repeat) = repeat.filter(|repeat| *repeat != Repeat::Finite(0)) {
+        encoder.set_repeat(repeat)?;
+    }
+
+    for (buffer, delay) in &frames {
+        let local = global.is_none().then(|| quantize(&[buffer]));
+        let quantizer = global.as_ref().or(local.as_ref()).unwrap();
+        let indices = gif_remap(buffer, quantizer, options.dithering, threshold, transparent);
 
-    let frames: Vec<Frame> = images.into_iter().map(|image| image.into()).collect();
-    let mut encoder = GifEncoder::new(BufWriter::new(file));
-    encoder.encode_frames(frames)?;
+        let frame = gif::Frame {
+            width: buffer.width() as u16,
+            height: buffer.height() as u16,
+            // centiseconds
+            delay: (delay.as_millis() / 10).min(u16::MAX as u128) as u16,
+            // Transparent pixels must not show the frame before.
+            dispose: if transparent {
+                DisposalMethod::Background
+            } else {
+                DisposalMethod::Keep
+            },
+            transparent: transparent.then_some(255),
+            palette: local.as_ref().map(palette),
+            buffer: Cow::Owned(indices),
+            ..Default::default()
+        };
+        encoder.write_frame(&frame)?;
+    }
+    encoder.into_inner()?.flush()?;
 
     Ok(fs::rename(temp_path, path)?)
 }
 
+/// Maps every pixel to the palette of `quantizer`, with Floyd-Steinberg dithering if enabled.
+fn gif_remap(
+    buffer: &RgbaImage,
+    quantizer: &color_quant::NeuQuant,
+    dithering: bool,
+    threshold: u8,
+    transparent: bool,
+) -> Vec<u8> {
+    let width = buffer.width() as usize;
+    let mut indices = Vec::with_capacity(buffer.len() / 4);
+    // The error carried to the pixels of this row and the next, with one pixel of padding on each side.
+    let mut current = vec![[0.0f32; 3]; width + 2];
+    let mut next = vec![[0.0f32; 3]; width + 2];
+
+    for row in buffer.rows() {
+        for (x, pixel) in row.enumerate() {
+            if transparent && pixel[3] < threshold {
+                indices.push(255);
+                continue;
+            }
+
+            let mut color = [pixel[0], pixel[1], pixel[2], 255];
+            if dithering {
+                for (c, value) in color.iter_mut().take(3).enumerate() {
+                    *value = (*value as f32 + current[x + 1][c])
+                        .round()
+                        .clamp(0.0, 255.0) as u8;
+                }
+            }
+            let index = quantizer.index_of(&color);
+            indices.push(index as u8);
+
+            if dithering && let Some(mapped) = quantizer.lookup(index) {
+                for c in 0..3 {
+                    let error = color[c] as f32 - mapped[c] as f32;
+                    current[x + 2][c] += error * 7.0 / 16.0;
+                    next[x][c] += error * 3.0 / 16.0;
+                    next[x + 1][c] += error * 5.0 / 16.0;
+                    next[x + 2][c] += error / 16.0;
+                }
+            }
+        }
+        mem::swap(&mut current, &mut next);
+        next.fill([0.0; 3]);
+    }
+
+    indices
+}
+
 pub fn webp_animation(
     path: impl AsRef<Path>,
     images: Vec<Image>,
-    quality: f32,
-    lossy: bool,
+    metadata: &RawMetadata,
+    options: WebpOptions,
 ) -> SaveResult<()> {
-    let encoding_type = if lossy {
+    let encoding_type = if options.lossy {
         EncodingType::Lossy(LossyEncodingConfig::default())
     } else {
         EncodingType::Lossless
     };
     let config = EncodingConfig {
         encoding_type,
-        quality,
-        method: 6,
+        quality: options.quality.clamp(0.0, 100.0),
+        method: options.method.min(6) as usize,
     };
 
     let dimensions = images[0].buffer().dimensions();
-    let options = EncoderOptions {
+    let mut options = EncoderOptions {
         encoding_config: Some(config),
         ..Default::default()
     };
+    options.anim_params.loop_count = metadata.loop_count.unwrap_or(0) as i32;
     let mut encoder = Encoder::new_with_options(dimensions, options)?;
     let mut timestamp: i32 = 0;
     for image in images {
@@ -260,6 +1814,7 @@ pub fn webp_animation(
     }
 
     let webp_data = encoder.finalize(timestamp)?;
+    let webp_data = webp_add_metadata(&webp_data, metadata)?;
 
     let temp_path = get_temp_path(path.as_ref());
     let mut file = open_file(&temp_path)?;
@@ -268,25 +1823,14 @@ pub fn webp_animation(
     Ok(fs::rename(temp_path, path)?)
 }
 
-pub fn webp(path: impl AsRef<Path>, image: &Image, quality: f32, lossy: bool) -> SaveResult<()> {
-    let (width, height) = image.buffer().dimensions();
-
-    let webp_data = if lossy {
-        libwebp::WebPEncodeLosslessRGBA(
-            &image.buffer().to_rgba8().into_raw(),
-            width,
-            height,
-            width * 4,
-        )?
-    } else {
-        libwebp::WebPEncodeRGBA(
-            &image.buffer().to_rgba8().into_raw(),
-            width,
-            height,
-            width * 4,
-            quality,
-        )?
-    };
+pub fn webp(
+    path: impl AsRef<Path>,
+    image: &Image,
+    metadata: &RawMetadata,
+    options: WebpOptions,
+) -> SaveResult<()> {
+    let webp_data = webp_encode(image, options)?;
+    let webp_data = webp_add_metadata(&webp_data, metadata)?;
 
     let temp_path = get_temp_path(path.as_ref());
     let mut file = open_file(&temp_path)?;
@@ -294,3 +1838,123 @@ pub fn webp(path: impl AsRef<Path>, image: &Image, quality: f32, lossy: bool) ->
 
     Ok(fs::rename(temp_path, path)?)
 }
+
+fn webp_encode(image: &Image, options: WebpOptions) -> SaveResult<Vec<u8>> {
+    use std::{ffi::c_int, mem::MaybeUninit, os::raw::c_void};
+
+    use libwebp_sys::*;
+
+    extern "C" fn write(data: *const u8, data_size: usize, picture: *const WebPPicture) -> c_int {
+        // SAFETY: custom_ptr is set to a live Vec<u8> below and libwebp hands us a valid buffer.
+        unsafe {
+            let out = &mut *((*picture).custom_ptr as *mut Vec<u8>);
+            out.extend_from_slice(std::slice::from_raw_parts(data, data_size));
+        }
+        1
+    }
+
+    let buffer = image.buffer().to_rgba8();
+    let (width, height) = buffer.dimensions();
+    let mut out: Vec<u8> = Vec::new();
+
+    // SAFETY: the config and picture are initialized by libwebp before use,
+    // the picture only borrows `buffer` and `out` which outlive it and it is freed before returning.
+    unsafe {
+        let mut config = MaybeUninit::<WebPConfig>::uninit();
+        if WebPConfigInit(config.as_mut_ptr()) == 0 {
+            return Err(SaveError::Webp(VP8_ENC_ERROR_INVALID_CONFIGURATION));
+        }
+        let mut config = config.assume_init();
+        config.lossless = (!options.lossy) as c_int;
+        config.quality = options.quality.clamp(0.0, 100.0);
+        config.method = options.method.min(6) as c_int;
+
+        let mut picture = MaybeUninit::<WebPPicture>::uninit();
+        if WebPPictureInit(picture.as_mut_ptr()) == 0 {
+            return Err(SaveError::Webp(VP8_ENC_ERROR_INVALID_CONFIGURATION));
+        }
+        let mut picture = picture.assume_init();
+        picture.use_argb = 1;
+        picture.width = width as c_int;
+        picture.height = height as c_int;
+        picture.writer = Some(write);
+        picture.custom_ptr = &mut out as *mut Vec<u8> as *mut c_void;
+
+        if WebPPictureImportRGBA(&mut picture, buffer.as_ptr(), (width * 4) as c_int) == 0 {
+            let error = picture.error_code;
+            WebPPictureFree(&mut picture);
+            return Err(SaveError::Webp(error));
+        }
+
+        let ok = WebPEncode(&config, &mut picture);
+        let error = picture.error_code;
+        WebPPictureFree(&mut picture);
+        if ok == 0 {
+            return Err(SaveError::Webp(error));
+        }
+    }
+
+    Ok(out)
+}
+
+/// Adds the ICCP, EXIF and XMP chunks to an encoded webp.
+fn webp_add_metadata(webp_data: &[u8], metadata: &RawMetadata) -> SaveResult<Vec<u8>> {
+    use std::{ffi::c_char, ptr};
+
+    use libwebp_sys::*;
+
+    if metadata.is_empty() {
+        return Ok(webp_data.to_vec());
+    }
+
+    let chunks: [(&[u8; 5], &Option<Vec<u8>>); 3] = [
+        (b"ICCP\0", &metadata.icc),
+        (b"EXIF\0", &metadata.exif),
+        (b"XMP \0", &metadata.xmp),
+    ];
+
+    // SAFETY: every WebPData handed to libwebp points into slices that outlive the mux,
+    // the mux copies them and is deleted before returning.
+    unsafe {
+        let input = WebPData {
+            bytes: webp_data.as_ptr(),
+            size: webp_data.len(),
+        };
+        let mux = WebPMuxCreate(&input, 1);
+        if mux.is_null() {
+            return Err(SaveError::WebpMux(WEBP_MUX_BAD_DATA));
+        }
+
+        for (fourcc, chunk) in chunks {
+            if let Some(chunk) = chunk {
+                let data = WebPData {
+                    bytes: chunk.as_ptr(),
+                    size: chunk.len(),
+                };
+                let error = WebPMuxSetChunk(mux, fourcc.as_ptr() as *const c_char, &data, 1);
+                if error != WEBP_MUX_OK {
+                    WebPMuxDelete(mux);
+                    return Err(SaveError::WebpMux(error));
+                }
+            }
+        }
+
+        let mut output = WebPData {
+            bytes: ptr::null(),
+            size: 0,
+        };
+        let error = WebPMuxAssemble(mux, &mut output);
+        WebPMuxDelete(mux);
+        if error != WEBP_MUX_OK {
+            WebPDataClear(&mut output);
+            return Err(SaveError::WebpMux(error));
+        }
+        if output.bytes.is_null() {
+            return Err(SaveError::WebpMux(WEBP_MUX_MEMORY_ERROR));
+        }
+
+        let out = std::slice::from_raw_parts(output.bytes, output.size).to_vec();
+        WebPDataClear(&mut output);
+        Ok(out)
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+/// The parsed document of an svg so it can be rasterized again at any size,
+/// `frames` only holds it rasterized at the size it was loaded with.
+pub struct Svg {
+    tree: Tree,
+    /// Length of the source, the parsed tree is about as large.
+    len: usize,
+}
+
+impl fmt::Debug for Svg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Svg")
+            .field("size", &self.tree.size())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Svg {
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        let tree = Tree::from_data(bytes, &Options::default()).ok()?;
+        Some(Self {
+            tree,
+            len: bytes.len(),
+        })
+    }
+
+    /// Bytes taken by the parsed document, an estimate.
+    pub fn memory_size(&self) -> usize {
+        self.len
+    }
+
+    /// The size the document asks for in CSS pixels.
+    pub fn size(&self) -> (f32, f32) {
+        (self.tree.size().width(), self.tree.size().height())
+    }
+
+    pub fn render(&self, width: u32, height: u32) -> Option<DynamicImage> {
+        let (tree_width, tree_height) = self.size();
+        let transform =
+            Transform::from_scale(width as f32 / tree_width, height as f32 / tree_height);
+        self.rasterize(width, height, transform)
+    }
+
+    /// Renders the rectangle `x`, `y`, `width`, `height` of the document rasterized at
+    /// `raster` pixels into an image of `output` pixels.
+    pub fn render_region(
+        &self,
+        raster: (u32, u32),
+        (x, y, width, height): (f32, f32, f32, f32),
+        output: (u32, u32),
+    ) -> Option<DynamicImage> {
+        let (tree_width, tree_height) = self.size();
+        let scale_x = output.0 as f32 / width;
+        let scale_y = output.1 as f32 / height;
+        let transform = Transform::from_row(
+            raster.0 as f32 / tree_width * scale_x,
+            0.0,
+            0.0,
+            raster.1 as f32 / tree_height * scale_y,
+            -x * scale_x,
+            -y * scale_y,
+        );
+        self.rasterize(output.0, output.1, transform)
+    }
+
+    fn rasterize(&self, width: u32, height: u32, transform: Transform) -> Option<DynamicImage> {
+        let mut pix_map = Pixmap::new(width, height)?;
+        resvg::render(&self.tree, transform, &mut pix_map.as_mut());
+
+        let width = pix_map.width();
+        let height = pix_map.height();
+        let data = pix_map.take();
+        ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+    }
+}
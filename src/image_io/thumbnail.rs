@@ -0,0 +1,364 @@
+//! Thumbnails stored the way the freedesktop thumbnail spec describes, so they are shared
+//! with file managers and other viewers.
+//!
+//! A thumbnail lives in `$XDG_CACHE_HOME/thumbnails/<size>/<md5 of the file uri>.png`
+//! and is only valid while its `Thumb::MTime` matches the modification time of the file.
+
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, Write},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use directories::BaseDirs;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+const SOFTWARE: &str = concat!("simp ", env!("CARGO_PKG_VERSION"));
+/// Failures are recorded per program and version in `fail/<program>-<version>`.
+const FAIL_DIR: &str = concat!("simp-", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ThumbnailSize {
+    Normal,
+    #[default]
+    Large,
+    XLarge,
+}
+
+impl AsRef<str> for ThumbnailSize {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Normal => "Normal (128 px)",
+            Self::Large => "Large (256 px)",
+            Self::XLarge => "Extra large (512 px)",
+        }
+    }
+}
+
+impl ThumbnailSize {
+    pub const ALL: [Self; 3] = [Self::Normal, Self::Large, Self::XLarge];
+
+    /// The longest side of a thumbnail of this size.
+    pub fn pixels(self) -> u32 {
+        match self {
+            Self::Normal => 128,
+            Self::Large => 256,
+            Self::XLarge => 512,
+        }
+    }
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Large => "large",
+            Self::XLarge => "x-large",
+        }
+    }
+}
+
+/// The root of the shared thumbnail cache, `~/.cache/thumbnails` on most systems.
+pub fn thumbnail_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| dirs.cache_dir().join("thumbnails"))
+}
+
+/// The absolute `file://` uri of a path, escaped like glib does it. Symlinks are kept as
+/// they are so the uri matches the one file managers hash for the same path.
+pub fn file_uri(path: &Path) -> io::Result<String> {
+    let mut absolute = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            Component::CurDir => (),
+            component => absolute.push(component),
+        }
+    }
+    let path = absolute;
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'!'
+            | b'~'
+            | b'*'
+            | b'\''
+            | b'('
+            | b')'
+            | b'/'
+            | b':'
+            | b'@'
+            | b'&'
+            | b'='
+            | b'+'
+            | b'$'
+            | b',' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Ok(uri)
+}
+
+fn file_name(uri: &str) -> String {
+    format!("{:x}.png", md5::compute(uri))
+}
+
+/// The file info a thumbnail is checked against.
+struct Source {
+    uri: String,
+    mtime: u64,
+    size: u64,
+}
+
+impl Source {
+    fn new(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        Ok(Self {
+            uri: file_uri(path)?,
+            mtime,
+            size: metadata.len(),
+        })
+    }
+
+    /// Checks the `Thumb::URI` and `Thumb::MTime` keys of a thumbnail png.
+    fn matches(&self, png: &[u8]) -> bool {
+        let decoder = png::Decoder::new(Cursor::new(png));
+        let Ok(reader) = decoder.read_info() else {
+            return false;
+        };
+        let info = reader.info();
+        let text = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), Some(chunk.text.clone())))
+            .chain(
+                info.compressed_latin1_text
+                    .iter()
+                    .map(|chunk| (chunk.keyword.clone(), chunk.get_text().ok())),
+            )
+            .chain(
+                info.utf8_text
+                    .iter()
+                    .map(|chunk| (chunk.keyword.clone(), chunk.get_text().ok())),
+            );
+
+        let mut uri = false;
+        let mut mtime = false;
+        for (keyword, text) in text {
+            let Some(text) = text else {
+                continue;
+            };
+            match keyword.as_str() {
+                "Thumb::URI" => uri = text == self.uri,
+                "Thumb::MTime" => mtime = text.trim().parse() == Ok(self.mtime),
+                _ => (),
+            }
+        }
+        uri && mtime
+    }
+}
+
+/// Whether the file is itself a thumbnail, those should never get thumbnails of their own.
+pub fn is_thumbnail(path: &Path) -> bool {
+    match (thumbnail_dir(), path.canonicalize()) {
+        (Some(dir), Ok(path)) => path.starts_with(dir.canonicalize().unwrap_or(dir)),
+        _ => false,
+    }
+}
+
+/// Reads the thumbnail of a file if there is one and it is still up to date.
+pub fn read(path: &Path, size: ThumbnailSize) -> Option<RgbaImage> {
+    let source = Source::new(path).ok()?;
+    let thumbnail = thumbnail_dir()?
+        .join(size.dir_name())
+        .join(file_name(&source.uri));
+    let bytes = fs::read(thumbnail).ok()?;
+    if !source.matches(&bytes) {
+        return None;
+    }
+    image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+        .ok()
+        .map(DynamicImage::into_rgba8)
+}
+
+/// Whether this version of simp already failed to generate a thumbnail for the file since
+/// it was last modified.
+pub fn has_failed(path: &Path) -> bool {
+    let (Ok(source), Some(dir)) = (Source::new(path), thumbnail_dir()) else {
+        return false;
+    };
+    fs::read(dir.join("fail").join(FAIL_DIR).join(file_name(&source.uri)))
+        .is_ok_and(|bytes| source.matches(&bytes))
+}
+
+/// Scales the image down to fit the thumbnail size, smaller images are kept as they are.
+pub fn scale(image: &DynamicImage, size: ThumbnailSize) -> RgbaImage {
+    let pixels = size.pixels();
+    if image.width() > pixels || image.height() > pixels {
+        image.thumbnail(pixels, pixels).into_rgba8()
+    } else {
+        image.to_rgba8()
+    }
+}
+
+/// Scales the image down to the thumbnail size and stores it in the cache.
+pub fn write(path: &Path, size: ThumbnailSize, image: &DynamicImage) -> io::Result<RgbaImage> {
+    let source = Source::new(path)?;
+    let dir = thumbnail_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?
+        .join(size.dir_name());
+
+    let thumbnail = scale(image, size);
+    let text = [
+        ("Thumb::Image::Width", image.width().to_string()),
+        ("Thumb::Image::Height", image.height().to_string()),
+    ];
+    store(&dir, &source, &thumbnail, &text)?;
+    Ok(thumbnail)
+}
+
+/// Marks the file as one that could not be thumbnailed so it is not tried again until it
+/// changes.
+pub fn write_failure(path: &Path) -> io::Result<()> {
+    let source = Source::new(path)?;
+    let dir = thumbnail_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?
+        .join("fail")
+        .join(FAIL_DIR);
+    store(&dir, &source, &RgbaImage::new(1, 1), &[])
+}
+
+fn store(
+    dir: &Path,
+    source: &Source,
+    thumbnail: &RgbaImage,
+    text: &[(&str, String)],
+) -> io::Result<()> {
+    create_private_dir(dir)?;
+
+    // Written to a temporary file first so nobody reads a half written thumbnail.
+    let name = file_name(&source.uri);
+    let temp = dir.join(format!("{name}.simp-{}", std::process::id()));
+    let res = encode_png(source, thumbnail, text).and_then(|png| {
+        let mut file = create_private_file(&temp)?;
+        file.write_all(&png)
+    });
+    match res {
+        Ok(()) => fs::rename(&temp, dir.join(name)),
+        Err(err) => {
+            let _ = fs::remove_file(&temp);
+            Err(err)
+        }
+    }
+}
+
+fn encode_png(
+    source: &Source,
+    thumbnail: &RgbaImage,
+    text: &[(&str, String)],
+) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, thumbnail.width(), thumbnail.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let text = [
+        ("Thumb::URI", source.uri.clone()),
+        ("Thumb::MTime", source.mtime.to_string()),
+        ("Thumb::Size", source.size.to_string()),
+        ("Software", SOFTWARE.to_string()),
+    ]
+    .into_iter()
+    .chain(text.iter().cloned());
+    for (keyword, text) in text {
+        encoder
+            .add_text_chunk(keyword.to_string(), text)
+            .map_err(io::Error::other)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(thumbnail.as_raw())
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    Ok(png)
+}
+
+// The spec asks for the cache to only be readable by the user.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> io::Result<File> {
+    File::create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_escapes_reserved_bytes() {
+        let uri = file_uri(Path::new("/tmp/a b/ü#%?.png")).unwrap();
+        assert_eq!(uri, "file:///tmp/a%20b/%C3%BC%23%25%3F.png");
+    }
+
+    #[test]
+    fn uri_keeps_sub_delimiters() {
+        let uri = file_uri(Path::new("/photos/me,you&them=(1)+'2'!~*$@:.png")).unwrap();
+        assert_eq!(uri, "file:///photos/me,you&them=(1)+'2'!~*$@:.png");
+    }
+
+    #[test]
+    fn uri_is_absolute_without_resolving_links() {
+        let uri = file_uri(Path::new("/tmp/./links/../link.png")).unwrap();
+        assert_eq!(uri, "file:///tmp/link.png");
+
+        let relative = file_uri(Path::new("image.png")).unwrap();
+        let dir = file_uri(&std::env::current_dir().unwrap()).unwrap();
+        assert_eq!(relative, format!("{dir}/image.png"));
+    }
+
+    /// The example from the thumbnail spec.
+    #[test]
+    fn file_name_is_md5_of_uri() {
+        assert_eq!(
+            file_name("file:///home/jens/photos/me.png"),
+            "c6ee772d9e49320e97ec29a7eb5b1697.png"
+        );
+    }
+}
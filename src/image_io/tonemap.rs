@@ -0,0 +1,158 @@
+use image::DynamicImage;
+
+use super::{Image, ImageData};
+
+// Everything here must match the tone mapping in image.frag so applying gives the displayed pixels.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMapOperator {
+    #[default]
+    Clamp,
+    Reinhard,
+    AcesFilmic,
+    Agx,
+}
+
+impl ToneMapOperator {
+    pub const ALL: [Self; 4] = [Self::Clamp, Self::Reinhard, Self::AcesFilmic, Self::Agx];
+
+    /// Value of the `tonemap` uniform.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    fn map(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Clamp => rgb.map(|v| v.min(1.0)),
+            Self::Reinhard => rgb.map(|v| v / (1.0 + v)),
+            Self::AcesFilmic => rgb.map(|v| {
+                ((v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }),
+            Self::Agx => agx(rgb),
+        }
+    }
+}
+
+impl AsRef<str> for ToneMapOperator {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Clamp => "Clamp",
+            Self::Reinhard => "Reinhard",
+            Self::AcesFilmic => "ACES filmic",
+            Self::Agx => "AgX",
+        }
+    }
+}
+
+/// Column major like glsl.
+const AGX_INSET: [f32; 9] = [
+    0.842_479_1,
+    0.042_328_242,
+    0.042_375_655,
+    0.078_433_6,
+    0.878_468_6,
+    0.078_433_6,
+    0.079_223_745,
+    0.079_166_13,
+    0.879_143,
+];
+
+const AGX_OUTSET: [f32; 9] = [
+    1.196_879,
+    -0.052_896_85,
+    -0.052_971_635,
+    -0.098_020_88,
+    1.151_903_1,
+    -0.098_043_45,
+    -0.099_029_74,
+    -0.098_961_18,
+    1.151_073_7,
+];
+
+const AGX_MIN_EV: f32 = -12.473_931;
+const AGX_MAX_EV: f32 = 4.026_069;
+
+fn mul(matrix: &[f32; 9], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| matrix[i] * v[0] + matrix[3 + i] * v[1] + matrix[6 + i] * v[2])
+}
+
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+fn agx(rgb: [f32; 3]) -> [f32; 3] {
+    let encoded = mul(&AGX_INSET, rgb).map(|v| {
+        let x = (v.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV)
+            / (AGX_MAX_EV - AGX_MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    mul(&AGX_OUTSET, encoded).map(|v| v.max(0.0).powf(2.2))
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value < 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value < 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Float images (exr, hdr) hold linear values, integer images are sRGB encoded.
+pub fn is_linear(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// Maps linear scene values to linear display values in the 0 to 1 range.
+pub fn tone_map(rgb: [f32; 3], exposure: f32, gamma: f32, operator: ToneMapOperator) -> [f32; 3] {
+    let scale = exposure.exp2();
+    operator
+        .map(rgb.map(|v| v.max(0.0) * scale))
+        .map(|v| v.max(0.0).powf(1.0 / gamma).min(1.0))
+}
+
+impl ImageData {
+    /// Bakes the exposure and tone mapping into sRGB pixels. Images with more than
+    /// 8 bits per channel become 16 bit so the result can be saved as png.
+    pub fn tone_map(&self, exposure: f32, gamma: f32, operator: ToneMapOperator) -> Vec<Image> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let buffer = frame.buffer();
+                let linear = is_linear(buffer);
+                let alpha = buffer.color().has_alpha();
+                let high_precision =
+                    buffer.color().bytes_per_pixel() / buffer.color().channel_count() > 1;
+
+                let mut rgba = buffer.to_rgba32f();
+                for pixel in rgba.pixels_mut() {
+                    let mut rgb = [pixel.0[0], pixel.0[1], pixel.0[2]];
+                    if !linear {
+                        rgb = rgb.map(srgb_to_linear);
+                    }
+                    let rgb = tone_map(rgb, exposure, gamma, operator).map(linear_to_srgb);
+                    pixel.0[..3].copy_from_slice(&rgb);
+                }
+
+                let rgba = DynamicImage::ImageRgba32F(rgba);
+                let image = match (high_precision, alpha) {
+                    (true, true) => DynamicImage::ImageRgba16(rgba.to_rgba16()),
+                    (true, false) => DynamicImage::ImageRgb16(rgba.to_rgb16()),
+                    (false, true) => DynamicImage::ImageRgba8(rgba.to_rgba8()),
+                    (false, false) => DynamicImage::ImageRgb8(rgba.to_rgb8()),
+                };
+                Image::with_delay(image, frame.delay)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use cgmath::Vector2;
+use image::{
+    ColorType, DynamicImage,
+    imageops::{
+        FilterType,
+        colorops::{contrast_in_place, huerotate_in_place},
+        flip_horizontal_in_place, flip_vertical_in_place,
+    },
+};
+
+use super::{
+    Image, ImageData,
+    exr::ExrSelection,
+    imageops::{self, adjust_saturation_in_place, brighten_in_place},
+    photoshop::PsdVisibility,
+    raw::RawSettings,
+};
+
+/// The color types an image can be converted to.
+pub const COLOR_SPACES: [ColorType; 10] = [
+    ColorType::L8,
+    ColorType::La8,
+    ColorType::Rgb8,
+    ColorType::Rgba8,
+    ColorType::L16,
+    ColorType::La16,
+    ColorType::Rgb16,
+    ColorType::Rgba16,
+    ColorType::Rgb32F,
+    ColorType::Rgba32F,
+];
+
+/// Rotates clockwise in steps of 90 degrees, the same way the view shows a rotation.
+pub fn rotate_frames<'a>(frames: impl IntoIterator<Item = &'a Image>, rotation: i32) -> Vec<Image> {
+    frames
+        .into_iter()
+        .map(|frame| {
+            let buffer = match rotation.rem_euclid(4) {
+                0 => frame.buffer().clone(),
+                1 => frame.buffer().rotate90(),
+                2 => frame.buffer().rotate180(),
+                3 => frame.buffer().rotate270(),
+                _ => unreachable!(),
+            };
+            Image::with_delay(buffer, frame.delay)
+        })
+        .collect()
+}
+
+// These are shared by the gui and the headless batch runner so both produce the same pixels.
+// They return the new frames and leave the image as it is.
+impl ImageData {
+    pub fn resize(&self, size: Vector2<u32>, resample: FilterType) -> Vec<Image> {
+        self.frames
+            .iter()
+            .map(|image| {
+                let buffer = image.buffer().resize_exact(size.x, size.y, resample);
+                Image::with_delay(buffer, image.delay)
+            })
+            .collect()
+    }
+
+    pub fn color(
+        &self,
+        hue: f32,
+        saturation: f32,
+        contrast: f32,
+        brightness: f32,
+        grayscale: bool,
+        invert: bool,
+    ) -> Vec<Image> {
+        let mut new = self.frames.clone();
+        for image in &mut new {
+            let buffer = image.buffer_mut();
+
+            if hue != 0.0 {
+                huerotate_in_place(buffer, hue as i32);
+            }
+
+            if contrast != 0.0 {
+                contrast_in_place(buffer, contrast);
+            }
+
+            if saturation != 0.0 {
+                adjust_saturation_in_place(buffer, saturation as f64);
+            }
+
+            if brightness != 0.0 {
+                brighten_in_place(buffer, brightness as f64);
+            }
+
+            if grayscale {
+                *buffer = DynamicImage::ImageLumaA8(imageops::grayscale(buffer));
+            }
+
+            if invert {
+                buffer.invert();
+            }
+        }
+        new
+    }
+
+    /// Returns `None` for color types that are not in [`COLOR_SPACES`].
+    pub fn color_space(&self, color_type: ColorType) -> Option<Vec<Image>> {
+        if !COLOR_SPACES.contains(&color_type) {
+            return None;
+        }
+        let frames = self
+            .frames
+            .iter()
+            .map(|image| {
+                let buffer = image.buffer();
+                let buffer = match color_type {
+                    ColorType::L8 => DynamicImage::ImageLuma8(buffer.to_luma8()),
+                    ColorType::La8 => DynamicImage::ImageLumaA8(buffer.to_luma_alpha8()),
+                    ColorType::Rgb8 => DynamicImage::ImageRgb8(buffer.to_rgb8()),
+                    ColorType::Rgba8 => DynamicImage::ImageRgba8(buffer.to_rgba8()),
+                    ColorType::L16 => DynamicImage::ImageLuma16(buffer.to_luma16()),
+                    ColorType::La16 => DynamicImage::ImageLumaA16(buffer.to_luma_alpha16()),
+                    ColorType::Rgb16 => DynamicImage::ImageRgb16(buffer.to_rgb16()),
+                    ColorType::Rgba16 => DynamicImage::ImageRgba16(buffer.to_rgba16()),
+                    ColorType::Rgb32F => DynamicImage::ImageRgb32F(buffer.to_rgb32f()),
+                    _ => DynamicImage::ImageRgba32F(buffer.to_rgba32f()),
+                };
+                Image::with_delay(buffer, image.delay)
+            })
+            .collect();
+        Some(frames)
+    }
+
+    /// Rotates clockwise in steps of 90 degrees.
+    pub fn rotate(&self, rotation: i32) -> Vec<Image> {
+        rotate_frames(&self.frames, rotation)
+    }
+
+    /// `position` and `size` are in the coordinates of the image after it has been rotated
+    /// by `rotation`.
+    pub fn crop(&self, position: Vector2<u32>, size: Vector2<u32>, rotation: i32) -> Vec<Image> {
+        self.rotate(rotation)
+            .into_iter()
+            .map(|frame| {
+                let image = frame
+                    .buffer()
+                    .crop_imm(position.x, position.y, size.x, size.y);
+                Image::with_delay(image, frame.delay)
+            })
+            .collect()
+    }
+
+    /// The frames for another layer or channel of an OpenEXR file.
+    pub fn exr_layer(&self, selection: ExrSelection) -> Option<Vec<Image>> {
+        let image = self.exr.as_ref()?.image(selection)?;
+        Some(vec![Image::new(image)])
+    }
+
+    /// The frames for another page of a multi-page file.
+    pub fn page(&self, page: usize) -> Option<Vec<Image>> {
+        let image = self.pages.as_ref()?.get(page)?.clone();
+        Some(vec![image])
+    }
+
+    /// The frames for a Photoshop file with other layers hidden or shown.
+    pub fn psd_composite(&self, visibility: &PsdVisibility) -> Option<Vec<Image>> {
+        let image = self.psd.as_ref()?.composite(visibility)?;
+        Some(vec![Image::new(image)])
+    }
+
+    /// The frames for a camera raw file developed with other settings.
+    pub fn raw_develop(&self, settings: &RawSettings) -> Option<Vec<Image>> {
+        let image = self.raw.as_ref()?.develop(settings)?;
+        Some(vec![Image::new(image)])
+    }
+
+    pub fn flip_horizontal(&self) -> Vec<Image> {
+        let mut new = self.frames.clone();
+        for frame in &mut new {
+            flip_horizontal_in_place(frame.buffer_mut());
+        }
+        new
+    }
+
+    pub fn flip_vertical(&self) -> Vec<Image> {
+        let mut new = self.frames.clone();
+        for frame in &mut new {
+            flip_vertical_in_place(frame.buffer_mut());
+        }
+        new
+    }
+
+    /// The frames without `start..=end`, `None` if that would leave no frames.
+    pub fn delete_frames(&self, start: usize, end: usize) -> Option<Vec<Image>> {
+        let end = end.min(self.frames.len() - 1);
+        if start > end || end - start + 1 >= self.frames.len() {
+            return None;
+        }
+        let mut new = self.frames.clone();
+        new.drain(start..=end);
+        Some(new)
+    }
+
+    /// The frames with a copy of frame `index` right after it.
+    pub fn duplicate_frame(&self, index: usize) -> Option<Vec<Image>> {
+        let frame = self.frames.get(index)?.clone();
+        let mut new = self.frames.clone();
+        new.insert(index + 1, frame);
+        Some(new)
+    }
+
+    pub fn reverse_frames(&self) -> Vec<Image> {
+        self.frames.iter().rev().cloned().collect()
+    }
+
+    /// Sets the delay of frame `index`, or of every frame when it is `None`.
+    pub fn frame_delay(&self, index: Option<usize>, delay: Duration) -> Vec<Image> {
+        let mut new = self.frames.clone();
+        for (i, frame) in new.iter_mut().enumerate() {
+            if index.is_none_or(|index| index == i) {
+                frame.delay = delay;
+            }
+        }
+        new
+    }
+}
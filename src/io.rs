@@ -0,0 +1,118 @@
+//! Decoding and encoding with the same loaders and encoders as the simp viewer.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "avif")]
+pub use crate::image_io::save::AvifOptions;
+#[cfg(feature = "heif")]
+pub use crate::image_io::save::HeifOptions;
+#[cfg(feature = "jxl")]
+pub use crate::image_io::save::JxlOptions;
+pub use crate::image_io::{
+    Image, ImageData, RawMetadata,
+    exr::{ExrChannel, ExrLayer, ExrLayers, ExrSelection},
+    extensions,
+    load::{LoadError, LoadOptions},
+    photoshop::{BlendMode, PsdGroup, PsdLayer, PsdLayers, PsdVisibility},
+    raw::{RawFile, RawSettings, WhiteBalance},
+    save::{
+        AvifBitDepth, GifOptions, GifRepeat, JpegOptions, JpegSubsampling, PngCompression,
+        PngFilter, PngOptions, SaveError, SaveOptions, TiffCompression, TiffOptions, WebpOptions,
+    },
+    svg::Svg,
+    thumbnail,
+    tonemap::{self, ToneMapOperator},
+    transform::{COLOR_SPACES, rotate_frames},
+};
+use crate::image_io::{load, save};
+
+/// Loads an image from disk. The extension is used to pick which loader is tried first.
+pub fn load(path: impl AsRef<Path>) -> Result<ImageData, LoadError> {
+    load::load_uncached(path, LoadOptions::default())
+}
+
+/// Loads an image from memory. `hint` is a file name whose extension is used to pick which loader is tried first.
+pub fn load_bytes(bytes: &[u8], hint: Option<&Path>) -> Result<ImageData, LoadError> {
+    load::load_from_bytes(bytes, hint.map(Path::to_path_buf), LoadOptions::default())
+}
+
+/// Same as [`load`] with custom loader settings.
+pub fn load_with_options(
+    path: impl AsRef<Path>,
+    options: LoadOptions,
+) -> Result<ImageData, LoadError> {
+    load::load_uncached(path, options)
+}
+
+/// Same as [`load_bytes`] with custom loader settings.
+pub fn load_bytes_with_options(
+    bytes: &[u8],
+    hint: Option<&Path>,
+    options: LoadOptions,
+) -> Result<ImageData, LoadError> {
+    load::load_from_bytes(bytes, hint.map(Path::to_path_buf), options)
+}
+
+/// Same as [`load_bytes_with_options`], also returns the name of the loader that decoded the image.
+pub fn load_bytes_with_loader(
+    bytes: &[u8],
+    hint: Option<&Path>,
+    options: LoadOptions,
+) -> Result<(ImageData, &'static str), LoadError> {
+    load::load_with_loader(bytes, hint.map(Path::to_path_buf), options)
+}
+
+/// Encodes all frames of `image_data` with the encoder picked from the extension of `path`.
+/// Unknown extensions are saved as png. Returns the path that was actually written.
+/// The exif, icc and xmp blocks of `image_data` are kept where the format supports them.
+pub fn save(
+    path: impl AsRef<Path>,
+    image_data: &ImageData,
+    options: SaveOptions,
+) -> Result<PathBuf, SaveError> {
+    save::save(
+        path.as_ref().to_path_buf(),
+        image_data.frames.clone(),
+        &image_data.raw_metadata,
+        options,
+    )
+}
+
+/// Encodes every page of a multi-page image, falling back to the frames for other images.
+/// TIFF keeps all pages in one file, other formats get one numbered file per page.
+pub fn save_pages(
+    path: impl AsRef<Path>,
+    image_data: &ImageData,
+    options: SaveOptions,
+) -> Result<(), SaveError> {
+    let pages = match &image_data.pages {
+        Some(pages) => pages.to_vec(),
+        None => image_data.frames.clone(),
+    };
+    save::save_pages(
+        path.as_ref().to_path_buf(),
+        pages,
+        &image_data.raw_metadata,
+        options,
+    )
+}
+
+/// Same as [`save`] for frames that are not part of an [`ImageData`], such as edited ones.
+pub fn save_frames(
+    path: impl AsRef<Path>,
+    frames: Vec<Image>,
+    metadata: &RawMetadata,
+    options: SaveOptions,
+) -> Result<PathBuf, SaveError> {
+    save::save(path.as_ref().to_path_buf(), frames, metadata, options)
+}
+
+/// Same as [`save_pages`] for pages that are not part of an [`ImageData`].
+pub fn save_frames_as_pages(
+    path: impl AsRef<Path>,
+    pages: Vec<Image>,
+    metadata: &RawMetadata,
+    options: SaveOptions,
+) -> Result<(), SaveError> {
+    save::save_pages(path.as_ref().to_path_buf(), pages, metadata, options)
+}
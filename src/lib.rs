@@ -1,3 +1,5 @@
 mod cli;
+mod image_io;
+pub mod io;
 
 pub use cli::get_clap_command;
@@ -10,9 +10,9 @@ use std::{
 };
 
 use cgmath::Vector2;
-use cli::get_clap_command;
 use egui::ViewportId;
 use serde::{Deserialize, Serialize};
+use simp::get_clap_command;
 
 mod app;
 use app::{
@@ -30,8 +30,7 @@ use winit::{
     keyboard::{Key, ModifiersState},
     window::{Fullscreen, Window, WindowAttributes},
 };
-mod cli;
-mod image_io;
+mod headless;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct Config {
@@ -348,7 +347,9 @@ impl WindowHandler {
                                     multiview_mask: None,
                                 });
 
-                            if let Some(image) = app.image_view.as_mut() {
+                            if !app.gallery_visible()
+                                && let Some(image) = app.image_view.as_mut()
+                            {
                                 let uniform = image.get_uniform(Vector2::new(
                                     wgpu.config.width as f32,
                                     wgpu.config.height as f32,
@@ -476,13 +477,24 @@ fn main() {
         println!("{}", panic_info);
     }));
 
-    let matches = cli::get_clap_command().get_matches();
+    let matches = get_clap_command().get_matches();
 
     if matches.get_flag("generate-man") {
         generate_man();
         return;
     }
 
+    if let Some((name, matches)) = matches.subcommand() {
+        headless::init();
+        let code = match name {
+            "convert" => headless::convert(matches),
+            "batch" => headless::batch(matches),
+            "info" => headless::info(matches),
+            _ => unreachable!("unknown subcommand {name}"),
+        };
+        std::process::exit(code);
+    }
+
     let path: Option<&String> = matches.get_one("file");
     let fullscreen: bool = matches.get_flag("fullscreen");
     let zen_mode: bool = matches.get_flag("zen-mode");
@@ -70,4 +70,12 @@ impl Rect {
         let y = ((self.y() + self.height() / 2.0) - (rect.y() + rect.height() / 2.0)).abs() * 2.0 < (self.height() + rect.height());
         x && y
     }
+
+    #[inline]
+    pub fn contains(&self, rect: &Self) -> bool {
+        self.left() <= rect.left()
+            && self.top() <= rect.top()
+            && self.right() >= rect.right()
+            && self.bottom() >= rect.bottom()
+    }
 }
@@ -12,6 +12,9 @@ layout(std140, set = 0, binding = 0) uniform InputUniform {
     float saturation;
     uint grayscale;
     uint invert;
+    float exposure;
+    uint tonemap;
+    float gamma;
 };
 layout(set = 1, binding = 0) uniform texture2D t_diffuse;
 layout(set = 1, binding = 1) uniform sampler s_diffuse;
@@ -127,6 +130,51 @@ vec3 adjustSaturation(vec3 p, float sat) {
     return hsl2rgb(hsl);
 }
 
+// The tone mapping must match src/app/op_queue/tonemap.rs so applying gives the displayed pixels.
+vec3 adjustExposure(vec3 p, float stops) {
+    return max(p, 0) * exp2(stops);
+}
+
+vec3 reinhard(vec3 p) {
+    return p / (1.0 + p);
+}
+
+vec3 acesFilmic(vec3 p) {
+    return clamp((p * (2.51 * p + 0.03)) / (p * (2.43 * p + 0.59) + 0.14), 0, 1);
+}
+
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+vec3 agx(vec3 p) {
+    const mat3 inset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+    const mat3 outset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+    const float min_ev = -12.47393;
+    const float max_ev = 4.026069;
+
+    vec3 x = inset * p;
+    x = (clamp(log2(max(x, 1e-10)), min_ev, max_ev) - min_ev) / (max_ev - min_ev);
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+    x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    return pow(max(outset * x, 0), vec3(2.2));
+}
+
+vec3 toneMap(vec3 p, uint operator) {
+    if(operator == 1) {
+        return reinhard(p);
+    } else if(operator == 2) {
+        return acesFilmic(p);
+    } else if(operator == 3) {
+        return agx(p);
+    }
+    return min(p, 1);
+}
+
 vec3 invertRgb(vec3 p) {
     return vec3(1.0 - p.r, 1.0 - p.g, 1.0 - p.b);
 }
@@ -148,6 +196,9 @@ vec3 getCheckColor() {
 
 void main() {
     vec4 p = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    p.rgb = adjustExposure(p.rgb, exposure);
+    p.rgb = toneMap(p.rgb, tonemap);
+    p.rgb = min(pow(max(p.rgb, 0), vec3(1.0 / gamma)), 1);
     p = fromLinear(p);
 
     p.rgb = rotateHue(p.rgb, hue);
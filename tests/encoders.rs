@@ -0,0 +1,356 @@
+//! Every encoder writes a file that decodes back to the same image.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::{DynamicImage, GenericImageView, GrayImage, Rgba, RgbaImage};
+use simp::io::{
+    GifOptions, Image, ImageData, JpegOptions, PngCompression, PngOptions, SaveOptions,
+    TiffCompression, TiffOptions, WebpOptions,
+};
+
+/// A file in the temp dir that is removed again when the test is done.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        let name = format!("simp-test-{}-{name}", std::process::id());
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn gradient(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([
+            (x * 255 / width) as u8,
+            (y * 255 / height) as u8,
+            ((x + y) * 4) as u8,
+            255,
+        ])
+    }))
+}
+
+fn save_and_load(path: &Path, frames: Vec<Image>, options: SaveOptions) -> ImageData {
+    let written = simp::io::save(path, &ImageData::from(frames), options).unwrap();
+    assert_eq!(written, path);
+    simp::io::load(path).unwrap()
+}
+
+/// Mean absolute difference of the rgba channels.
+fn difference(a: &DynamicImage, b: &DynamicImage) -> f64 {
+    assert_eq!(a.dimensions(), b.dimensions());
+    let (a, b) = (a.to_rgba8(), b.to_rgba8());
+    let sum: u64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum();
+    sum as f64 / a.as_raw().len() as f64
+}
+
+#[test]
+fn png_is_lossless() {
+    let image = gradient(64, 48);
+    for compression in [PngCompression::Uncompressed, PngCompression::Best] {
+        let file = TempFile::new("lossless.png");
+        let options = SaveOptions::Png(PngOptions {
+            compression,
+            ..Default::default()
+        });
+        let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+        assert_eq!(loaded.frames[0].buffer().to_rgba8(), image.to_rgba8());
+    }
+}
+
+#[test]
+fn apng_keeps_frames_and_delays() {
+    let file = TempFile::new("animated.png");
+    let frames = vec![
+        Image::with_delay(gradient(32, 32), Duration::from_millis(100)),
+        Image::with_delay(gradient(32, 32).fliph(), Duration::from_millis(250)),
+    ];
+    let loaded = save_and_load(&file.0, frames.clone(), SaveOptions::Default);
+    assert_eq!(loaded.frames.len(), 2);
+    for (loaded, frame) in loaded.frames.iter().zip(&frames) {
+        assert_eq!(loaded.delay, frame.delay);
+        assert_eq!(loaded.buffer().to_rgba8(), frame.buffer().to_rgba8());
+    }
+}
+
+#[test]
+fn tiff_is_lossless() {
+    let image = gradient(64, 48);
+    for compression in [
+        TiffCompression::Uncompressed,
+        TiffCompression::Lzw,
+        TiffCompression::Deflate,
+        TiffCompression::Packbits,
+    ] {
+        let file = TempFile::new("lossless.tiff");
+        let options = SaveOptions::Tiff(TiffOptions { compression });
+        let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+        assert_eq!(loaded.frames[0].buffer().to_rgba8(), image.to_rgba8());
+    }
+}
+
+#[test]
+fn webp_lossless_and_lossy() {
+    let image = gradient(64, 48);
+
+    let file = TempFile::new("lossless.webp");
+    let options = SaveOptions::WebP(WebpOptions {
+        lossy: false,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert_eq!(loaded.frames[0].buffer().to_rgba8(), image.to_rgba8());
+
+    let file = TempFile::new("lossy.webp");
+    let options = SaveOptions::WebP(WebpOptions {
+        lossy: true,
+        quality: 90.0,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert!(difference(loaded.frames[0].buffer(), &image) < 4.0);
+}
+
+#[test]
+fn jpeg_is_close_to_the_source() {
+    let image = gradient(64, 48);
+    let file = TempFile::new("color.jpg");
+    let options = SaveOptions::Jpeg(JpegOptions {
+        quality: 95,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert!(difference(loaded.frames[0].buffer(), &image) < 3.0);
+}
+
+#[test]
+fn jpeg_keeps_grayscale() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(40, 30, |x, _| [(x * 6) as u8].into()));
+    let file = TempFile::new("gray.jpg");
+    let loaded = save_and_load(
+        &file.0,
+        vec![Image::new(image.clone())],
+        SaveOptions::Default,
+    );
+    assert!(!loaded.frames[0].buffer().color().has_color());
+    assert!(difference(loaded.frames[0].buffer(), &image) < 3.0);
+}
+
+#[test]
+fn jpeg_rejects_images_that_are_too_wide() {
+    let file = TempFile::new("wide.jpg");
+    let image = DynamicImage::ImageLuma8(GrayImage::new(65536, 1));
+    let res = simp::io::save(
+        &file.0,
+        &ImageData::from(vec![Image::new(image)]),
+        SaveOptions::Default,
+    );
+    assert!(res.is_err());
+    assert!(!file.0.exists());
+}
+
+#[test]
+fn gif_keeps_frames_and_delays() {
+    let file = TempFile::new("animated.gif");
+    let frames = vec![
+        Image::with_delay(gradient(32, 32), Duration::from_millis(100)),
+        Image::with_delay(gradient(32, 32).flipv(), Duration::from_millis(300)),
+    ];
+    let options = SaveOptions::Gif(GifOptions {
+        speed: 1,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, frames.clone(), options);
+    assert_eq!(loaded.frames.len(), 2);
+    for (loaded, frame) in loaded.frames.iter().zip(&frames) {
+        assert_eq!(loaded.delay, frame.delay);
+        assert!(difference(loaded.buffer(), frame.buffer()) < 8.0);
+    }
+}
+
+#[test]
+fn gif_global_palette_covers_every_frame() {
+    let file = TempFile::new("global.gif");
+    let frames: Vec<_> = [gradient(32, 32), gradient(32, 32).fliph().flipv()]
+        .into_iter()
+        .map(|image| Image::with_delay(image, Duration::from_millis(100)))
+        .collect();
+    let options = SaveOptions::Gif(GifOptions {
+        speed: 1,
+        global_palette: true,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, frames.clone(), options);
+    assert_eq!(loaded.frames.len(), 2);
+    for (loaded, frame) in loaded.frames.iter().zip(&frames) {
+        assert!(difference(loaded.buffer(), frame.buffer()) < 8.0);
+    }
+}
+
+#[cfg(feature = "jxl")]
+#[test]
+fn jxl_lossless() {
+    let image = gradient(64, 48);
+    let file = TempFile::new("lossless.jxl");
+    let options = SaveOptions::Jxl(simp::io::JxlOptions {
+        lossy: false,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert_eq!(loaded.frames[0].buffer().to_rgba8(), image.to_rgba8());
+}
+
+#[cfg(feature = "jxl")]
+#[test]
+fn jxl_keeps_icc_profile() {
+    let icc = moxcms::ColorProfile::new_display_p3().encode().unwrap();
+    for frames in [1, 2] {
+        let file = TempFile::new("icc.jxl");
+        let frames = (0..frames)
+            .map(|_| Image::with_delay(gradient(32, 32), Duration::from_millis(100)))
+            .collect();
+        let mut image = ImageData::from(frames);
+        image.raw_metadata.icc = Some(icc.clone());
+        let options = SaveOptions::Jxl(simp::io::JxlOptions {
+            lossy: false,
+            ..Default::default()
+        });
+        simp::io::save(&file.0, &image, options).unwrap();
+        let loaded = simp::io::load(&file.0).unwrap();
+        assert_eq!(loaded.raw_metadata.icc.as_ref(), Some(&icc));
+    }
+}
+
+/// Goes through the frame writer that drives libjxl directly. Only the first frame is
+/// loaded back.
+#[cfg(feature = "jxl")]
+#[test]
+fn jxl_animation_is_lossless() {
+    let file = TempFile::new("animated.jxl");
+    let frames = vec![
+        Image::with_delay(gradient(32, 24), Duration::from_millis(100)),
+        Image::with_delay(gradient(32, 24).fliph(), Duration::from_millis(250)),
+    ];
+    let options = SaveOptions::Jxl(simp::io::JxlOptions {
+        lossy: false,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, frames.clone(), options);
+    assert_eq!(
+        loaded.frames[0].buffer().to_rgba8(),
+        frames[0].buffer().to_rgba8()
+    );
+}
+
+#[cfg(feature = "jxl")]
+#[test]
+fn jxl_animation_keeps_sixteen_bits() {
+    let image = DynamicImage::ImageRgba16(gradient(32, 24).to_rgba16());
+    let file = TempFile::new("sixteen-bit.jxl");
+    let frames = vec![
+        Image::with_delay(image.clone(), Duration::from_millis(100)),
+        Image::with_delay(image.flipv(), Duration::from_millis(100)),
+    ];
+    let options = SaveOptions::Jxl(simp::io::JxlOptions {
+        lossy: false,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, frames, options);
+    assert_eq!(loaded.frames[0].buffer().to_rgba16(), image.to_rgba16());
+}
+
+#[cfg(feature = "jxl")]
+#[test]
+fn jxl_animation_rejects_frames_of_different_sizes() {
+    let file = TempFile::new("sizes.jxl");
+    let frames = vec![Image::new(gradient(32, 24)), Image::new(gradient(24, 32))];
+    let res = simp::io::save(&file.0, &ImageData::from(frames), SaveOptions::Default);
+    assert!(res.is_err());
+    assert!(!file.0.exists());
+}
+
+#[cfg(feature = "avif")]
+#[test]
+fn avif_is_close_to_the_source() {
+    let image = gradient(64, 48);
+    let file = TempFile::new("lossy.avif");
+    let options = SaveOptions::Avif(simp::io::AvifOptions {
+        quality: 95,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert!(difference(loaded.frames[0].buffer(), &image) < 4.0);
+}
+
+#[cfg(feature = "avif")]
+#[test]
+fn avif_ten_bit_is_close_to_the_source() {
+    let image = DynamicImage::ImageRgba16(gradient(64, 48).to_rgba16());
+    let file = TempFile::new("ten-bit.avif");
+    let options = SaveOptions::Avif(simp::io::AvifOptions {
+        quality: 95,
+        bit_depth: simp::io::AvifBitDepth::Ten,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert!(difference(loaded.frames[0].buffer(), &image) < 4.0);
+}
+
+#[cfg(feature = "heif")]
+#[test]
+fn heif_is_close_to_the_source() {
+    let image = gradient(64, 48);
+    let file = TempFile::new("lossy.heic");
+    let options = SaveOptions::Heif(simp::io::HeifOptions {
+        quality: 95,
+        ..Default::default()
+    });
+    let loaded = save_and_load(&file.0, vec![Image::new(image.clone())], options);
+    assert!(difference(loaded.frames[0].buffer(), &image) < 4.0);
+}
+
+#[cfg(feature = "heif")]
+#[test]
+fn heif_keeps_icc_profile() {
+    let icc = moxcms::ColorProfile::new_display_p3().encode().unwrap();
+    let file = TempFile::new("icc.heic");
+    let mut image = ImageData::from(vec![Image::new(gradient(32, 32))]);
+    image.raw_metadata.icc = Some(icc.clone());
+    simp::io::save(&file.0, &image, SaveOptions::Heif(Default::default())).unwrap();
+    let loaded = simp::io::load(&file.0).unwrap();
+    assert_eq!(loaded.raw_metadata.icc.as_ref(), Some(&icc));
+}
+
+#[test]
+fn saving_no_frames_is_an_error() {
+    let file = TempFile::new("empty.png");
+    let res = simp::io::save(&file.0, &ImageData::from(Vec::new()), SaveOptions::Default);
+    assert!(res.is_err());
+    assert!(!file.0.exists());
+}
+
+#[test]
+fn options_for_another_format_are_an_error() {
+    let file = TempFile::new("mismatch.png");
+    let res = simp::io::save(
+        &file.0,
+        &ImageData::from(vec![Image::new(gradient(8, 8))]),
+        SaveOptions::Jpeg(JpegOptions::default()),
+    );
+    assert!(res.is_err());
+    assert!(!file.0.exists());
+}